@@ -1,26 +1,33 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::fs;
 use std::io::{self, BufRead, BufReader, Stderr, Write};
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
-use chrono::Local;
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use clap::{Parser, Subcommand, ValueEnum};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
     terminal::{self, Clear, ClearType},
     ExecutableCommand, QueueableCommand,
 };
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 // Cached regex patterns
+/// `name-YYYY-MM-DD`, optionally with the `-N` counter `dated_name` adds.
 fn date_suffix_regex() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
-    RE.get_or_init(|| Regex::new(r"^(.+)-(\d{4}-\d{2}-\d{2})$").unwrap())
+    RE.get_or_init(|| Regex::new(r"^(.+)-(\d{4}-\d{2}-\d{2})(-\d+)?$").unwrap())
 }
 
 fn git_url_regex() -> &'static Regex {
@@ -31,1613 +38,11791 @@ fn git_url_regex() -> &'static Regex {
 // Keep the CLI version in sync with Cargo.toml's [package] version.
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Errors surfaced by the workspace store, clone, and script-emission paths.
+/// `main` still works in `anyhow::Result`; these typed variants let callers
+/// (and tests) `match` on specific failure conditions.
+#[derive(Debug, Error)]
+enum TryError {
+    #[error("config file '{0}' is corrupted: {1}")]
+    ConfigCorrupted(PathBuf, String),
+    #[error(
+        "config file '{}' is format v{found}, but this try only understands up to v{supported}; upgrade try",
+        .path.display()
+    )]
+    ConfigTooNew {
+        path: PathBuf,
+        found: u32,
+        supported: u32,
+    },
+    #[error("workspace '{}' is not in the history", .0.display())]
+    WorkspaceNotFound(PathBuf),
+    #[error("workspace path '{}' is unavailable: {reason}", .path.display())]
+    WorkspaceUnavailable { path: PathBuf, reason: String },
+    #[error("'{}' is not a directory", .0.display())]
+    NotADirectory(PathBuf),
+    #[error("invalid git url: {0}")]
+    InvalidGitUrl(String),
+    #[error("--into '{0}' must be a relative path without '..'")]
+    InvalidSubdir(String),
+    #[error("clone directory name '{0}' must be a single directory name, without '/' or '..'")]
+    InvalidCloneName(String),
+    #[error("`{command}` failed: {reason}")]
+    CommandFailed { command: String, reason: String },
+    #[error("named pipe '{}' does not exist (create it with `mkfifo`)", .0.display())]
+    FifoMissing(PathBuf),
+    #[error("'{}' is not a named pipe", .0.display())]
+    NotAFifo(PathBuf),
+    #[error("failed to write script to '{}'", .path.display())]
+    ShellEmitFailed {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("no entry in '{}' matches '{name}'", .base.display())]
+    NoMatchingEntry { base: PathBuf, name: String },
+    #[error("'{name}' is ambiguous; it matches: {}", .candidates.join(", "))]
+    AmbiguousEntry {
+        name: String,
+        candidates: Vec<String>,
+    },
+    #[error("no editor configured: pass --editor, set `editor` in config.toml, or set $EDITOR")]
+    EditorNotSet,
+    #[error("'{}' is not inside a cloned workspace (one with an `origin` remote)", .0.display())]
+    NotAClone(PathBuf),
+    #[error("{} of {total} pulls failed:\n  {}", .failed.len(), .failed.join("\n  "))]
+    PullsFailed { failed: Vec<String>, total: usize },
+    #[error(
+        "post_clone_cmd contains '{pattern}'; keep it to one command, or set \
+         `unsafe_post_clone = true` in config.toml"
+    )]
+    UnsafePostClone { pattern: &'static str },
+    #[error("'{}' is not a try archive (expected a single top-level directory)", .0.display())]
+    NotAnArchive(PathBuf),
+    #[error("'{}' already exists; move it away before restoring", .0.display())]
+    RestoreTargetExists(PathBuf),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
 fn today_suffix() -> String {
     Local::now().format("%Y-%m-%d").to_string()
 }
 
+/// `name-YYYY-MM-DD` for a new directory next to `target`, or
+/// `name-YYYY-MM-DD-2`, `-3`, ... when that is taken, so a second `foo` on
+/// the same day gets its own directory.
+fn dated_name(target: &Path, name: &str) -> String {
+    let dated = format!("{}-{}", name, today_suffix());
+    (1..)
+        .map(|n| match n {
+            1 => dated.clone(),
+            n => format!("{}-{}", dated, n),
+        })
+        .find(|d| !target.with_file_name(d).exists())
+        .unwrap_or(dated)
+}
+
+/// On-disk format version of the workspaces file, recorded in its first
+/// line. Bump it together with a new `migrate_vN_to_vM` step.
+const WORKSPACES_VERSION: u32 = 4;
+const WORKSPACES_HEADER: &str = "# try-rs workspaces v";
+
+/// v1 only introduced the version header; the path-per-line body is as-is.
+fn migrate_v0_to_v1(lines: Vec<String>) -> Vec<String> {
+    lines
+}
+
+/// v2 prefixes each path with its last access time, unknown for old entries.
+fn migrate_v1_to_v2(lines: Vec<String>) -> Vec<String> {
+    lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| format!("-\t{}", line.trim()))
+        .collect()
+}
+
+/// v3 adds a group column between the access time and the path (empty for
+/// ungrouped workspaces).
+fn migrate_v2_to_v3(lines: Vec<String>) -> Vec<String> {
+    lines
+        .iter()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(secs, path)| format!("{}\t\t{}", secs, path))
+        .collect()
+}
+
+/// v4 adds a sort weight column before the path (empty for no weight).
+fn migrate_v3_to_v4(lines: Vec<String>) -> Vec<String> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let (secs, rest) = line.split_once('\t')?;
+            let (group, path) = rest.split_once('\t')?;
+            Some(format!("{}\t{}\t\t{}", secs, group, path))
+        })
+        .collect()
+}
+
+/// One remembered workspace root.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct WorkspaceRecord {
+    path: PathBuf,
+    /// When it was last chosen (`try set`, `init`). Unlike the directory's
+    /// mtime, this doesn't move when files inside change.
+    last_accessed: Option<SystemTime>,
+    /// Client/project group (`init --group`, `$TRY_GROUP`).
+    group: Option<String>,
+    /// Added to the entry's score in `try set` (`try weight`); negative
+    /// sinks it.
+    sort_weight: f64,
+    /// The `workspaces.d/` file a shared workspace came from; `None` for the
+    /// user's own history. Never written back.
+    source: Option<String>,
+}
+
+impl WorkspaceRecord {
+    /// `<unix seconds or ->\t<group>\t<weight>\t<path>`; the path goes last
+    /// so it may contain tabs.
+    fn to_line(&self) -> String {
+        let secs = self
+            .last_accessed
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let weight = if self.sort_weight == 0.0 {
+            String::new()
+        } else {
+            self.sort_weight.to_string()
+        };
+        format!(
+            "{}\t{}\t{}\t{}",
+            secs,
+            self.group.as_deref().unwrap_or(""),
+            weight,
+            self.path.to_string_lossy()
+        )
+    }
+
+    fn parse_line(line: &str) -> Option<WorkspaceRecord> {
+        let mut fields = line.splitn(4, '\t');
+        let (secs, group, weight, path) = (
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        let last_accessed = match secs {
+            "-" => None,
+            secs => Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?)),
+        };
+        let sort_weight = match weight {
+            "" => 0.0,
+            weight => weight.parse().ok().filter(|w: &f64| w.is_finite())?,
+        };
+        Some(WorkspaceRecord {
+            path: PathBuf::from(path.trim()),
+            last_accessed,
+            group: (!group.is_empty()).then(|| group.to_string()),
+            sort_weight,
+            source: None,
+        })
+    }
+}
+
+/// A `workspaces.d/*.toml` file: shared workspaces, one table each.
+///
+/// ```toml
+/// [[workspace]]
+/// path = "~/ci/clones"
+/// group = "ci"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SharedWorkspaces {
+    workspace: Vec<SharedWorkspace>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SharedWorkspace {
+    path: String,
+    group: Option<String>,
+}
+
+/// Upper bound on remembered workspaces; the oldest fall off the end.
+const MAX_HISTORY: usize = 200;
+
+/// How many directory visits `state.toml` remembers for recency.
+const MAX_VISITS: usize = 500;
+
+/// Remembers the last permissions warning shown, so it isn't repeated on
+/// every run.
+const PERMISSIONS_WARNED_FILE: &str = ".permissions-warned";
+
+/// The workspace event log, one JSON `WorkspaceEvent` per line.
+const EVENTS_FILE: &str = "events.jsonl";
+
+/// Lines the event log keeps; the oldest are dropped beyond this.
+const MAX_EVENTS: usize = 10_000;
+
+/// Size past which the event log is trimmed back to `MAX_EVENTS` lines,
+/// about twice their size, so trimming is rare.
+const EVENTS_TRIM_BYTES: u64 = 4 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EventKind {
+    /// First recorded in the history.
+    Added,
+    /// Dropped from the history (`try gc`, batch delete).
+    Removed,
+    /// Chosen again, or entered with `init --watch`.
+    Accessed,
+    /// A pinned or permanent directory found under a new path.
+    Renamed,
+}
+
+/// One line of the event log, for analytics and outside tools:
+/// `{"kind":"added","path":"/home/me/work","timestamp":1700000000}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct WorkspaceEvent {
+    kind: EventKind,
+    path: PathBuf,
+    /// `Renamed`: the old path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<PathBuf>,
+    /// Unix seconds.
+    #[serde(with = "unix_seconds")]
+    timestamp: SystemTime,
+}
+
+impl WorkspaceEvent {
+    fn now(kind: EventKind, path: &Path) -> Self {
+        WorkspaceEvent {
+            kind,
+            path: path.to_path_buf(),
+            from: None,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+/// `SystemTime` as whole seconds since the epoch.
+mod unix_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime};
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        s.serialize_u64(secs)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<SystemTime, D::Error> {
+        Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(u64::deserialize(d)?))
+    }
+}
+
 struct WorkspaceManager;
 
 impl WorkspaceManager {
     fn get_config_path() -> PathBuf {
-        // Allow overriding the config location (useful for tests and for users
-        // who want a custom location). Falls back to ~/.config/try/workspaces.
-        if let Ok(p) = env::var("TRY_CONFIG") {
-            if !p.trim().is_empty() {
-                return PathBuf::from(p);
-            }
+        Self::config_path_with(env::var("TRY_CONFIG").ok())
+    }
+
+    /// `TRY_CONFIG` overrides the location (the escape hatch for tests and
+    /// CI, which shouldn't touch the real history, and for users who want a
+    /// custom location). Falls back to ~/.config/try/workspaces.
+    fn config_path_with(try_config: Option<String>) -> PathBuf {
+        match try_config.filter(|p| !p.trim().is_empty()) {
+            Some(p) => PathBuf::from(p),
+            None => Self::default_config_path(),
         }
+    }
+
+    fn default_config_path() -> PathBuf {
         dirs::home_dir()
             .map(|home| home.join(".config/try/workspaces"))
             .unwrap_or_else(|| PathBuf::from(".config/try/workspaces"))
     }
 
+    /// The event log for the config directory `config_file` lives in:
+    /// `try-rs/events.jsonl` under the platform data directory
+    /// (`~/.local/share` on Linux) for the default location, else next to
+    /// `config_file`, so a relocated config (`TRY_CONFIG`) keeps its own.
+    fn events_path(config_file: &Path) -> PathBuf {
+        let default_config = Self::default_config_path();
+        match dirs::data_dir() {
+            Some(data) if config_file.parent() == default_config.parent() => {
+                data.join("try-rs").join(EVENTS_FILE)
+            }
+            _ => config_file.with_file_name(EVENTS_FILE),
+        }
+    }
+
+    /// Append `events` to the log. Callers ignore failures: the log must
+    /// never get in the way.
+    fn log_events(config_file: &Path, events: &[WorkspaceEvent]) -> Result<(), TryError> {
+        Self::append_events(&Self::events_path(config_file), events, EVENTS_TRIM_BYTES)
+    }
+
+    /// Append `events` to the log at `path` under its lock. Once the file
+    /// passes `trim_bytes` it is cut back to the newest `MAX_EVENTS` lines
+    /// and replaced atomically; until then an event costs one small write.
+    fn append_events(
+        path: &Path,
+        events: &[WorkspaceEvent],
+        trim_bytes: u64,
+    ) -> Result<(), TryError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut text = String::new();
+        for event in events {
+            text.push_str(&serde_json::to_string(event).map_err(io::Error::other)?);
+            text.push('\n');
+        }
+        Self::locked(path, || {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            file.write_all(text.as_bytes())?;
+            if file.metadata()?.len() <= trim_bytes {
+                return Ok(());
+            }
+            let text = fs::read_to_string(path)?;
+            let lines: Vec<&str> = text.lines().collect();
+            let keep = &lines[lines.len().saturating_sub(MAX_EVENTS)..];
+            let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+            fs::write(&tmp, keep.join("\n") + "\n")?;
+            fs::rename(&tmp, path).inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })?;
+            Ok(())
+        })
+    }
+
+    /// A warning when the workspaces file is readable by other users: its
+    /// paths can give away client or project names.
+    fn check_config_permissions() -> Option<String> {
+        Self::permissions_warning(&Self::get_config_path())
+    }
+
+    /// `--fix-permissions`: make the workspaces file readable by its owner only.
+    fn fix_config_permissions() -> Result<PathBuf, TryError> {
+        let config_path = Self::get_config_path();
+        Self::restrict_permissions(&config_path)?;
+        Ok(config_path)
+    }
+
     // --- Path-parameterized core logic (testable without touching the real config) ---
 
-    fn save_workspaces_to(config_path: &Path, workspaces: &[PathBuf]) -> Result<()> {
+    #[cfg(unix)]
+    fn permissions_warning(config_path: &Path) -> Option<String> {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(config_path).ok()?.permissions().mode() & 0o777;
+        (mode & 0o044 != 0).then(|| {
+            format!(
+                "{} is readable by other users (mode {:o}); run `try --fix-permissions` to \
+                 restrict it to you",
+                config_path.display(),
+                mode
+            )
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn permissions_warning(_config_path: &Path) -> Option<String> {
+        None
+    }
+
+    #[cfg(unix)]
+    fn restrict_permissions(config_path: &Path) -> Result<(), TryError> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(config_path, fs::Permissions::from_mode(0o600))?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn restrict_permissions(_config_path: &Path) -> Result<(), TryError> {
+        Ok(())
+    }
+
+    /// Print `warning` unless the sentinel next to the workspaces file says
+    /// it was already shown. A new warning (say, another mode) is shown again.
+    fn warn_once(config_path: &Path, warning: &str) {
+        let sentinel = config_path.with_file_name(PERMISSIONS_WARNED_FILE);
+        if fs::read_to_string(&sentinel).is_ok_and(|seen| seen == warning) {
+            return;
+        }
+        eprintln!("Warning: {}", warning);
+        let _ = fs::write(&sentinel, warning);
+    }
+
+    fn save_records_to(config_path: &Path, records: &[WorkspaceRecord]) -> Result<(), TryError> {
+        let lines: Vec<String> = records.iter().map(WorkspaceRecord::to_line).collect();
+        Self::write_lines(config_path, &lines)
+    }
+
+    /// Write body lines under the current version header. The file is
+    /// written beside the old one and renamed over it, so a reader never
+    /// sees it half-written; its permissions carry over.
+    fn write_lines(config_path: &Path, lines: &[String]) -> Result<(), TryError> {
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        let mut file = std::fs::File::create(config_path)?;
-        for ws in workspaces {
-            writeln!(file, "{}", ws.to_string_lossy())?;
+        let mut text = format!("{}{}\n", WORKSPACES_HEADER, WORKSPACES_VERSION);
+        for line in lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        let tmp = config_path.with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&tmp, text)?;
+        if let Ok(metadata) = fs::metadata(config_path) {
+            let _ = fs::set_permissions(&tmp, metadata.permissions());
         }
+        fs::rename(&tmp, config_path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })?;
         Ok(())
     }
 
-    fn get_workspaces_from(config_path: &Path) -> Result<Vec<PathBuf>> {
+    /// Run a read-modify-write of the history (or the event log) under an
+    /// exclusive lock on `<file>.lock`, so shells updating it at the same
+    /// time queue up instead of dropping each other's changes. A nested call
+    /// for the same file on the same thread (a migration while reading
+    /// inside an update) runs under the lock already held.
+    fn locked<T>(
+        config_path: &Path,
+        update: impl FnOnce() -> Result<T, TryError>,
+    ) -> Result<T, TryError> {
+        thread_local! {
+            static HELD: std::cell::RefCell<Vec<PathBuf>> = const { std::cell::RefCell::new(Vec::new()) };
+        }
+        let lock_path = config_path.with_extension("lock");
+        if HELD.with_borrow(|held| held.contains(&lock_path)) {
+            return update();
+        }
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        lock.lock()?;
+        // The lock goes with the file handle.
+        HELD.with_borrow_mut(|held| held.push(lock_path.clone()));
+        let result = update();
+        HELD.with_borrow_mut(|held| held.retain(|p| *p != lock_path));
+        result
+    }
+
+    fn get_records_from(config_path: &Path) -> Result<Vec<WorkspaceRecord>, TryError> {
+        let lines = Self::read_lines(config_path)?;
+        lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                WorkspaceRecord::parse_line(line).ok_or_else(|| {
+                    TryError::ConfigCorrupted(
+                        config_path.to_path_buf(),
+                        format!("bad workspace line '{}'", line),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// The directory of team-shared workspace lists next to the history.
+    fn shared_dir(config_path: &Path) -> PathBuf {
+        config_path.with_file_name("workspaces.d")
+    }
+
+    /// Workspaces from every `*.txt` (one path per line, `#` comments) and
+    /// `*.toml` file in `dir`, in file name order. These files are managed
+    /// elsewhere and only ever read. A file that can't be read or parsed is
+    /// left out, and its error goes to `skipped`.
+    fn get_shared_records_from(
+        dir: &Path,
+        skipped: &mut Vec<TryError>,
+    ) -> Result<Vec<WorkspaceRecord>, TryError> {
+        let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.is_file())
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        files.sort();
+
+        let mut records = Vec::new();
+        for file in files {
+            let ext = file.extension().and_then(|e| e.to_str());
+            if !matches!(ext, Some("txt" | "toml")) {
+                continue;
+            }
+            let source = file.file_name().map(|n| n.to_string_lossy().to_string());
+            let entries = match Self::read_shared_file(&file, ext == Some("toml")) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    skipped.push(e);
+                    continue;
+                }
+            };
+            records.extend(entries.into_iter().map(|(path, group)| WorkspaceRecord {
+                path: expand_path(&path),
+                group,
+                source: source.clone(),
+                ..Default::default()
+            }));
+        }
+        Ok(records)
+    }
+
+    /// The `(path, group)` pairs listed in one `workspaces.d` file.
+    fn read_shared_file(
+        file: &Path,
+        toml: bool,
+    ) -> Result<Vec<(String, Option<String>)>, TryError> {
+        let corrupted = |e: &dyn std::fmt::Display| {
+            TryError::ConfigCorrupted(file.to_path_buf(), e.to_string())
+        };
+        let content = fs::read_to_string(file).map_err(|e| corrupted(&e))?;
+        if toml {
+            return Ok(toml::from_str::<SharedWorkspaces>(&content)
+                .map_err(|e| corrupted(&e))?
+                .workspace
+                .into_iter()
+                .map(|w| (w.path, w.group))
+                .collect());
+        }
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| (l.to_string(), None))
+            .collect())
+    }
+
+    /// The history followed by the shared workspaces it doesn't already
+    /// hold, for `try set`. A broken shared file is skipped with a warning,
+    /// so one bad drop-in doesn't hide the rest.
+    fn get_merged_records_from(config_path: &Path) -> Result<Vec<WorkspaceRecord>, TryError> {
+        let mut records = Self::get_records_from(config_path)?;
+        let mut skipped = Vec::new();
+        let shared = Self::get_shared_records_from(&Self::shared_dir(config_path), &mut skipped)?;
+        for e in skipped {
+            eprintln!("Warning: {}; skipping it", e);
+        }
+        for shared in shared {
+            if !records.iter().any(|r| r.path == shared.path) {
+                records.push(shared);
+            }
+        }
+        Ok(records)
+    }
+
+    fn get_workspaces_from(config_path: &Path) -> Result<Vec<PathBuf>, TryError> {
+        Ok(Self::get_records_from(config_path)?
+            .into_iter()
+            .map(|r| r.path)
+            .collect())
+    }
+
+    /// Read the body lines of the workspaces file, upgrading older formats in
+    /// place first (the original is kept as `<file>.v<N>.bak`). The upgrade
+    /// runs under the history lock and re-reads the file once it holds it,
+    /// so shells starting together migrate it once.
+    fn read_lines(config_path: &Path) -> Result<Vec<String>, TryError> {
+        let (version, lines) = Self::read_versioned(config_path)?;
+        if version == WORKSPACES_VERSION {
+            return Ok(lines);
+        }
+        Self::locked(config_path, || {
+            let (version, mut lines) = Self::read_versioned(config_path)?;
+            if version == WORKSPACES_VERSION {
+                return Ok(lines);
+            }
+            // An earlier, interrupted upgrade's backup is the real original.
+            let backup = config_path.with_extension(format!("v{}.bak", version));
+            if !backup.exists() {
+                fs::copy(config_path, &backup)?;
+            }
+            let mut v = version;
+            while v < WORKSPACES_VERSION {
+                lines = match v {
+                    0 => migrate_v0_to_v1(lines),
+                    1 => migrate_v1_to_v2(lines),
+                    2 => migrate_v2_to_v3(lines),
+                    3 => migrate_v3_to_v4(lines),
+                    _ => unreachable!("no migration from workspaces v{}", v),
+                };
+                v += 1;
+            }
+            Self::write_lines(config_path, &lines)?;
+            Ok(lines)
+        })
+    }
+
+    /// The workspaces file's format version and body lines, as stored. A
+    /// missing file reads as current and empty.
+    fn read_versioned(config_path: &Path) -> Result<(u32, Vec<String>), TryError> {
         if !config_path.exists() {
-            return Ok(Vec::new());
+            return Ok((WORKSPACES_VERSION, Vec::new()));
         }
 
         let file = std::fs::File::open(config_path)?;
         let reader = BufReader::new(file);
 
-        let mut workspaces = Vec::new();
+        let mut lines = Vec::new();
         for line in reader.lines() {
-            let line = line?;
-            if !line.trim().is_empty() {
-                workspaces.push(PathBuf::from(line.trim()));
+            let line = line.map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => {
+                    TryError::ConfigCorrupted(config_path.to_path_buf(), e.to_string())
+                }
+                _ => TryError::Io(e),
+            })?;
+            lines.push(line);
+        }
+
+        // Files written before versioning have no header: that's v0.
+        let version = match lines
+            .first()
+            .and_then(|l| l.strip_prefix(WORKSPACES_HEADER))
+        {
+            Some(v) => {
+                let v = v.trim().parse::<u32>().map_err(|_| {
+                    TryError::ConfigCorrupted(
+                        config_path.to_path_buf(),
+                        format!("bad version header '{}'", lines[0]),
+                    )
+                })?;
+                lines.remove(0);
+                v
             }
+            None => 0,
+        };
+
+        if version > WORKSPACES_VERSION {
+            return Err(TryError::ConfigTooNew {
+                path: config_path.to_path_buf(),
+                found: version,
+                supported: WORKSPACES_VERSION,
+            });
         }
-        Ok(workspaces)
+        Ok((version, lines))
     }
 
-    fn add_workspace_to(config_path: &Path, path: &Path) -> Result<()> {
-        let abs_path = canonicalize_clean(path);
-        let path_str = abs_path.to_string_lossy().to_string();
+    /// Move `path` to the top of the history and stamp it as accessed now.
+    /// `group` reassigns its group; `None` keeps the current one. A path
+    /// that doesn't exist yet is fine (init may name a base to be created);
+    /// a file is refused.
+    fn add_workspace_to(
+        config_path: &Path,
+        path: &Path,
+        group: Option<&str>,
+    ) -> Result<(), TryError> {
+        if is_non_directory(path) {
+            return Err(TryError::NotADirectory(path.to_path_buf()));
+        }
+        Self::locked(config_path, || {
+            let abs_path = canonicalize_clean(path);
+            let path_str = abs_path.to_string_lossy().to_string();
+
+            let mut records = Self::get_records_from(config_path)?;
+            // Remove if exists to move to top, keeping its group and weight
+            let (mut record, kind) = match records
+                .iter()
+                .position(|r| r.path.to_string_lossy() == path_str)
+            {
+                Some(i) => (records.remove(i), EventKind::Accessed),
+                None => (WorkspaceRecord::default(), EventKind::Added),
+            };
+            let event = WorkspaceEvent::now(kind, &abs_path);
+            record.path = abs_path;
+            record.last_accessed = Some(SystemTime::now());
+            if let Some(group) = group {
+                record.group = Some(group.to_string());
+            }
+            // Insert at the beginning (first position)
+            records.insert(0, record);
+            records.truncate(MAX_HISTORY);
+
+            Self::save_records_to(config_path, &records)?;
+            let _ = Self::log_events(config_path, &[event]);
+            Ok(())
+        })
+    }
 
-        let mut workspaces = Self::get_workspaces_from(config_path)?;
-        // Remove if exists to move to top
-        workspaces.retain(|p| p.to_string_lossy() != path_str);
-        // Insert at the beginning (first position)
-        workspaces.insert(0, abs_path);
+    /// Set the sort weight of the workspace `name` refers to: its path, or
+    /// the final component of exactly one remembered path.
+    fn set_weight_to(config_path: &Path, name: &str, weight: f64) -> Result<PathBuf, TryError> {
+        Self::locked(config_path, || {
+            let mut records = Self::get_records_from(config_path)?;
+            let target = canonicalize_clean(&expand_path(name));
+            let by_name: Vec<usize> = (0..records.len())
+                .filter(|&i| records[i].path.file_name().is_some_and(|n| n == name))
+                .collect();
+            let index = records
+                .iter()
+                .position(|r| r.path == target)
+                .or(match by_name[..] {
+                    [i] => Some(i),
+                    _ => None,
+                })
+                .ok_or_else(|| TryError::WorkspaceNotFound(PathBuf::from(name)))?;
+            records[index].sort_weight = weight;
+            let path = records[index].path.clone();
+            Self::save_records_to(config_path, &records)?;
+            Ok(path)
+        })
+    }
 
-        Self::save_workspaces_to(config_path, &workspaces)
+    /// Stamp the innermost remembered workspace containing `dir` as accessed
+    /// now, in place: unlike `add_workspace_to`, it neither reorders the
+    /// history nor adds unknown directories. Returns the workspace touched.
+    fn touch_workspace_to(config_path: &Path, dir: &Path) -> Result<Option<PathBuf>, TryError> {
+        Self::locked(config_path, || {
+            let dir = canonicalize_clean(dir);
+            let mut records = Self::get_records_from(config_path)?;
+            let Some(record) = records
+                .iter_mut()
+                .filter(|r| dir.starts_with(&r.path))
+                .max_by_key(|r| r.path.components().count())
+            else {
+                return Ok(None);
+            };
+            record.last_accessed = Some(SystemTime::now());
+            let path = record.path.clone();
+            Self::save_records_to(config_path, &records)?;
+            let _ = Self::log_events(
+                config_path,
+                &[WorkspaceEvent::now(EventKind::Accessed, &path)],
+            );
+            Ok(Some(path))
+        })
     }
 
-    fn remove_workspaces_from(config_path: &Path, paths_to_remove: &[PathBuf]) -> Result<()> {
-        let mut workspaces = Self::get_workspaces_from(config_path)?;
+    /// Point the record for `from` at `to`, after the directory was moved.
+    /// Returns whether there was such a record.
+    fn rename_workspace_to(config_path: &Path, from: &Path, to: &Path) -> Result<bool, TryError> {
+        Self::locked(config_path, || {
+            let mut records = Self::get_records_from(config_path)?;
+            let Some(record) = records.iter_mut().find(|r| r.path == from) else {
+                return Ok(false);
+            };
+            record.path = to.to_path_buf();
+            Self::save_records_to(config_path, &records)?;
+            Ok(true)
+        })
+    }
 
-        // Remove matching paths
-        workspaces.retain(|ws| {
-            !paths_to_remove
+    /// `try gc`: drop records whose directory no longer exists. With
+    /// `aggressive`, also order the rest by last access, newest first; the
+    /// first record stays put, since it is the active base path. Returns how
+    /// many records were dropped.
+    fn gc_to(config_path: &Path, aggressive: bool) -> Result<usize, TryError> {
+        Self::locked(config_path, || {
+            let mut records = Self::get_records_from(config_path)?;
+            let (kept, gone): (Vec<_>, Vec<_>) = records.into_iter().partition(|r| r.path.is_dir());
+            records = kept;
+            let removed = gone.len();
+            if aggressive && records.len() > 1 {
+                records[1..].sort_by_key(|r| std::cmp::Reverse(r.last_accessed));
+            }
+            if removed > 0 || aggressive {
+                Self::save_records_to(config_path, &records)?;
+            }
+            let events: Vec<_> = gone
                 .iter()
-                .any(|p| ws.to_string_lossy() == p.to_string_lossy())
-        });
+                .map(|r| WorkspaceEvent::now(EventKind::Removed, &r.path))
+                .collect();
+            let _ = Self::log_events(config_path, &events);
+            Ok(removed)
+        })
+    }
 
-        Self::save_workspaces_to(config_path, &workspaces)
+    fn remove_workspaces_from(
+        config_path: &Path,
+        paths_to_remove: &[PathBuf],
+    ) -> Result<(), TryError> {
+        Self::locked(config_path, || {
+            let mut records = Self::get_records_from(config_path)?;
+
+            if let Some(missing) = paths_to_remove.iter().find(|p| {
+                !records
+                    .iter()
+                    .any(|r| r.path.to_string_lossy() == p.to_string_lossy())
+            }) {
+                return Err(TryError::WorkspaceNotFound(missing.clone()));
+            }
+
+            // Remove matching paths
+            records.retain(|r| {
+                !paths_to_remove
+                    .iter()
+                    .any(|p| r.path.to_string_lossy() == p.to_string_lossy())
+            });
+
+            Self::save_records_to(config_path, &records)?;
+            let events: Vec<_> = paths_to_remove
+                .iter()
+                .map(|p| WorkspaceEvent::now(EventKind::Removed, p))
+                .collect();
+            let _ = Self::log_events(config_path, &events);
+            Ok(())
+        })
+    }
+
+    /// The workspaces `try set` offers (history, then shared), in `order`.
+    /// Ties keep the order before them: recency falls back to the history's
+    /// own, the others to recency. `size_exclude` is as for `try info`.
+    fn list_sorted_from(
+        config_path: &Path,
+        order: SortOrder,
+        size_exclude: &[String],
+    ) -> Result<Vec<WorkspaceRecord>, TryError> {
+        let mut records = Self::get_merged_records_from(config_path)?;
+        records.sort_by_key(|r| std::cmp::Reverse(r.last_accessed));
+        match order {
+            SortOrder::Recency => {}
+            SortOrder::Name => records.sort_by_cached_key(|r| {
+                r.path
+                    .file_name()
+                    .unwrap_or(r.path.as_os_str())
+                    .to_string_lossy()
+                    .to_lowercase()
+            }),
+            SortOrder::Frequency => {
+                let counts = Self::access_counts(&Self::events_path(config_path));
+                records
+                    .sort_by_key(|r| std::cmp::Reverse(counts.get(&r.path).copied().unwrap_or(0)));
+            }
+            SortOrder::Size => {
+                records.sort_by_cached_key(|r| std::cmp::Reverse(dir_size(&r.path, size_exclude)))
+            }
+        }
+        Ok(records)
+    }
+
+    /// How often each workspace was added or chosen, per the event log at
+    /// `events_path`; a rename carries the count to the new path. Lines that
+    /// don't parse are skipped.
+    fn access_counts(events_path: &Path) -> HashMap<PathBuf, usize> {
+        let mut counts = HashMap::new();
+        let Ok(text) = fs::read_to_string(events_path) else {
+            return counts;
+        };
+        for event in text
+            .lines()
+            .filter_map(|l| serde_json::from_str::<WorkspaceEvent>(l).ok())
+        {
+            match event.kind {
+                EventKind::Added | EventKind::Accessed => {
+                    *counts.entry(event.path).or_insert(0) += 1;
+                }
+                EventKind::Renamed => {
+                    if let Some(n) = event.from.and_then(|from| counts.remove(&from)) {
+                        *counts.entry(event.path).or_insert(0) += n;
+                    }
+                }
+                EventKind::Removed => {}
+            }
+        }
+        counts
     }
 
     // --- Convenience wrappers that target the real config path ---
 
-    fn add_workspace(path: &Path) -> Result<()> {
-        Self::add_workspace_to(&Self::get_config_path(), path)
+    fn add_workspace(path: &Path, group: Option<&str>) -> Result<(), TryError> {
+        Self::add_workspace_to(&Self::get_config_path(), path, group)
     }
 
-    fn get_workspaces() -> Result<Vec<PathBuf>> {
+    fn get_workspaces() -> Result<Vec<PathBuf>, TryError> {
         Self::get_workspaces_from(&Self::get_config_path())
     }
 
-    fn remove_workspaces(paths_to_remove: &[PathBuf]) -> Result<()> {
+    fn get_records() -> Result<Vec<WorkspaceRecord>, TryError> {
+        Self::get_merged_records_from(&Self::get_config_path())
+    }
+
+    fn remove_workspaces(paths_to_remove: &[PathBuf]) -> Result<(), TryError> {
         Self::remove_workspaces_from(&Self::get_config_path(), paths_to_remove)
     }
-}
 
-#[derive(Parser)]
-#[command(name = "try")]
-#[command(version = VERSION)]
-#[command(about = "Ephemeral workspace manager", long_about = None)]
-struct Cli {
-    #[command(subcommand)]
-    command: Option<Commands>,
+    fn set_weight(name: &str, weight: f64) -> Result<PathBuf, TryError> {
+        Self::set_weight_to(&Self::get_config_path(), name, weight)
+    }
 
-    /// Optional query for interactive mode
-    #[arg(index = 1)]
-    query: Option<String>,
+    fn touch_workspace(dir: &Path) -> Result<Option<PathBuf>, TryError> {
+        Self::touch_workspace_to(&Self::get_config_path(), dir)
+    }
+
+    fn gc(aggressive: bool) -> Result<usize, TryError> {
+        Self::gc_to(&Self::get_config_path(), aggressive)
+    }
+
+    fn list_sorted(
+        order: SortOrder,
+        size_exclude: &[String],
+    ) -> Result<Vec<WorkspaceRecord>, TryError> {
+        Self::list_sorted_from(&Self::get_config_path(), order, size_exclude)
+    }
 }
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Output shell function definition
-    Init {
-        #[arg(default_value = "~/project/test")]
-        path: String,
-        /// Target shell: bash | powershell (auto-detected if omitted)
-        #[arg(short, long)]
-        shell: Option<String>,
-        /// Wrapper command name (default: `try` on bash, `tr` on PowerShell,
-        /// since `try` is a reserved PowerShell keyword)
-        #[arg(short, long)]
-        name: Option<String>,
-    },
-    /// Clone git repo into date-prefixed directory
-    Clone {
-        url: String,
-        name: Option<String>,
-        #[arg(short, long)]
-        proxy: Option<String>,
-    },
-    /// Select a workspace from history
-    Set,
+/// User settings from `config.toml`, which lives next to the `workspaces`
+/// history file (so `TRY_CONFIG` relocates both). Every key is optional.
+///
+/// ```toml
+/// after_create = "git init"
+///
+/// [bases."~/experiments/js"]
+/// after_create = "npm init -y"
+///
+/// [icons]
+/// "deno.json" = "🦕"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TryConfig {
+    /// Command run inside a newly created directory (`{path}` is replaced
+    /// with the quoted path).
+    after_create: Option<String>,
+    /// Resolve a symlinked base path to its target, so entries (and `cd`)
+    /// use the real location.
+    follow_symlinks: bool,
+    /// How much confirmation batch delete asks for.
+    delete_confirmation: DeleteConfirmation,
+    /// Per-base-path overrides, keyed by path (`~/` is expanded).
+    bases: HashMap<String, BaseConfig>,
+    /// Extra or replacement project icons, keyed by marker file name.
+    icons: HashMap<String, String>,
+    /// Editor for `try open` (falls back to `$EDITOR`).
+    editor: Option<String>,
+    /// Recency bonus for an entry touched just now (default 3).
+    recency_weight: Option<f64>,
+    /// Hours after which the recency bonus has halved (default 3).
+    recency_half_life_hours: Option<f64>,
+    /// Show "shown of total" entry counts in the selector's footer.
+    show_counts: bool,
+    /// How Ctrl-Y copies a path.
+    copy_method: CopyMethod,
+    /// Starting sort order for base paths Ctrl-S hasn't set one for.
+    sort: SortMode,
+    /// Leave the selector as soon as typing narrows it to a single entry.
+    auto_accept_single: bool,
+    /// Entering delete mode takes a double-tap of Delete (default true).
+    delete_confirm_required: Option<bool>,
+    /// Emit a `touch` of the directory after `cd` (default true). The
+    /// selector orders by its own visit record either way.
+    touch_on_cd: Option<bool>,
+    /// Directory name for `try clone` when none is given (default
+    /// `{repo}-{date}`); see `--name-template`.
+    clone_name_template: Option<String>,
+    /// Short names for git hosts in `{host}`, on top of (or replacing) the
+    /// built-in `github.com = "gh"`, `gitlab.com = "gl"` and
+    /// `bitbucket.org = "bb"`.
+    host_aliases: HashMap<String, String>,
+    /// Names (globs) `try info` counts apart from a workspace's own size
+    /// (default `DEFAULT_SIZE_EXCLUDE`).
+    size_exclude: Option<Vec<String>>,
+    /// Command run inside a fresh `try clone` checkout (`{path}` is replaced
+    /// with the quoted path), e.g. `"npm install"`.
+    post_clone_cmd: Option<String>,
+    /// Let `post_clone_cmd` chain, pipe or background commands, or
+    /// substitute output.
+    unsafe_post_clone: bool,
+    /// `try <git-url>` clones (default true); when off, the URL is only
+    /// searched for.
+    clone_on_url: Option<bool>,
+    /// Judge recency by the newest file inside each directory (a bounded
+    /// walk) instead of the directory's own mtime.
+    recency_from_files: bool,
+    /// Where Ctrl-A puts archived workspaces (default `archive/` next to
+    /// config.toml).
+    archive_dir: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-struct TryEntry {
-    basename: String,
-    basename_down: String,
-    path: PathBuf,
-    mtime: SystemTime,
-    score: f64,
+/// `copy_method` in `config.toml`. Every method falls back to saving the
+/// path in a temp file (and saying where) when it can't deliver.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum CopyMethod {
+    /// System clipboard, then a clipboard tool, then OSC 52.
+    #[default]
+    Auto,
+    /// The system clipboard (X11, Wayland, macOS, Windows).
+    Clipboard,
+    /// `wl-copy`, `xclip`, `xsel`, `pbcopy` or `clip.exe`, whichever exists.
+    Command,
+    /// An OSC 52 escape, which the terminal (even over SSH) puts on the
+    /// clipboard, unless `$TERM` suggests it would be ignored.
+    Osc52,
+    /// Only the temp file.
+    File,
 }
 
-enum SelectorMode {
-    Scan(PathBuf),
-    History(Vec<PathBuf>),
+/// `delete_confirmation` in `config.toml`. `none` only ever applies when set
+/// explicitly; the default is `type-yes`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum DeleteConfirmation {
+    /// Delete immediately.
+    None,
+    /// A single `y`/`n`.
+    Keypress,
+    /// Type `YES` and press Enter.
+    #[default]
+    TypeYes,
 }
 
-struct TrySelector {
-    mode: SelectorMode,
-    workspace_path: PathBuf,
-    input_buffer: String,
-    cursor_pos: usize,
-    scroll_offset: usize,
-    entries: Vec<TryEntry>,
-    marked_for_deletion: Vec<PathBuf>,
-    delete_mode: bool,
-    delete_status: Option<String>,
-    width: u16,
-    height: u16,
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BaseConfig {
+    after_create: Option<String>,
 }
 
-impl TrySelector {
-    fn new(mode: SelectorMode, search_term: String, workspace_path: PathBuf) -> Self {
-        let (w, h) = terminal::size().unwrap_or((80, 24));
-        Self {
-            mode,
-            workspace_path,
-            input_buffer: search_term.clone().replace(" ", "-"),
-            cursor_pos: 0,
-            scroll_offset: 0,
-            entries: Vec::new(),
-            marked_for_deletion: Vec::new(),
-            delete_mode: false,
-            delete_status: None,
-            width: w,
-            height: h,
-        }
+impl TryConfig {
+    fn get_config_path() -> PathBuf {
+        WorkspaceManager::get_config_path().with_file_name("config.toml")
     }
 
-    fn cursor_up(&mut self) -> bool {
-        if self.cursor_pos > 0 {
-            self.cursor_pos -= 1;
-            true
-        } else {
-            false
+    fn load_from(config_path: &Path) -> Result<TryConfig, TryError> {
+        if !config_path.exists() {
+            return Ok(TryConfig::default());
         }
+        let text = fs::read_to_string(config_path)?;
+        Self::parse(&text).map_err(|msg| TryError::ConfigCorrupted(config_path.to_path_buf(), msg))
     }
 
-    fn cursor_down(&mut self) -> bool {
-        let max_idx = self.visible_count().saturating_sub(1);
-        if self.cursor_pos < max_idx {
-            self.cursor_pos += 1;
-            true
-        } else {
-            false
+    fn parse(text: &str) -> Result<TryConfig, String> {
+        toml::from_str(text).map_err(|e| e.message().to_string())
+    }
+
+    fn load() -> Result<TryConfig, TryError> {
+        Self::load_from(&Self::get_config_path())
+    }
+
+    /// The `[bases."..."]` section matching `base_path`, if any.
+    fn base(&self, base_path: &Path) -> Option<&BaseConfig> {
+        let target = canonicalize_clean(base_path);
+        self.bases
+            .iter()
+            .find(|(key, _)| canonicalize_clean(&expand_path(key)) == target)
+            .map(|(_, base)| base)
+    }
+
+    /// Recency scoring parameters: `TRY_RECENCY_WEIGHT` /
+    /// `TRY_RECENCY_HALF_LIFE` override the config for a session. Values that
+    /// don't parse, or aren't positive, fall back to the default.
+    fn recency_with<F: Fn(&str) -> Option<String>>(&self, env: F) -> Recency {
+        let valid = |v: &f64| v.is_finite() && *v > 0.0;
+        let pick = |var: &str, configured: Option<f64>, default: f64| {
+            env(var)
+                .and_then(|v| v.trim().parse().ok())
+                .filter(valid)
+                .or(configured.filter(valid))
+                .unwrap_or(default)
+        };
+        let default = Recency::default();
+        Recency {
+            weight: pick("TRY_RECENCY_WEIGHT", self.recency_weight, default.weight),
+            half_life_hours: pick(
+                "TRY_RECENCY_HALF_LIFE",
+                self.recency_half_life_hours,
+                default.half_life_hours,
+            ),
         }
     }
 
-    fn run(&mut self) -> Result<Option<ShellAction>> {
-        // Ensure the workspace directory exists (Scan mode) *before* touching
-        // the terminal, so a failure (e.g. an inaccessible path) reports a
-        // clear error instead of leaving the terminal in raw mode.
-        if let SelectorMode::Scan(base_path) = &self.mode {
-            if !base_path.exists() {
-                fs::create_dir_all(base_path).with_context(|| {
-                    format!(
-                        "Failed to create workspace directory '{}'. \
-                         Check the path/permissions, or set a valid one with \
-                         `try set` or the TRY_PATH env var.",
-                        base_path.display()
-                    )
-                })?;
-            }
-        }
-
-        self.load_entries()?;
+    fn recency(&self) -> Recency {
+        self.recency_with(|k| env::var(k).ok())
+    }
 
-        terminal::enable_raw_mode()?;
-        let mut stderr = io::stderr();
-        stderr.execute(cursor::Hide)?;
-        // Clear screen once at startup to ensure clean slate
-        stderr.execute(Clear(ClearType::All))?;
+    fn delete_double_tap(&self) -> bool {
+        self.delete_confirm_required.unwrap_or(true)
+    }
 
-        let result = self.main_loop(&mut stderr);
+    fn touch_on_cd(&self) -> bool {
+        self.touch_on_cd.unwrap_or(true)
+    }
 
-        stderr.execute(cursor::Show)?;
-        stderr.execute(Clear(ClearType::All))?;
-        stderr.execute(cursor::MoveTo(0, 0))?;
-        terminal::disable_raw_mode()?;
+    fn clone_on_url(&self) -> bool {
+        self.clone_on_url.unwrap_or(true)
+    }
 
-        result
+    fn archive_dir(&self) -> PathBuf {
+        match &self.archive_dir {
+            Some(dir) => expand_path(dir),
+            None => Self::get_config_path().with_file_name("archive"),
+        }
     }
 
-    fn main_loop(&mut self, stderr: &mut Stderr) -> Result<Option<ShellAction>> {
-        // Initial render
-        self.refresh_scores();
-        self.render(stderr)?;
+    fn size_exclude(&self) -> Vec<String> {
+        self.size_exclude
+            .clone()
+            .unwrap_or_else(|| DEFAULT_SIZE_EXCLUDE.iter().map(|s| s.to_string()).collect())
+    }
 
-        loop {
-            // Block until an event is available
-            if event::poll(Duration::from_millis(1000))? {
-                let mut needs_redraw = false;
-                let mut needs_recalc = false;
-
-                match event::read()? {
-                    // On Windows, crossterm also reports key Release events.
-                    // Ignore them — otherwise the key-up events left over from
-                    // typing `tr<Enter>` to launch get injected as input and
-                    // immediately dismiss the selector.
-                    Event::Key(key) if key.kind != KeyEventKind::Release => {
-                        // Check for cancel keys (Ctrl+C or Esc)
-                        let is_cancel = matches!(key.code, KeyCode::Esc)
-                            || (key.code == KeyCode::Char('c')
-                                && key.modifiers.contains(KeyModifiers::CONTROL));
-
-                        match key.code {
-                            _ if is_cancel => {
-                                if self.delete_mode {
-                                    self.delete_mode = false;
-                                    self.marked_for_deletion.clear();
-                                    needs_redraw = true;
-                                } else {
-                                    return Ok(None);
-                                }
-                            }
-                            KeyCode::Enter => {
-                                if self.delete_mode && !self.marked_for_deletion.is_empty() {
-                                    self.confirm_batch_delete(stderr)?;
-                                    needs_redraw = true;
-                                    needs_recalc = true;
-                                } else if let Some(action) = self.handle_selection() {
-                                    return Ok(Some(action));
-                                }
-                            }
-                            KeyCode::Up => {
-                                needs_redraw = self.cursor_up();
-                            }
-                            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                needs_redraw = self.cursor_up();
-                            }
-                            KeyCode::Down => {
-                                needs_redraw = self.cursor_down();
-                            }
-                            KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                needs_redraw = self.cursor_down();
-                            }
-                            KeyCode::Backspace => {
-                                self.input_buffer.pop();
-                                self.cursor_pos = 0;
-                                needs_redraw = true;
-                                needs_recalc = true;
-                            }
-                            KeyCode::Delete => {
-                                // Toggle delete mark
-                                self.toggle_delete_mark();
-                                needs_redraw = true;
-                            }
-                            KeyCode::Char(c) => {
-                                if is_allowed_input_char(c) {
-                                    self.input_buffer.push(c);
-                                    self.cursor_pos = 0;
-                                    needs_redraw = true;
-                                    needs_recalc = true;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-                    Event::Resize(w, h) => {
-                        self.width = w;
-                        self.height = h;
-                        needs_redraw = true;
-                        // On resize, we might want to clear all once to be safe
-                        stderr.execute(Clear(ClearType::All))?;
-                    }
-                    _ => {}
-                }
+    /// Post-create hook for `base_path`: the base's own setting wins over the
+    /// global one.
+    fn after_create_for(&self, base_path: &Path) -> Option<&str> {
+        self.base(base_path)
+            .and_then(|b| b.after_create.as_deref())
+            .or(self.after_create.as_deref())
+    }
+}
 
-                if needs_recalc {
-                    self.refresh_scores();
-                }
+/// List order in the selector, cycled with Ctrl-S.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SortMode {
+    /// Match score (recency-weighted when the query is empty).
+    #[default]
+    Score,
+    /// Most recently modified first.
+    Mtime,
+    /// Alphabetical.
+    Name,
+}
 
-                if needs_redraw || needs_recalc {
-                    self.render(stderr)?;
-                }
-            }
+impl SortMode {
+    fn next(self) -> SortMode {
+        match self {
+            SortMode::Score => SortMode::Mtime,
+            SortMode::Mtime => SortMode::Name,
+            SortMode::Name => SortMode::Score,
         }
     }
 
-    fn get_filtered_entries(&self) -> Vec<&TryEntry> {
-        if self.input_buffer.is_empty() {
-            self.entries.iter().collect()
-        } else {
-            self.entries.iter().filter(|e| e.score > 0.0).collect()
+    fn label(self) -> &'static str {
+        match self {
+            SortMode::Score => "score",
+            SortMode::Mtime => "mtime",
+            SortMode::Name => "name",
         }
     }
+}
 
-    fn visible_count(&self) -> usize {
-        let create_new_option = !self.input_buffer.is_empty();
-        // Filtered entries + optional create new
-        self.get_filtered_entries().len() + if create_new_option { 1 } else { 0 }
-    }
+/// What the selector's main loop does after `TrySelector::step`.
+#[derive(Debug)]
+enum Step {
+    /// Keep reading events, redrawing first if the state changed.
+    Continue { redraw: bool },
+    /// Batch delete was confirmed; its prompt needs the terminal.
+    ConfirmDelete,
+    /// "Create new" points outside the base path; ask before doing it.
+    ConfirmCreate(ShellAction),
+    /// Ctrl-A on this directory; ask before archiving and removing it.
+    ConfirmArchive(PathBuf),
+    /// Leave the selector with this result (`None` when cancelled).
+    Exit(Option<ShellAction>),
+}
 
-    fn toggle_delete_mark(&mut self) {
-        let path_to_toggle = {
-            let filtered = self.get_filtered_entries();
-            if self.cursor_pos < filtered.len() {
-                Some(filtered[self.cursor_pos].path.clone())
-            } else {
-                None
-            }
-        };
+/// Section an entry falls under when the list is grouped by date (Ctrl-T).
+/// Declared in display order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum DateBucket {
+    Pinned,
+    Today,
+    Yesterday,
+    ThisWeek,
+    Older,
+}
 
-        if let Some(path) = path_to_toggle {
-            if self.marked_for_deletion.contains(&path) {
-                self.marked_for_deletion.retain(|p| p != &path);
-            } else {
-                self.marked_for_deletion.push(path);
-                self.delete_mode = true;
-            }
-            if self.marked_for_deletion.is_empty() {
-                self.delete_mode = false;
-            }
+impl DateBucket {
+    /// Pins keep their own section; otherwise the entry's date suffix decides,
+    /// falling back to its mtime.
+    fn of(entry: &TryEntry, today: NaiveDate) -> DateBucket {
+        if entry.pinned {
+            return DateBucket::Pinned;
         }
-    }
-
-    fn handle_selection(&self) -> Option<ShellAction> {
-        let filtered = self.get_filtered_entries();
-
-        // Check if "Create new" / "Add path" is selected
-        if !self.input_buffer.is_empty() && self.cursor_pos == filtered.len() {
-            match &self.mode {
-                SelectorMode::Scan(base_path) => {
-                    // Create new directory with date suffix
-                    let date_suffix = today_suffix();
-                    let name = self.input_buffer.replace(" ", "-");
-                    let dirname = format!("{}-{}", name, date_suffix);
-                    let path = base_path.join(dirname);
-                    return Some(ShellAction::MkdirCd(path));
-                }
-                SelectorMode::History(_) => {
-                    // Add new path to workspace (no date suffix)
-                    let path = expand_path(&self.input_buffer);
-                    return Some(ShellAction::Set(path));
-                }
-            }
+        let Some(date) = entry_date(entry) else {
+            return DateBucket::Older;
+        };
+        let days = (today - date).num_days();
+        let week_start = today.weekday().num_days_from_monday() as i64;
+        match days {
+            ..=0 => DateBucket::Today,
+            1 => DateBucket::Yesterday,
+            d if d <= week_start => DateBucket::ThisWeek,
+            _ => DateBucket::Older,
         }
+    }
 
-        if self.cursor_pos < filtered.len() {
-            match &self.mode {
-                SelectorMode::Scan(_) => {
-                    return Some(ShellAction::Cd(filtered[self.cursor_pos].path.clone()))
-                }
-                SelectorMode::History(_) => {
-                    return Some(ShellAction::Set(filtered[self.cursor_pos].path.clone()))
-                }
-            }
+    fn label(self) -> &'static str {
+        match self {
+            DateBucket::Pinned => "Pinned",
+            DateBucket::Today => "Today",
+            DateBucket::Yesterday => "Yesterday",
+            DateBucket::ThisWeek => "This week",
+            DateBucket::Older => "Older",
         }
-
-        None
     }
+}
 
-    fn load_entries(&mut self) -> Result<()> {
-        let mut entries = Vec::new();
-        match &self.mode {
-            SelectorMode::Scan(base_path) => {
-                if base_path.exists() {
-                    for entry in fs::read_dir(base_path)? {
-                        let entry = entry?;
-                        let path = entry.path();
-                        if path.is_dir() {
-                            let basename = path.file_name().unwrap().to_string_lossy().to_string();
-                            if basename.starts_with(".") {
-                                continue;
-                            }
-
-                            let metadata = fs::metadata(&path)?;
-                            let mtime = metadata.modified()?;
+/// The date in an entry's `-YYYY-MM-DD` suffix, else the local date of its
+/// mtime (`None` when that is unknown).
+fn entry_date(entry: &TryEntry) -> Option<NaiveDate> {
+    date_suffix_regex()
+        .captures(&entry.basename)
+        .and_then(|caps| NaiveDate::parse_from_str(&caps[2], "%Y-%m-%d").ok())
+        .or_else(|| {
+            (entry.mtime != SystemTime::UNIX_EPOCH)
+                .then(|| DateTime::<Local>::from(entry.mtime).date_naive())
+        })
+}
 
-                            entries.push(TryEntry {
-                                basename: basename.clone(),
-                                basename_down: basename.to_lowercase(),
-                                path,
-                                mtime,
-                                score: 0.0,
-                            });
-                        }
-                    }
-                }
-            }
-            SelectorMode::History(workspaces) => {
-                for path in workspaces {
-                    // Show all workspaces, even if path doesn't exist
-                    let metadata = fs::metadata(path).ok();
-                    let mtime = metadata
-                        .and_then(|m| m.modified().ok())
-                        .unwrap_or(SystemTime::UNIX_EPOCH); // Use epoch for non-existent paths
+/// `--since` / `--until`: an inclusive range of entry dates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct DateRange {
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+}
 
-                    entries.push(TryEntry {
-                        basename: path.to_string_lossy().to_string(), // Use full path for history
-                        basename_down: path.to_string_lossy().to_lowercase(),
-                        path: path.clone(),
-                        mtime,
-                        score: 0.0,
-                    });
-                }
-                // Reverse to show latest first by default if load order is preserved
-                entries.reverse();
-            }
+impl DateRange {
+    /// Entries without a date only pass when no bound is set.
+    fn contains(&self, entry: &TryEntry) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
         }
-        self.entries = entries;
-        Ok(())
+        entry_date(entry).is_some_and(|date| {
+            self.since.is_none_or(|since| date >= since)
+                && self.until.is_none_or(|until| date <= until)
+        })
     }
+}
 
-    fn refresh_scores(&mut self) {
-        let query = self.input_buffer.to_lowercase();
-        let query_chars: Vec<char> = query.chars().collect();
-        let now = SystemTime::now();
+/// Parse a `--since`/`--until` bound: `YYYY-MM-DD`, or `<n>d` / `<n>w` ago.
+fn parse_date_bound(s: &str) -> Result<NaiveDate, String> {
+    parse_date_bound_at(s, Local::now().date_naive())
+}
 
-        for entry in &mut self.entries {
-            entry.score = calculate_score(entry, &query, &query_chars, now);
-        }
+/// Parse `--height`: enough rows for the compact layout.
+fn parse_height(s: &str) -> Result<u16, String> {
+    match s.parse::<u16>() {
+        Ok(rows) if rows >= MIN_HEIGHT => Ok(rows),
+        _ => Err(format!(
+            "expected at least {} rows, got '{}'",
+            MIN_HEIGHT, s
+        )),
+    }
+}
 
-        // Sort: High score first
-        self.entries.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+fn parse_date_bound_at(s: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date);
     }
+    let invalid = || format!("expected YYYY-MM-DD, <n>d or <n>w, got '{}'", s);
+    let (count, per_unit) = if let Some(count) = s.strip_suffix('d') {
+        (count, 1)
+    } else if let Some(count) = s.strip_suffix('w') {
+        (count, 7)
+    } else {
+        return Err(invalid());
+    };
+    let count: u32 = count.parse().map_err(|_| invalid())?;
+    let days = count.checked_mul(per_unit).ok_or_else(invalid)?;
+    today
+        .checked_sub_days(chrono::Days::new(days.into()))
+        .ok_or_else(invalid)
+}
 
-    fn render(&mut self, stderr: &mut Stderr) -> Result<()> {
-        // Instead of Clear(All), we move to top and overwrite.
-        // This reduces flickering and bandwidth.
-        stderr.queue(cursor::MoveTo(0, 0))?;
+/// One line of the list: a non-selectable date header, or the selectable
+/// row at that cursor position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListRow {
+    Header(DateBucket),
+    Item(usize),
+}
 
-        let separator = "─".repeat((self.width as usize).saturating_sub(1));
+/// What `try` remembers between runs, kept in `state.toml` next to
+/// `config.toml` so the user's own config file is never rewritten.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TryState {
+    /// Last-used sort mode per base path.
+    sort: BTreeMap<String, SortMode>,
+    /// Pinned entry paths, kept above everything else.
+    pins: BTreeSet<String>,
+    /// Directories created without a date suffix (Alt+Enter), which
+    /// age-based cleanup must leave alone.
+    permanent: BTreeSet<String>,
+    /// Query of the last successful selection, offered again next time.
+    last_query: Option<String>,
+    /// Last known path of each pinned or permanent directory, keyed by its
+    /// file identity (`file_id`), so the flags follow a `mv`.
+    ids: BTreeMap<String, String>,
+    /// When each directory was last entered through the selector, in
+    /// nanoseconds since the epoch and strictly increasing, so visits within
+    /// the same second (or on a filesystem with coarse mtimes) still order.
+    visits: BTreeMap<String, u64>,
+}
 
-        // Header
-        stderr.queue(SetForegroundColor(Color::Red))?; // Orange-ish
-        stderr.queue(SetAttribute(Attribute::Bold))?;
-        stderr.queue(Print("📁 Try Selector"))?;
+impl TryState {
+    fn get_state_path() -> PathBuf {
+        WorkspaceManager::get_config_path().with_file_name("state.toml")
+    }
 
-        // Show workspace path
-        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-        stderr.queue(Print(" @ "))?;
-        stderr.queue(SetForegroundColor(Color::Cyan))?;
-        stderr.queue(Print(self.workspace_path.display().to_string()))?;
+    fn load_from(state_path: &Path) -> Result<TryState, TryError> {
+        if !state_path.exists() {
+            return Ok(TryState::default());
+        }
+        let text = fs::read_to_string(state_path)?;
+        toml::from_str(&text).map_err(|e| {
+            TryError::ConfigCorrupted(state_path.to_path_buf(), e.message().to_string())
+        })
+    }
 
-        stderr.queue(SetAttribute(Attribute::Reset))?;
-        stderr.queue(Clear(ClearType::UntilNewLine))?; // Clear rest of line
-        stderr.queue(Print("\r\n"))?;
+    /// Write beside the old file and rename over it, like
+    /// `WorkspaceManager::write_lines`, so a reader never sees half a file.
+    fn save_to(&self, state_path: &Path) -> Result<(), TryError> {
+        if let Some(parent) = state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let text = toml::to_string(self).map_err(|e| io::Error::other(e.to_string()))?;
+        let tmp = state_path.with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, state_path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })?;
+        Ok(())
+    }
 
-        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-        stderr.queue(Print(&separator))?;
-        stderr.queue(SetAttribute(Attribute::Reset))?;
-        stderr.queue(Clear(ClearType::UntilNewLine))?;
-        stderr.queue(Print("\r\n"))?;
+    /// Load, `change` and save the state file under its lock, so two
+    /// shells recording at once don't drop each other's pins or visits.
+    fn update(state_path: &Path, change: impl FnOnce(&mut TryState)) -> Result<(), TryError> {
+        WorkspaceManager::locked(state_path, || {
+            let mut state = Self::load_from(state_path)?;
+            change(&mut state);
+            state.save_to(state_path)
+        })
+    }
 
-        // Search bar
-        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-        stderr.queue(Print("Search: "))?;
-        stderr.queue(SetAttribute(Attribute::Reset))?;
+    fn key(base_path: &Path) -> String {
+        canonicalize_clean(base_path).to_string_lossy().to_string()
+    }
 
-        // Render search text with cursor
-        stderr.queue(SetAttribute(Attribute::Bold))?;
-        stderr.queue(SetForegroundColor(Color::Yellow))?;
-        stderr.queue(Print(&self.input_buffer))?;
-        stderr.queue(SetAttribute(Attribute::Reverse))?;
-        stderr.queue(Print(" "))?; // Cursor block
-        stderr.queue(SetAttribute(Attribute::Reset))?;
-        stderr.queue(Clear(ClearType::UntilNewLine))?;
-        stderr.queue(Print("\r\n"))?;
+    /// Sort order last chosen with Ctrl-S for `base_path`, if any.
+    fn sort_for(&self, base_path: &Path) -> Option<SortMode> {
+        self.sort.get(&Self::key(base_path)).copied()
+    }
 
-        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-        stderr.queue(Print(&separator))?;
-        stderr.queue(SetAttribute(Attribute::Reset))?;
-        stderr.queue(Clear(ClearType::UntilNewLine))?;
-        stderr.queue(Print("\r\n"))?;
+    /// Add or remove `path` from the pins in the state file at `state_path`.
+    fn remember_pin(state_path: &Path, path: &Path, pinned: bool) -> Result<(), TryError> {
+        Self::update(state_path, |state| {
+            let key = path.to_string_lossy().to_string();
+            if pinned {
+                state.pins.insert(key);
+            } else {
+                state.pins.remove(&key);
+            }
+        })
+    }
 
-        // List
-        let max_visible = (self.height as usize).saturating_sub(8).max(3);
-        let show_create_new = !self.input_buffer.is_empty();
+    /// Mark `path` as permanent in the state file at `state_path`.
+    fn remember_permanent(state_path: &Path, path: &Path) -> Result<(), TryError> {
+        Self::update(state_path, |state| {
+            state.permanent.insert(path.to_string_lossy().to_string());
+        })
+    }
 
-        // Calculate filtered len first to update scroll_offset
-        let filtered_len = self.get_filtered_entries().len();
-        let total_items = filtered_len + if show_create_new { 1 } else { 0 };
+    /// Record the query a selection was made with (`None` when it was empty).
+    fn remember_query(state_path: &Path, query: Option<&str>) -> Result<(), TryError> {
+        Self::update(state_path, |state| {
+            state.last_query = query.map(String::from);
+        })
+    }
 
-        // Adjust scroll
-        if self.cursor_pos < self.scroll_offset {
-            self.scroll_offset = self.cursor_pos;
-        } else if self.cursor_pos >= self.scroll_offset + max_visible {
-            self.scroll_offset = self.cursor_pos + 1 - max_visible;
+    /// `try gc`: forget pins, permanent marks, identities, sort modes and
+    /// visits of directories that no longer exist. Returns how many were dropped.
+    fn gc_at(state_path: &Path) -> Result<usize, TryError> {
+        if !state_path.exists() {
+            return Ok(0);
         }
-
-        // Get list for rendering
-        let filtered = self.get_filtered_entries();
-        let visible_end = (self.scroll_offset + max_visible).min(total_items);
-
-        for i in self.scroll_offset..visible_end {
-            let is_selected = i == self.cursor_pos;
-
-            // Cursor
-            if is_selected {
-                stderr.queue(SetAttribute(Attribute::Bold))?;
-                stderr.queue(SetForegroundColor(Color::Yellow))?;
-                stderr.queue(Print("→ "))?;
-                stderr.queue(SetAttribute(Attribute::Reset))?;
-            } else {
-                stderr.queue(Print("  "))?;
+        WorkspaceManager::locked(state_path, || {
+            let mut state = Self::load_from(state_path)?;
+            let exists = |p: &String| Path::new(p).is_dir();
+            let count = |s: &TryState| {
+                s.pins.len() + s.permanent.len() + s.ids.len() + s.sort.len() + s.visits.len()
+            };
+            let before = count(&state);
+            state.pins.retain(exists);
+            state.permanent.retain(exists);
+            state.ids.retain(|_, p| exists(p));
+            state.sort.retain(|p, _| exists(p));
+            state.visits.retain(|p, _| exists(p));
+            let after = count(&state);
+            if after < before {
+                state.save_to(state_path)?;
             }
+            Ok(before - after)
+        })
+    }
 
-            if i < filtered.len() {
-                let entry = filtered[i];
-                let is_marked = self.marked_for_deletion.contains(&entry.path);
-                let path_exists = entry.path.exists();
-
-                if is_marked {
-                    stderr.queue(Print("🗑️  "))?;
-                    stderr.queue(SetAttribute(Attribute::CrossedOut))?;
-                } else if !path_exists {
-                    stderr.queue(Print("❌ "))?;
-                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-                } else {
-                    stderr.queue(Print("📁 "))?;
+    /// Record a visit to `path`, later than every visit before it. Only the
+    /// newest `MAX_VISITS` are kept.
+    fn remember_visit(state_path: &Path, path: &Path) -> Result<(), TryError> {
+        Self::update(state_path, |state| {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64);
+            let last = state.visits.values().max().copied().unwrap_or(0);
+            state
+                .visits
+                .insert(path.to_string_lossy().to_string(), now.max(last + 1));
+            while state.visits.len() > MAX_VISITS {
+                if let Some(oldest) = state.visits.iter().min_by_key(|(_, v)| **v) {
+                    let oldest = oldest.0.clone();
+                    state.visits.remove(&oldest);
                 }
+            }
+        })
+    }
 
-                if is_selected {
-                    stderr.queue(SetAttribute(Attribute::Bold))?;
-                }
+    /// Record the identity map, along with the pins, permanent paths and
+    /// visits it may have renamed.
+    fn remember_ids(
+        state_path: &Path,
+        ids: &BTreeMap<String, String>,
+        pins: &BTreeSet<String>,
+        permanent: &BTreeSet<String>,
+        visits: &BTreeMap<String, u64>,
+    ) -> Result<(), TryError> {
+        Self::update(state_path, |state| {
+            state.ids = ids.clone();
+            state.pins = pins.clone();
+            state.permanent = permanent.clone();
+            state.visits = visits.clone();
+        })
+    }
 
-                // Render Name (Name + Date suffix)
-                // Assuming format Name-YYYY-MM-DD
-                if let Some(caps) = date_suffix_regex().captures(&entry.basename) {
-                    let name_part = caps.get(1).unwrap().as_str();
-                    let date_part = caps.get(2).unwrap().as_str();
+    /// Record `mode` for `base_path` in the state file at `state_path`.
+    fn remember_sort(state_path: &Path, base_path: &Path, mode: SortMode) -> Result<(), TryError> {
+        Self::update(state_path, |state| {
+            state.sort.insert(Self::key(base_path), mode);
+        })
+    }
+}
 
-                    self.print_highlighted(stderr, name_part, &self.input_buffer, is_selected)?;
+#[derive(Parser)]
+#[command(name = "try")]
+#[command(version = VERSION)]
+#[command(about = "Ephemeral workspace manager", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-                    if !self.input_buffer.is_empty() && self.input_buffer.contains('-') {
-                        stderr.queue(SetForegroundColor(Color::Yellow))?;
-                        stderr.queue(SetAttribute(Attribute::Bold))?;
-                        stderr.queue(Print("-"))?;
-                        stderr.queue(SetAttribute(Attribute::Reset))?;
-                        if is_selected {
-                            stderr.queue(SetAttribute(Attribute::Bold))?;
-                        }
-                    } else {
-                        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-                        stderr.queue(Print("-"))?;
-                    }
+    /// Optional query for interactive mode
+    #[arg(index = 1)]
+    query: Option<String>,
 
-                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-                    stderr.queue(Print(date_part))?;
+    /// Open the selector with this text already in the search box. Unlike
+    /// the positional query it is never taken as a git URL to clone
+    #[arg(long, value_name = "TEXT", conflicts_with = "query")]
+    initial_query: Option<String>,
+
+    /// Write the generated script to this file instead of stdout (e.g. for a
+    /// tmux popup that reads it back and `send-keys` it to the parent pane)
+    #[arg(long, global = true, value_name = "PATH")]
+    out_file: Option<PathBuf>,
+
+    /// Write the selected action to this named pipe as one `<verb>\t<path>`
+    /// line (`cd`, `mkdir`, `set`, `open`, or `cancel`) for runners that can't eval
+    #[arg(long, value_name = "PATH", conflicts_with = "out_file")]
+    fifo: Option<PathBuf>,
+
+    /// Run the selector without drawing: read key events as JSON lines from
+    /// stdin and write its state as JSON lines to this file or pipe, for
+    /// embedding in another TUI
+    #[arg(long, value_name = "PATH")]
+    emit_events: Option<PathBuf>,
+
+    /// Command to run inside a newly created directory; `{path}` expands to
+    /// its quoted path (overrides `after_create` in config.toml)
+    #[arg(long, value_name = "CMD")]
+    after: Option<String>,
+
+    /// Hide the "Create new" / "Add path" row, so the selector only navigates
+    #[arg(long)]
+    no_create: bool,
+
+    /// Disable delete mode (the Del key does nothing)
+    #[arg(long)]
+    no_delete: bool,
+
+    /// Browse only: never create the base path or anything in it, and don't
+    /// delete or touch. Implied when the base path isn't writable
+    #[arg(long)]
+    read_only: bool,
+
+    /// Start with an empty search instead of the last selection's query
+    #[arg(long)]
+    no_restore_query: bool,
+
+    /// Only show entries dated on or after this day (`YYYY-MM-DD`, or `7d` /
+    /// `2w` ago); the date suffix is used, else the modification time
+    #[arg(long, global = true, value_name = "DATE", value_parser = parse_date_bound)]
+    since: Option<NaiveDate>,
+
+    /// Only show entries dated on or before this day (same formats as --since)
+    #[arg(long, global = true, value_name = "DATE", value_parser = parse_date_bound)]
+    until: Option<NaiveDate>,
+
+    /// Make the workspaces file readable by its owner only (mode 600)
+    #[arg(long, global = true)]
+    fix_permissions: bool,
+
+    /// Draw the selector in this many rows below the prompt instead of
+    /// taking over the whole terminal
+    #[arg(long, global = true, value_name = "ROWS", value_parser = parse_height)]
+    height: Option<u16>,
+}
 
-                    stderr.queue(SetAttribute(Attribute::Reset))?;
-                    if is_selected {
-                        stderr.queue(SetAttribute(Attribute::Bold))?;
-                    }
-                    if is_marked {
-                        stderr.queue(SetAttribute(Attribute::CrossedOut))?;
-                    }
-                } else {
-                    self.print_highlighted(
-                        stderr,
-                        &entry.basename,
-                        &self.input_buffer,
-                        is_selected,
-                    )?;
-                }
+#[derive(Subcommand)]
+enum Commands {
+    /// Output shell function definition
+    Init {
+        #[arg(default_value = "~/project/test")]
+        path: String,
+        /// Target shell: bash | fish | powershell (auto-detected if omitted)
+        #[arg(short, long)]
+        shell: Option<String>,
+        /// Wrapper command name (default: `try` on bash, `tr` on PowerShell,
+        /// since `try` is a reserved PowerShell keyword)
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Only print the script; never record `path` in the workspace
+        /// history (safe to source from every new shell)
+        #[arg(long)]
+        print_only: bool,
+        /// Workspace group for this session (exported as TRY_GROUP); `try set`
+        /// then only lists workspaces in this group
+        #[arg(short, long)]
+        group: Option<String>,
+        /// Also install a prompt hook that refreshes the workspace you're in
+        /// (`try set --silent`) whenever the directory changes
+        #[arg(long)]
+        watch: bool,
+        /// Print a one-line `eval` of this init, ready to paste into a
+        /// shell rc file
+        #[arg(long, conflicts_with = "install")]
+        eval: bool,
+        /// Append that line to your shell's rc file (asks first)
+        #[arg(long)]
+        install: bool,
+    },
+    /// Clone git repo into date-prefixed directory
+    Clone {
+        url: String,
+        name: Option<String>,
+        #[arg(short, long)]
+        proxy: Option<String>,
+        /// Only check out paths matching this pattern (cone mode); repeat
+        /// for several
+        #[arg(long, value_name = "PATTERN")]
+        sparse: Vec<String>,
+        /// Clone into `<name>/<repo>`, leaving the dated directory for notes
+        #[arg(long)]
+        nest: bool,
+        /// Clone into this subdirectory of the target (relative, no `..`)
+        #[arg(long, value_name = "SUBDIR")]
+        into: Option<String>,
+        /// Clone under this directory instead of the base path, for this
+        /// clone only (`~` and `$VAR` are expanded)
+        #[arg(long, value_name = "DIR")]
+        base: Option<String>,
+        /// Directory name from `{repo}`, `{host}` and `{date}`, e.g.
+        /// `{host}-{repo}-{date}` (overrides `clone_name_template`)
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "name")]
+        name_template: Option<String>,
+    },
+    /// Select a workspace from history, make it this shell's TRY_PATH and cd
+    /// into it
+    Set {
+        /// Don't open the selector: just mark the workspace containing
+        /// `path` (default: the current directory) as used now. Prints
+        /// nothing, for prompt hooks.
+        #[arg(long)]
+        silent: bool,
+        /// Directory to record with `--silent`
+        #[arg(requires = "silent")]
+        path: Option<String>,
+    },
+    /// Open a directory in an editor instead of `cd`-ing into it
+    Open {
+        /// Entry to open (fuzzy-matched); opens the selector if omitted
+        name: Option<String>,
+        /// Editor command (overrides `editor` in config.toml and $EDITOR)
+        #[arg(short, long)]
+        editor: Option<String>,
+        /// Print the matching path instead of opening it, e.g.
+        /// `$EDITOR "$(command try open myproj --print)"`
+        #[arg(short, long, requires = "name", conflicts_with = "editor")]
+        print: bool,
+    },
+    /// Raise (positive) or sink (negative) a workspace among the matches of
+    /// `try set`
+    Weight {
+        /// Workspace path, or its directory name
+        name: String,
+        /// Added to its score; 0 removes the weight
+        #[arg(allow_negative_numbers = true)]
+        weight: f64,
+    },
+    /// Forget workspaces, pins and other state for directories that no
+    /// longer exist, and report the bytes saved
+    Gc {
+        /// Also order the workspace history by last access (newest first)
+        #[arg(long)]
+        aggressive: bool,
+    },
+    /// Show a directory's path, modification time and size
+    Info {
+        /// Entry to describe (fuzzy-matched)
+        name: String,
+    },
+    /// Restore an archived workspace (Ctrl-A in the selector) into the
+    /// base path and cd into it
+    Unarchive {
+        /// Archive to restore (fuzzy-matched); pick one in a selector if
+        /// omitted
+        name: Option<String>,
+    },
+    /// `git pull --ff-only` cloned workspaces
+    Pull {
+        /// Workspace to pull (fuzzy-matched); the clone containing the
+        /// current directory if omitted
+        #[arg(conflicts_with = "all")]
+        name: Option<String>,
+        /// Pull every clone in the base path and the workspace history
+        #[arg(long)]
+        all: bool,
+        /// Run several pulls at the same time
+        #[arg(long)]
+        parallel: bool,
+    },
+    /// Print the most recently used directories (no TUI)
+    Recent {
+        /// Maximum number of entries to print
+        #[arg(short, long, default_value_t = 5)]
+        limit: usize,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = RecentFormat::Path)]
+        format: RecentFormat,
+        /// Order of the printed entries (the most recent are picked either way)
+        #[arg(short, long, value_enum, default_value_t = RecentSort::Mtime)]
+        sort: RecentSort,
+    },
+    /// Print the workspaces `try set` offers, one path per line (no TUI)
+    List {
+        /// Order of the printed workspaces
+        #[arg(short, long, value_enum, default_value_t = SortOrder::Recency)]
+        sort: SortOrder,
+    },
+}
+
+impl Commands {
+    /// Whether the command prints a script for the shell wrapper to eval,
+    /// the only output `--out-file` redirects.
+    fn emits_script(&self) -> bool {
+        match self {
+            Commands::Clone { .. } | Commands::Unarchive { .. } => true,
+            Commands::Set { silent, .. } => !silent,
+            Commands::Open { print, .. } => !print,
+            Commands::Init { .. }
+            | Commands::Weight { .. }
+            | Commands::Gc { .. }
+            | Commands::Info { .. }
+            | Commands::Pull { .. }
+            | Commands::Recent { .. }
+            | Commands::List { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RecentSort {
+    /// Most recently modified first
+    Mtime,
+    /// Alphabetical
+    Name,
+    /// Largest first (walks each printed directory)
+    Size,
+}
+
+/// Order of `try list` (`WorkspaceManager::list_sorted`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortOrder {
+    /// Most recently chosen first
+    Recency,
+    /// Alphabetical by directory name
+    Name,
+    /// Most often chosen first, counted from the event log
+    Frequency,
+    /// Largest first (walks every workspace)
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RecentFormat {
+    /// One absolute path per line
+    Path,
+    /// One directory name per line
+    Name,
+    /// A JSON array of `{name, path, mtime}` objects
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct TryEntry {
+    basename: String,
+    basename_down: String,
+    path: PathBuf,
+    mtime: SystemTime,
+    /// History mode: when the workspace was last chosen. Preferred over
+    /// `mtime` for recency.
+    last_accessed: Option<SystemTime>,
+    score: f64,
+    /// Where the entry points when it is itself a symlink.
+    link_target: Option<PathBuf>,
+    /// The path existed when the list was loaded.
+    verified: bool,
+    /// Pinned entries rank above all others.
+    pinned: bool,
+    /// Created without a date suffix to be kept indefinitely.
+    permanent: bool,
+    /// History mode: the workspace's group.
+    group: Option<String>,
+    /// History mode: the workspace's `try weight`.
+    sort_weight: f64,
+    /// History mode: the `workspaces.d/` file a shared workspace came from.
+    source: Option<String>,
+    /// A git worktree: how many worktrees its repository has.
+    worktrees: Option<usize>,
+    /// Clone, worktree or neither (`WorkspaceKind::detect`).
+    kind: WorkspaceKind,
+    /// Stable across renames and moves on the same filesystem (`file_id`).
+    file_id: Option<String>,
+    /// Project-type icon; `None` renders the kind's icon.
+    icon: Option<String>,
+    /// The first line of the workspace's `.try-desc`, searched after the name.
+    description: Option<String>,
+    /// `description` lowercased once, for matching (see `basename_down`).
+    description_down: Option<String>,
+}
+
+impl TryEntry {
+    fn new(basename: String, path: PathBuf, mtime: SystemTime) -> Self {
+        Self {
+            basename_down: basename.to_lowercase(),
+            basename,
+            path,
+            mtime,
+            last_accessed: None,
+            verified: true,
+            pinned: false,
+            permanent: false,
+            group: None,
+            sort_weight: 0.0,
+            source: None,
+            worktrees: None,
+            kind: WorkspaceKind::Manual,
+            file_id: None,
+            score: 0.0,
+            link_target: None,
+            icon: None,
+            description: None,
+            description_down: None,
+        }
+    }
+
+    fn set_description(&mut self, description: Option<String>) {
+        self.description_down = description.as_deref().map(str::to_lowercase);
+        self.description = description;
+    }
+}
+
+enum SelectorMode {
+    Scan(PathBuf),
+    History(Vec<WorkspaceRecord>),
+    /// `try unarchive`: the archives in this directory.
+    Archive(PathBuf),
+}
+
+/// Quiet period a resize burst must reach before redrawing.
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(50);
+/// Idle poll interval when nothing is pending.
+const IDLE_POLL: Duration = Duration::from_millis(1000);
+/// The search cursor is shown, then hidden, for this long each.
+const CURSOR_BLINK: Duration = Duration::from_millis(500);
+
+/// Collapses bursts of `Event::Resize` (tiling WMs fire many per second) into
+/// one redraw, and ignores spurious SIGWINCHs that don't change size.
+struct ResizeDebouncer {
+    size: (u16, u16),
+    last_resize: Option<Instant>,
+    /// Height of the last frame drawn after a burst.
+    drawn_height: u16,
+}
+
+impl ResizeDebouncer {
+    fn new(width: u16, height: u16) -> Self {
+        Self {
+            size: (width, height),
+            last_resize: None,
+            drawn_height: height,
+        }
+    }
+
+    /// Record a resize event. Returns `true` when the size actually changed.
+    fn on_resize(&mut self, width: u16, height: u16, now: Instant) -> bool {
+        if (width, height) == self.size {
+            return false;
+        }
+        self.size = (width, height);
+        self.last_resize = Some(now);
+        true
+    }
+
+    /// `true` exactly once per burst, after `RESIZE_DEBOUNCE` of quiet.
+    fn settled(&mut self, now: Instant) -> bool {
+        match self.last_resize {
+            Some(t) if now.duration_since(t) >= RESIZE_DEBOUNCE => {
+                self.last_resize = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// After `settled`: whether the screen got shorter than the last frame,
+    /// the one case that takes a full clear. Otherwise every row overwrites
+    /// itself and ends with `Clear(UntilNewLine)`, so a redraw is enough and
+    /// the screen doesn't flash.
+    fn shrank(&mut self) -> bool {
+        let shrank = self.size.1 < self.drawn_height;
+        self.drawn_height = self.size.1;
+        shrank
+    }
+
+    /// How long to wait for input before re-checking `settled`.
+    fn poll_timeout(&self, now: Instant) -> Duration {
+        match self.last_resize {
+            Some(t) => RESIZE_DEBOUNCE.saturating_sub(now.duration_since(t)),
+            None => IDLE_POLL,
+        }
+    }
+}
+
+/// Rows needed for the full layout: header, search, and footer with their
+/// separators (6) plus a three-row list.
+const FULL_LAYOUT_HEIGHT: u16 = 9;
+/// Rows needed for the compact layout: search, one list row, footer.
+const MIN_HEIGHT: u16 = 3;
+
+/// How long the first Delete press inverts the entry's row.
+const DELETE_FLASH: Duration = Duration::from_millis(200);
+
+/// Characters Left/Right scroll the Ctrl-G path by.
+const PATH_SCROLL_STEP: usize = 8;
+
+/// The second Delete press must follow the first within this.
+const DOUBLE_TAP_WINDOW: Duration = Duration::from_millis(500);
+
+/// Set while the selector holds the terminal in raw mode, so a signal knows
+/// there is something to undo.
+static TERMINAL_RAW: AtomicBool = AtomicBool::new(false);
+
+/// The first row a `--height` selector draws on; `None` while it has the
+/// whole screen.
+static INLINE_TOP: Mutex<Option<u16>> = Mutex::new(None);
+
+/// Undo what `TrySelector::run` does to the terminal. An inline selector
+/// only wipes its own rows and leaves the cursor where they began.
+fn restore_terminal(stderr: &mut impl Write) -> io::Result<()> {
+    stderr.execute(cursor::Show)?;
+    match INLINE_TOP.lock().ok().and_then(|top| *top) {
+        Some(top) => {
+            stderr.execute(cursor::MoveTo(0, top))?;
+            stderr.execute(Clear(ClearType::FromCursorDown))?;
+        }
+        None => {
+            stderr.execute(Clear(ClearType::All))?;
+            stderr.execute(cursor::MoveTo(0, 0))?;
+        }
+    }
+    terminal::disable_raw_mode()
+}
+
+/// On SIGINT, SIGTERM or SIGHUP (the terminal closing), restore the
+/// terminal if the selector has it in raw mode, then exit with the usual
+/// `128 + signal` status. Installed once per process.
+#[cfg(unix)]
+fn install_signal_cleanup() {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+    INSTALLED.get_or_init(|| {
+        let Ok(mut signals) = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP]) else {
+            return;
+        };
+        std::thread::spawn(move || {
+            if let Some(sig) = signals.forever().next() {
+                if TERMINAL_RAW.load(Ordering::SeqCst) {
+                    let _ = restore_terminal(&mut io::stderr());
+                }
+                std::process::exit(128 + sig);
+            }
+        });
+    });
+}
+
+#[cfg(not(unix))]
+fn install_signal_cleanup() {}
+
+struct TrySelector {
+    mode: SelectorMode,
+    workspace_path: PathBuf,
+    input_buffer: String,
+    cursor_pos: usize,
+    scroll_offset: usize,
+    entries: Vec<TryEntry>,
+    marked_for_deletion: Vec<PathBuf>,
+    delete_mode: bool,
+    /// Footer message from the last batch delete or failed selection.
+    status: Option<String>,
+    scorer: Box<dyn Scorer>,
+    /// Debug overlay: show each entry's raw score (toggled with Ctrl-D).
+    show_scores: bool,
+    /// Entry counts at the right of the footer (`show_counts`).
+    show_counts: bool,
+    /// Pick the sole match without Enter (`auto_accept_single`).
+    auto_accept_single: bool,
+    /// Outside delete mode, Delete only marks on a second press within
+    /// `DOUBLE_TAP_WINDOW` (`delete_confirm_required`).
+    delete_double_tap: bool,
+    /// Entry the first Delete press flashed, and when.
+    delete_armed: Option<(PathBuf, Instant)>,
+    icons: ProjectIcons,
+    /// `recency_from_files`: newest-file times, `None` when off.
+    file_activity: Option<FileActivity>,
+    delete_confirmation: DeleteConfirmation,
+    /// Background scan still streaming entries, if any.
+    scan: Option<ScanJob>,
+    /// Cycled with Ctrl-S.
+    sort_mode: SortMode,
+    /// Group the list under Today/Yesterday/This week/Older headers (Ctrl-T).
+    date_headers: bool,
+    /// `--since` / `--until`; entries outside are dropped as they load.
+    date_range: DateRange,
+    /// The input holds the last run's query: typing replaces it, Backspace
+    /// clears it, any other key keeps it.
+    query_restored: bool,
+    /// Session group (`$TRY_GROUP`); an `@group` query overrides it.
+    group_filter: Option<String>,
+    /// Opened on the first Ctrl-Y.
+    clipboard: Option<arboard::Clipboard>,
+    /// How Ctrl-Y copies (`copy_method`).
+    copy_method: CopyMethod,
+    /// What Ctrl-O runs on the selected path (`file_opener()`).
+    opener: &'static str,
+    /// Pinned entry paths (toggled with Ctrl-P).
+    pins: BTreeSet<String>,
+    /// Paths created as permanent (Alt+Enter).
+    permanent: BTreeSet<String>,
+    /// `file_id` -> last known path of pinned/permanent entries.
+    ids: BTreeMap<String, String>,
+    /// Path -> last visit (`TryState::visits`).
+    visits: BTreeMap<String, u64>,
+    /// Base paths Ctrl-B cycles through in Scan mode (the workspace history).
+    bases: Vec<PathBuf>,
+    /// Hide entries whose directory is gone (Ctrl-F). Unlike `try gc`,
+    /// nothing is removed from history.
+    filter_existing_only: bool,
+    /// Scan mode, Ctrl-R: match paths relative to the base, which brings in
+    /// the workspaces nested one level down (`try client/idea`).
+    match_paths: bool,
+    /// Show the selection's absolute path in the footer (Ctrl-G).
+    show_path: bool,
+    /// How far Left/Right have scrolled that path, in characters.
+    path_scroll: usize,
+    /// Start of the cursor's blink cycle; reset on each key so it stays
+    /// visible while typing.
+    blink_epoch: Instant,
+    /// Whether the last render drew the cursor block.
+    cursor_drawn: bool,
+    /// Where sort mode (Scan mode only) and pin changes are remembered;
+    /// `None` keeps them for this session.
+    state_path: Option<PathBuf>,
+    /// The workspaces file, whose records follow a directory that was
+    /// moved (see `track_identity`); `None` leaves it alone.
+    history_path: Option<PathBuf>,
+    /// Offer the "Create new" / "Add path" row (off with `--no-create`).
+    allow_create: bool,
+    /// Allow marking entries for deletion (off with `--no-delete`).
+    allow_delete: bool,
+    /// `--read-only`, or a base path that can't be written: no creating,
+    /// deleting or touching.
+    read_only: bool,
+    /// Ctrl-A archives the selected entry here (Scan mode only).
+    archive_dir: Option<PathBuf>,
+    width: u16,
+    height: u16,
+    /// `--height`: draw in this many rows at the bottom of the terminal
+    /// instead of taking over the screen.
+    inline_height: Option<u16>,
+    /// The terminal row the selector starts on (0 unless inline).
+    top: u16,
+    /// `render`'s frame buffer, kept between frames to reuse its capacity.
+    frame: Vec<u8>,
+}
+
+impl TrySelector {
+    fn new(mode: SelectorMode, search_term: String, workspace_path: PathBuf) -> Self {
+        let (w, h) = terminal::size().unwrap_or((80, 24));
+        // Directory names use dashes for spaces; workspace paths (and
+        // `@group` queries) keep theirs, as do searches with `!word`
+        // exclusions, whose tokens the spaces separate.
+        let input_buffer = match mode {
+            SelectorMode::Scan(_) | SelectorMode::Archive(_) if !has_negation(&search_term) => {
+                search_term.replace(' ', "-")
+            }
+            _ => search_term,
+        };
+        let scorer = LeafScorer::for_mode(ScorerKind::detect().scorer(Recency::default()), &mode);
+        Self {
+            mode,
+            workspace_path,
+            input_buffer,
+            cursor_pos: 0,
+            scroll_offset: 0,
+            entries: Vec::new(),
+            marked_for_deletion: Vec::new(),
+            delete_mode: false,
+            status: None,
+            scorer,
+            show_scores: false,
+            show_counts: false,
+            auto_accept_single: false,
+            delete_double_tap: false,
+            delete_armed: None,
+            icons: ProjectIcons::new(None),
+            file_activity: None,
+            delete_confirmation: DeleteConfirmation::default(),
+            scan: None,
+            sort_mode: SortMode::default(),
+            date_headers: false,
+            date_range: DateRange::default(),
+            query_restored: false,
+            group_filter: None,
+            clipboard: None,
+            copy_method: CopyMethod::default(),
+            opener: file_opener(),
+            pins: BTreeSet::new(),
+            permanent: BTreeSet::new(),
+            ids: BTreeMap::new(),
+            visits: BTreeMap::new(),
+            bases: Vec::new(),
+            filter_existing_only: false,
+            match_paths: false,
+            show_path: false,
+            path_scroll: 0,
+            blink_epoch: Instant::now(),
+            cursor_drawn: true,
+            state_path: None,
+            history_path: None,
+            allow_create: true,
+            read_only: false,
+            allow_delete: true,
+            archive_dir: None,
+            width: w,
+            height: h,
+            inline_height: None,
+            top: 0,
+            frame: Vec::new(),
+        }
+    }
+
+    /// The first key after a restored query decides its fate: text input
+    /// and Backspace start over, anything else keeps it. Returns `true`
+    /// when a restored query was pending.
+    fn take_restored_query(&mut self, key: &KeyEvent) -> bool {
+        if !std::mem::take(&mut self.query_restored) {
+            return false;
+        }
+        let typing = matches!(key.code, KeyCode::Char(c) if is_allowed_input_char(c))
+            && !key.modifiers.contains(KeyModifiers::CONTROL);
+        if typing || key.code == KeyCode::Backspace {
+            self.input_buffer.clear();
+        }
+        true
+    }
+
+    /// The query changed: jump back to the top of the (re-ranked) list.
+    fn reset_viewport(&mut self) {
+        self.cursor_pos = 0;
+        self.scroll_offset = 0;
+        self.path_scroll = 0;
+    }
+
+    fn cursor_up(&mut self) -> bool {
+        if self.cursor_pos > 0 {
+            self.cursor_pos -= 1;
+            self.path_scroll = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn cursor_down(&mut self) -> bool {
+        let max_idx = self.visible_count().saturating_sub(1);
+        if self.cursor_pos < max_idx {
+            self.cursor_pos += 1;
+            self.path_scroll = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn run(&mut self) -> Result<Option<ShellAction>> {
+        self.prepare_base()?;
+        self.start_loading().with_context(|| {
+            format!(
+                "Workspace path '{}' is unavailable",
+                self.workspace_path.display()
+            )
+        })?;
+
+        install_signal_cleanup();
+        terminal::enable_raw_mode()?;
+        TERMINAL_RAW.store(true, Ordering::SeqCst);
+        let mut stderr = io::stderr();
+        stderr.execute(cursor::Hide)?;
+        if self.inline_height.is_some() {
+            self.reserve_rows(&mut stderr)?;
+        }
+        // Clear screen once at startup to ensure clean slate
+        self.clear_screen(&mut stderr)?;
+
+        let result = self.main_loop(&mut stderr);
+
+        TERMINAL_RAW.store(false, Ordering::SeqCst);
+        restore_terminal(&mut stderr)?;
+        if let Ok(mut top) = INLINE_TOP.lock() {
+            *top = None;
+        }
+
+        result
+    }
+
+    /// `--height`: scroll the terminal until the selector's rows fit below
+    /// the cursor, and start drawing where the cursor was left.
+    fn reserve_rows(&mut self, stderr: &mut Stderr) -> Result<()> {
+        let (_, rows) = terminal::size().unwrap_or((self.width, self.height));
+        self.fit_inline(rows);
+        let below = self.height - 1;
+        stderr.execute(Print("\r\n".repeat(below as usize)))?;
+        stderr.execute(cursor::MoveUp(below))?;
+        self.top = match cursor::position() {
+            Ok((_, row)) => row,
+            Err(_) => rows.saturating_sub(self.height),
+        };
+        if let Ok(mut top) = INLINE_TOP.lock() {
+            *top = Some(self.top);
+        }
+        Ok(())
+    }
+
+    /// Size an inline selector for a terminal of `rows`, keeping it on
+    /// screen when the terminal shrinks.
+    fn fit_inline(&mut self, rows: u16) {
+        if let Some(height) = self.inline_height {
+            self.height = height.min(rows);
+            self.top = self.top.min(rows.saturating_sub(self.height));
+        }
+    }
+
+    /// Blank the selector's area: the screen, or an inline selector's rows.
+    fn clear_screen<W: Write>(&self, stderr: &mut W) -> io::Result<()> {
+        if self.inline_height.is_some() {
+            stderr.execute(cursor::MoveTo(0, self.top))?;
+            stderr.execute(Clear(ClearType::FromCursorDown))?;
+        } else {
+            stderr.execute(Clear(ClearType::All))?;
+        }
+        Ok(())
+    }
+
+    /// `--emit-events`: the selector as a model without a view. Each line of
+    /// `input` is a JSON event (`{"key": "a"}`, `{"key": "b", "ctrl": true}`,
+    /// `{"key": "Enter", "alt": true}`, `{"resize": [80, 24]}`); after each
+    /// one the state goes to `output` as a JSON line (see `state_json`).
+    /// Confirmations are asked with a `prompt` and answered with
+    /// `{"answer": true}`. The last line is `{"exit": ...}`.
+    fn run_headless<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        output: &mut W,
+    ) -> Result<Option<ShellAction>> {
+        self.prepare_base()?;
+        self.load_entries().with_context(|| {
+            format!(
+                "Workspace path '{}' is unavailable",
+                self.workspace_path.display()
+            )
+        })?;
+        self.refresh_scores();
+        writeln!(output, "{}", self.state_json(None))?;
+
+        let mut lines = input.lines();
+        let action = loop {
+            let Some(line) = lines.next().transpose()? else {
+                break None; // the embedder went away: cancel
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = match parse_event_line(&line) {
+                Ok(event) => event,
+                Err(e) => {
+                    self.status = Some(format!("Bad event: {}", e));
+                    writeln!(output, "{}", self.state_json(None))?;
+                    continue;
+                }
+            };
+            let mut ask = |sel: &mut Self, prompt: &str| -> Result<bool> {
+                writeln!(output, "{}", sel.state_json(Some(prompt)))?;
+                let answer = lines.next().transpose()?.unwrap_or_default();
+                Ok(serde_json::from_str::<serde_json::Value>(&answer)
+                    .is_ok_and(|v| v["answer"] == true))
+            };
+            match self.step(&event)? {
+                Step::Continue { .. } => {}
+                Step::ConfirmDelete => {
+                    let prompt = format!("Delete {} directories?", self.marked_for_deletion.len());
+                    let confirmed = ask(self, &prompt)?;
+                    self.finish_batch_delete(confirmed)?;
+                    self.refresh_scores();
+                }
+                Step::ConfirmArchive(path) => {
+                    let prompt = format!("Archive {} and remove it?", path.display());
+                    let confirmed = ask(self, &prompt)?;
+                    self.finish_archive(&path, confirmed)?;
+                    self.refresh_scores();
+                }
+                Step::ConfirmCreate(action) => {
+                    let ShellAction::MkdirCd(path) = &action else {
+                        break Some(action);
+                    };
+                    let prompt = format!("Create {} outside the base path?", path.display());
+                    if ask(self, &prompt)? {
+                        break Some(action);
+                    }
+                    self.status = Some("Create cancelled.".to_string());
+                }
+                Step::Exit(action) => break action,
+            }
+            writeln!(output, "{}", self.state_json(None))?;
+        };
+        let exit = action.as_ref().map(|a| {
+            let (verb, path) = action_line(a)
+                .split_once('\t')
+                .map(|(v, p)| (v.to_string(), p.to_string()))
+                .unwrap_or_default();
+            serde_json::json!({ "verb": verb, "path": path })
+        });
+        writeln!(output, "{}", serde_json::json!({ "exit": exit }))?;
+        Ok(action)
+    }
+
+    /// One `--emit-events` state line. `rows` lines up with `cursor`: group
+    /// rows, then entries, then the "Create new" / "Add path" row.
+    fn state_json(&self, prompt: Option<&str>) -> serde_json::Value {
+        let mut rows: Vec<serde_json::Value> = self
+            .group_rows()
+            .into_iter()
+            .map(|g| serde_json::json!({ "kind": "group", "name": g }))
+            .collect();
+        rows.extend(self.get_filtered_entries().into_iter().map(|e| {
+            serde_json::json!({
+                "kind": "entry",
+                "name": e.basename,
+                "path": e.path.to_string_lossy(),
+                "score": e.score,
+                "pinned": e.pinned,
+                "marked": self.marked_for_deletion.contains(&e.path),
+            })
+        }));
+        if self.show_create_new() {
+            rows.push(serde_json::json!({ "kind": "create", "query": self.search() }));
+        }
+        serde_json::json!({
+            "mode": match self.mode {
+                SelectorMode::Scan(_) => "scan",
+                SelectorMode::History(_) => "history",
+                SelectorMode::Archive(_) => "archive",
+            },
+            "base": self.workspace_path.to_string_lossy(),
+            "query": self.input_buffer,
+            "cursor": self.cursor_pos,
+            "rows": rows,
+            "sort": self.sort_mode.label(),
+            "delete_mode": self.delete_mode,
+            "status": self.status,
+            "prompt": prompt,
+        })
+    }
+
+    /// Ensure the workspace directory exists (Scan mode) *before* touching
+    /// the terminal, so a failure (e.g. an inaccessible path) reports a
+    /// clear error instead of leaving the terminal in raw mode.
+    fn prepare_base(&self) -> Result<()> {
+        if let SelectorMode::Scan(base_path) = &self.mode {
+            check_base_available(base_path)?;
+            if !base_path.exists() && self.read_only {
+                anyhow::bail!(
+                    "Workspace directory '{}' does not exist, and --read-only \
+                     won't create it.",
+                    base_path.display()
+                );
+            }
+            if !base_path.exists() {
+                fs::create_dir_all(base_path).with_context(|| {
+                    format!(
+                        "Failed to create workspace directory '{}'. \
+                         Check the path/permissions, or set a valid one with \
+                         `try set` or the TRY_PATH env var.",
+                        base_path.display()
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn main_loop(&mut self, stderr: &mut Stderr) -> Result<Option<ShellAction>> {
+        // Initial render
+        self.refresh_scores();
+        self.render(stderr)?;
+        let mut resize = ResizeDebouncer::new(self.width, self.height);
+
+        loop {
+            // Once a resize burst has settled, redraw once.
+            if resize.settled(Instant::now()) {
+                if resize.shrank() {
+                    self.clear_screen(stderr)?;
+                }
+                self.render(stderr)?;
+            }
+
+            if self.poll_scan(Instant::now()) {
+                self.render(stderr)?;
+            }
+
+            // Block until an event is available (or a pending resize settles,
+            // or the spinner is due for its next frame)
+            let mut timeout = resize.poll_timeout(Instant::now());
+            if self.scan.is_some() {
+                timeout = timeout.min(SPINNER_TICK);
+            }
+            // Wake up to put a flashed row back to normal, and to blink.
+            let flashing = self.flash_remaining(Instant::now());
+            if let Some(remaining) = flashing {
+                timeout = timeout.min(remaining);
+            }
+            timeout = timeout.min(self.until_blink(Instant::now()));
+            if event::poll(timeout)? {
+                let event = event::read()?;
+                if let Event::Resize(w, h) = event {
+                    // Rendering is deferred until the burst settles.
+                    resize.on_resize(w, h, Instant::now());
+                }
+                match self.step(&event)? {
+                    Step::Continue { redraw } => {
+                        if redraw {
+                            self.render(stderr)?;
+                        }
+                    }
+                    Step::ConfirmDelete => {
+                        self.confirm_batch_delete(stderr)?;
+                        self.refresh_scores();
+                        self.render(stderr)?;
+                    }
+                    Step::ConfirmArchive(path) => {
+                        let prompt = format!("Archive {} and remove it? [y/N] ", path.display());
+                        let confirmed = self.confirm_keypress(stderr, &prompt)?;
+                        self.finish_archive(&path, confirmed)?;
+                        self.refresh_scores();
+                        self.clear_screen(stderr)?;
+                        self.render(stderr)?;
+                    }
+                    Step::ConfirmCreate(action) => {
+                        let ShellAction::MkdirCd(path) = &action else {
+                            return Ok(Some(action));
+                        };
+                        let prompt =
+                            format!("Create {} outside the base path? [y/N] ", path.display());
+                        if self.confirm_keypress(stderr, &prompt)? {
+                            return Ok(Some(action));
+                        }
+                        self.status = Some("Create cancelled.".to_string());
+                        self.clear_screen(stderr)?;
+                        self.render(stderr)?;
+                    }
+                    Step::Exit(action) => return Ok(action),
+                }
+            } else if (flashing.is_some() && self.flash_remaining(Instant::now()).is_none())
+                || self.cursor_on(Instant::now()) != self.cursor_drawn
+            {
+                self.render(stderr)?;
+            }
+        }
+    }
+
+    /// Apply one terminal event to the selector state, without touching the
+    /// terminal. Scores are refreshed here; drawing is up to the caller.
+    fn step(&mut self, event: &Event) -> Result<Step> {
+        let mut needs_redraw = false;
+        let mut needs_recalc = false;
+        let mut typed = false;
+
+        match event {
+            // On Windows, crossterm also reports key Release events.
+            // Ignore them — otherwise the key-up events left over from
+            // typing `tr<Enter>` to launch get injected as input and
+            // immediately dismiss the selector.
+            Event::Key(key) if key.kind != KeyEventKind::Release => {
+                self.blink_epoch = Instant::now();
+                // Check for cancel keys (Ctrl+C or Esc)
+                let is_cancel = matches!(key.code, KeyCode::Esc)
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL));
+                if self.take_restored_query(key) {
+                    needs_redraw = true;
+                }
+
+                match key.code {
+                    _ if is_cancel => {
+                        if self.delete_mode {
+                            self.delete_mode = false;
+                            self.marked_for_deletion.clear();
+                            needs_redraw = true;
+                        } else {
+                            return Ok(Step::Exit(None));
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if self.delete_mode && !self.marked_for_deletion.is_empty() {
+                            return Ok(Step::ConfirmDelete);
+                        } else if let Some(path) = self.selected_missing_path() {
+                            // Deleted behind our back: say so and refresh.
+                            self.status = Some(format!("'{}' no longer exists.", path.display()));
+                            self.load_entries()?;
+                            needs_recalc = true;
+                        } else if let Some(group) = self.selected_group() {
+                            // Drill into the group.
+                            self.input_buffer = format!("@{} ", group);
+                            self.reset_viewport();
+                            needs_recalc = true;
+                        } else {
+                            // Alt+Enter on "Create new": no date suffix, kept
+                            // out of age-based cleanup.
+                            let permanent = key.modifiers.contains(KeyModifiers::ALT);
+                            if let Some(action) = self.handle_selection(permanent) {
+                                if let (SelectorMode::Scan(base), ShellAction::MkdirCd(path)) =
+                                    (&self.mode, &action)
+                                {
+                                    if !path.starts_with(base) {
+                                        return Ok(Step::ConfirmCreate(action));
+                                    }
+                                }
+                                if let (true, ShellAction::MkdirCd(path)) = (permanent, &action) {
+                                    self.remember_permanent(path);
+                                }
+                                return Ok(Step::Exit(Some(action)));
+                            }
+                        }
+                    }
+                    KeyCode::Up => {
+                        needs_redraw = self.cursor_up();
+                    }
+                    KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        needs_redraw = self.cursor_up();
+                    }
+                    KeyCode::Down => {
+                        needs_redraw = self.cursor_down();
+                    }
+                    KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        needs_redraw = self.cursor_down();
+                    }
+                    KeyCode::Backspace => {
+                        self.input_buffer.pop();
+                        self.reset_viewport();
+                        needs_recalc = true;
+                    }
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.show_scores = !self.show_scores;
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.open_in_file_manager();
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.toggle_pin();
+                        needs_recalc = true;
+                    }
+                    KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.copy_selected_path();
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if let Some(path) = self.archive_candidate() {
+                            return Ok(Step::ConfirmArchive(path));
+                        }
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.cycle_sort_mode();
+                        needs_recalc = true;
+                    }
+                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.date_headers = !self.date_headers;
+                        needs_recalc = true;
+                    }
+                    KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.next_base();
+                        needs_recalc = true;
+                    }
+                    KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.show_path = !self.show_path;
+                        self.path_scroll = 0;
+                        self.status = None;
+                        needs_redraw = true;
+                    }
+                    KeyCode::Left if self.show_path => {
+                        self.path_scroll = self.path_scroll.saturating_sub(PATH_SCROLL_STEP);
+                        needs_redraw = true;
+                    }
+                    KeyCode::Right if self.show_path => {
+                        self.path_scroll =
+                            (self.path_scroll + PATH_SCROLL_STEP).min(self.max_path_scroll());
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char('r')
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && matches!(self.mode, SelectorMode::Scan(_)) =>
+                    {
+                        self.match_paths = !self.match_paths;
+                        self.load_entries()?;
+                        self.reset_viewport();
+                        needs_recalc = true;
+                    }
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.filter_existing_only = !self.filter_existing_only;
+                        self.reset_viewport();
+                        needs_recalc = true;
+                    }
+                    KeyCode::Delete => {
+                        if self.delete_tap(Instant::now()) {
+                            self.toggle_delete_mark();
+                        }
+                        needs_redraw = true;
+                    }
+                    KeyCode::Char(c) if is_allowed_input_char(c) => {
+                        self.input_buffer.push(c);
+                        self.reset_viewport();
+                        needs_recalc = true;
+                        typed = true;
+                    }
+                    _ => {}
+                }
+            }
+            Event::Resize(w, h) => {
+                self.width = *w;
+                self.height = *h;
+                self.fit_inline(*h);
+            }
+            _ => {}
+        }
+
+        if needs_recalc {
+            self.refresh_scores();
+        }
+        if typed && self.auto_accept_single {
+            if let Some(entry) = self.sole_match() {
+                let action = match self.mode {
+                    SelectorMode::Scan(_) | SelectorMode::Archive(_) => {
+                        ShellAction::Cd(entry.path.clone())
+                    }
+                    SelectorMode::History(_) => ShellAction::Set(entry.path.clone()),
+                };
+                return Ok(Step::Exit(Some(action)));
+            }
+        }
+        Ok(Step::Continue {
+            redraw: needs_redraw || needs_recalc,
+        })
+    }
+
+    /// Split the input into an `@group` prefix (up to the first space) and
+    /// the search text. Only history entries have groups, so elsewhere an
+    /// `@` is just part of the search.
+    fn query_parts(&self) -> (Option<&str>, &str) {
+        if !matches!(self.mode, SelectorMode::History(_)) {
+            return (None, &self.input_buffer);
+        }
+        match self.input_buffer.strip_prefix('@') {
+            Some(rest) => match rest.split_once(' ') {
+                Some((group, search)) => (Some(group), search.trim_start()),
+                None => (Some(rest), ""),
+            },
+            None => (None, &self.input_buffer),
+        }
+    }
+
+    /// The text entries are matched against.
+    fn search(&self) -> &str {
+        self.query_parts().1
+    }
+
+    /// The group being shown: an `@group` query, else the session's. Always
+    /// `None` outside History mode.
+    fn active_group(&self) -> Option<&str> {
+        if !matches!(self.mode, SelectorMode::History(_)) {
+            return None;
+        }
+        self.query_parts()
+            .0
+            .filter(|g| !g.is_empty())
+            .or(self.group_filter.as_deref())
+    }
+
+    /// History mode with nothing typed and no group chosen: the known groups
+    /// are listed above the workspaces.
+    fn group_rows(&self) -> Vec<String> {
+        if !matches!(self.mode, SelectorMode::History(_))
+            || !self.input_buffer.is_empty()
+            || self.active_group().is_some()
+        {
+            return Vec::new();
+        }
+        let groups: BTreeSet<&str> = self
+            .entries
+            .iter()
+            .filter_map(|e| e.group.as_deref())
+            .collect();
+        groups.into_iter().map(String::from).collect()
+    }
+
+    /// The highlighted group row, if any.
+    fn selected_group(&self) -> Option<String> {
+        self.group_rows().into_iter().nth(self.cursor_pos)
+    }
+
+    /// Index into `get_filtered_entries` for the cursor (past the group rows).
+    fn entry_cursor(&self) -> Option<usize> {
+        self.cursor_pos.checked_sub(self.group_rows().len())
+    }
+
+    /// The one entry the query names, without asking: an exact name wins,
+    /// otherwise the best match if it clearly leads. On a near tie, the
+    /// contenders come back instead.
+    fn unique_match(&self) -> Result<&TryEntry, Vec<&TryEntry>> {
+        let query = self.search();
+        if let Some(exact) = self.entries.iter().find(|e| e.basename == query) {
+            return Ok(exact);
+        }
+        let matches = self.get_filtered_entries();
+        let Some(best) = matches.first() else {
+            return Err(Vec::new());
+        };
+        let contenders: Vec<&TryEntry> = matches
+            .iter()
+            .copied()
+            .take_while(|e| e.score >= best.score * AMBIGUITY_RATIO)
+            .collect();
+        match contenders.as_slice() {
+            [only] => Ok(only),
+            _ => Err(contenders),
+        }
+    }
+
+    /// The entry `auto_accept_single` jumps to: the only match of a query of
+    /// at least `AUTO_ACCEPT_MIN_QUERY` characters, once the scan is done.
+    fn sole_match(&self) -> Option<&TryEntry> {
+        if self.delete_mode
+            || self.scan.is_some()
+            || self.search().chars().count() < AUTO_ACCEPT_MIN_QUERY
+        {
+            return None;
+        }
+        match self.get_filtered_entries().as_slice() {
+            [only] if only.path.exists() => Some(only),
+            _ => None,
+        }
+    }
+
+    fn get_filtered_entries(&self) -> Vec<&TryEntry> {
+        let searching = !self.search().is_empty();
+        let group = self.active_group();
+        self.entries
+            .iter()
+            .filter(|e| group.is_none_or(|g| e.group.as_deref() == Some(g)))
+            .filter(|e| !searching || e.score > 0.0)
+            .filter(|e| !self.filter_existing_only || e.path.exists())
+            .collect()
+    }
+
+    /// The "Create new" / "Add path" row follows the entries while typing.
+    /// A query with `!` exclusions filters; it doesn't name a directory.
+    fn show_create_new(&self) -> bool {
+        self.allow_create && !self.search().is_empty() && !has_negation(self.search())
+    }
+
+    fn visible_count(&self) -> usize {
+        // Group rows + filtered entries + optional create new
+        self.group_rows().len()
+            + self.get_filtered_entries().len()
+            + if self.show_create_new() { 1 } else { 0 }
+    }
+
+    /// Below `FULL_LAYOUT_HEIGHT` rows the header and separators are dropped
+    /// so the list keeps at least one row.
+    fn is_compact(&self) -> bool {
+        self.height < FULL_LAYOUT_HEIGHT
+    }
+
+    /// Number of list rows that fit between the chrome.
+    fn max_visible(&self) -> usize {
+        if self.is_compact() {
+            // Search line + footer line
+            (self.height as usize).saturating_sub(2).max(1)
+        } else {
+            (self.height as usize).saturating_sub(8).max(3)
+        }
+    }
+
+    /// Whether this Delete press should toggle the mark. With
+    /// `delete_double_tap`, the first press outside delete mode only flashes
+    /// the entry; a second one on it within `DOUBLE_TAP_WINDOW` goes ahead.
+    fn delete_tap(&mut self, now: Instant) -> bool {
+        if !self.delete_double_tap || self.delete_mode || !self.allow_delete {
+            return true;
+        }
+        let Some(path) = self.selected_path() else {
+            return true;
+        };
+        match self.delete_armed.take() {
+            Some((armed, at)) if armed == path && now.duration_since(at) <= DOUBLE_TAP_WINDOW => {
+                true
+            }
+            _ => {
+                self.delete_armed = Some((path, now));
+                false
+            }
+        }
+    }
+
+    /// Blink phase of the search cursor: shown for the first `CURSOR_BLINK`
+    /// of each cycle, hidden for the second.
+    fn cursor_on(&self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.blink_epoch).as_millis();
+        (elapsed / CURSOR_BLINK.as_millis()).is_multiple_of(2)
+    }
+
+    /// Time left until the cursor next shows or hides.
+    fn until_blink(&self, now: Instant) -> Duration {
+        let elapsed = now.duration_since(self.blink_epoch).as_millis();
+        let into_phase = (elapsed % CURSOR_BLINK.as_millis()) as u64;
+        CURSOR_BLINK - Duration::from_millis(into_phase)
+    }
+
+    /// How long the flashed row still has to stay inverted, if at all.
+    fn flash_remaining(&self, now: Instant) -> Option<Duration> {
+        let (_, at) = self.delete_armed.as_ref()?;
+        DELETE_FLASH
+            .checked_sub(now.duration_since(*at))
+            .filter(|d| !d.is_zero())
+    }
+
+    fn toggle_delete_mark(&mut self) {
+        if !self.allow_delete {
+            return;
+        }
+        let filtered = self.get_filtered_entries();
+        let selected = self.entry_cursor().and_then(|i| filtered.get(i));
+        // Shared workspaces aren't in the history file, so there is nothing
+        // to remove them from.
+        if let Some(source) = selected.and_then(|e| e.source.clone()) {
+            self.status = Some(format!(
+                "Shared workspace from workspaces.d/{}; edit that file to remove it.",
+                source
+            ));
+            return;
+        }
+        let path_to_toggle = self.selected_path();
+
+        if let Some(path) = path_to_toggle {
+            if self.marked_for_deletion.contains(&path) {
+                self.marked_for_deletion.retain(|p| p != &path);
+            } else {
+                self.marked_for_deletion.push(path);
+                self.delete_mode = true;
+            }
+            if self.marked_for_deletion.is_empty() {
+                self.delete_mode = false;
+            }
+        }
+    }
+
+    /// Where "Create new" goes, and the name it gets before any suffix. An
+    /// absolute or `~/` query creates it there instead of under the base.
+    fn create_target(&self, base_path: &Path) -> Option<(PathBuf, String)> {
+        let query = self.search();
+        let target = if is_explicit_location(query, cfg!(windows)) {
+            expand_path(query)
+        } else {
+            base_path.join(query)
+        };
+        let name = target.file_name()?.to_string_lossy().replace(' ', "-");
+        Some((target, name))
+    }
+
+    /// The highlighted entry's path (`None` on the "Create new" row).
+    fn selected_path(&self) -> Option<PathBuf> {
+        let filtered = self.get_filtered_entries();
+        filtered.get(self.entry_cursor()?).map(|e| e.path.clone())
+    }
+
+    /// Ctrl-A: the highlighted directory, if it may be archived. Only Scan
+    /// mode archives (in `try set` an entry is a whole workspace root), and
+    /// deleting must be allowed. The caller confirms before `finish_archive`.
+    fn archive_candidate(&mut self) -> Option<PathBuf> {
+        if !matches!(self.mode, SelectorMode::Scan(_))
+            || self.archive_dir.is_none()
+            || !self.allow_delete
+        {
+            return None;
+        }
+        let path = self.selected_path().filter(|p| p.is_dir());
+        if path.is_none() {
+            self.status = Some("Nothing to archive.".to_string());
+        }
+        path
+    }
+
+    /// Tar `path` into `archive_dir`, remove it, and reload; or, unconfirmed,
+    /// just say it was left alone.
+    fn finish_archive(&mut self, path: &Path, confirmed: bool) -> Result<()> {
+        let Some(archive_dir) = self.archive_dir.clone().filter(|_| confirmed) else {
+            self.status = Some("Archive cancelled.".to_string());
+            return Ok(());
+        };
+        self.status = Some(match archive_workspace(path, &archive_dir) {
+            Ok(archive) => format!("Archived to {}", archive.display()),
+            Err(e) => format!("Archive failed: {}", e),
+        });
+        self.load_entries()
+    }
+
+    /// The highlighted entry's path, if it has disappeared since loading.
+    fn selected_missing_path(&self) -> Option<PathBuf> {
+        self.selected_path().filter(|p| !p.exists())
+    }
+
+    /// Ctrl-O: launch the file manager on the selected entry and keep the
+    /// selector open. Its output is discarded so it can't scribble over the
+    /// list; a thread reaps it once it exits.
+    fn open_in_file_manager(&mut self) {
+        let Some(path) = self.selected_path() else {
+            self.status = Some("Nothing to open.".to_string());
+            return;
+        };
+        let spawned = std::process::Command::new(self.opener)
+            .arg(&path)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        self.status = Some(match spawned {
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+                "Opened in file manager".to_string()
+            }
+            Err(e) => format!("Could not run {}: {}", self.opener, e),
+        });
+    }
+
+    /// Copy the highlighted entry's absolute path the `copy_method` way and
+    /// report the outcome in the footer. When nothing can reach a
+    /// clipboard (headless sessions, terminals that drop OSC 52), the path
+    /// lands in a file instead (see `save_copied_path`), so it is never
+    /// silently lost.
+    fn copy_selected_path(&mut self) {
+        let Some(path) = self.selected_path() else {
+            self.status = Some("Nothing to copy.".to_string());
+            return;
+        };
+        let text = canonicalize_clean(&path).to_string_lossy().to_string();
+        let result = match self.copy_method {
+            CopyMethod::Auto => self
+                .copy_to_clipboard(&text)
+                .or_else(|_| copy_with_command(&text))
+                .or_else(|_| copy_with_osc52(&text)),
+            CopyMethod::Clipboard => self.copy_to_clipboard(&text),
+            CopyMethod::Command => copy_with_command(&text),
+            CopyMethod::Osc52 => copy_with_osc52(&text),
+            CopyMethod::File => Err("copy_method is \"file\"".to_string()),
+        };
+        self.status = Some(match result {
+            Ok(how) => how,
+            Err(reason) => match save_copied_path(self.state_path.as_deref(), &text) {
+                Ok(file) => format!("Saved to {} ({})", file.display(), reason),
+                Err(e) => format!("Could not copy: {}; {}", reason, e),
+            },
+        });
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<String, String> {
+        let result = match &mut self.clipboard {
+            Some(clipboard) => clipboard.set_text(text),
+            None => arboard::Clipboard::new().and_then(|mut clipboard| {
+                let result = clipboard.set_text(text);
+                // Keep it alive: on X11 the contents vanish with the handle.
+                self.clipboard = Some(clipboard);
+                result
+            }),
+        };
+        result
+            .map(|()| "Copied to clipboard".to_string())
+            .map_err(|e| format!("clipboard unavailable: {}", e))
+    }
+
+    /// The action for the highlighted row. `permanent` creates the new
+    /// directory without the date suffix.
+    fn handle_selection(&self, permanent: bool) -> Option<ShellAction> {
+        let filtered = self.get_filtered_entries();
+        let pos = self.entry_cursor()?;
+
+        // Check if "Create new" / "Add path" is selected
+        if self.show_create_new() && pos == filtered.len() {
+            match &self.mode {
+                SelectorMode::Scan(base_path) => {
+                    // Create new directory with date suffix.
+                    let (target, name) = self.create_target(base_path)?;
+                    let dirname = if permanent {
+                        name
+                    } else {
+                        dated_name(&target, &name)
+                    };
+                    let path = target.with_file_name(dirname);
+                    return Some(ShellAction::MkdirCd(path));
+                }
+                SelectorMode::History(_) => {
+                    // Add new path to workspace (no date suffix)
+                    let path = expand_path(self.search());
+                    return Some(ShellAction::Set(path));
+                }
+                SelectorMode::Archive(_) => return None,
+            }
+        }
+
+        if pos < filtered.len() {
+            match &self.mode {
+                SelectorMode::Scan(_) | SelectorMode::Archive(_) => {
+                    return Some(ShellAction::Cd(filtered[pos].path.clone()))
+                }
+                SelectorMode::History(_) => {
+                    return Some(ShellAction::Set(filtered[pos].path.clone()))
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Initial load. Scan mode streams entries from a worker thread (see
+    /// `poll_scan`); opening the base directory still fails up front.
+    fn start_loading(&mut self) -> Result<()> {
+        match &self.mode {
+            // `load_entries` reports it in the status line.
+            SelectorMode::Scan(base_path) if is_non_directory(base_path) => self.load_entries(),
+            SelectorMode::Scan(base_path) => {
+                let dir = fs::read_dir(base_path)?;
+                let ignore = TryIgnore::load(base_path);
+                self.entries.clear();
+                let activity = self.file_activity.as_mut().map(FileActivity::take);
+                self.scan = Some(ScanJob::spawn(dir, ignore, self.icons.take(), activity));
+                Ok(())
+            }
+            SelectorMode::History(_) | SelectorMode::Archive(_) => self.load_entries(),
+        }
+    }
+
+    /// Collect entries from a running scan. Returns `true` when the list
+    /// should be redrawn: a batch was added, the scan finished, or the
+    /// spinner advanced.
+    fn poll_scan(&mut self, now: Instant) -> bool {
+        let Some(job) = &mut self.scan else {
+            return false;
+        };
+        let finished = loop {
+            match job.rx.try_recv() {
+                Ok(entry) => job.pending.push(entry),
+                Err(mpsc::TryRecvError::Empty) => break false,
+                Err(mpsc::TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        let mut redraw = false;
+        let mut batch = Vec::new();
+        if finished || job.pending.len() >= SCAN_BATCH {
+            job.pending.retain(|e| self.date_range.contains(e));
+            batch = std::mem::take(&mut job.pending);
+            redraw = true;
+        }
+        if now.duration_since(job.last_tick) >= SPINNER_TICK {
+            job.frame += 1;
+            job.last_tick = now;
+            redraw = true;
+        }
+        let mut ids_changed = false;
+        for entry in &mut batch {
+            ids_changed |= self.track_identity(entry);
+            self.apply_state(entry);
+        }
+        if ids_changed {
+            self.save_ids();
+        }
+        self.entries.append(&mut batch);
+        if finished {
+            if let Some(job) = self.scan.take() {
+                if let Ok((icons, activity)) = job.handle.join() {
+                    self.icons = icons;
+                    self.file_activity = activity;
+                }
+            }
+        }
+        if redraw {
+            self.refresh_scores();
+        }
+        redraw
+    }
+
+    fn load_entries(&mut self) -> Result<()> {
+        // A synchronous reload supersedes any scan still in flight.
+        self.scan = None;
+        let mut entries = Vec::new();
+        // Files where a workspace should be: listed in the status line
+        // instead of failing the whole load with "Not a directory".
+        let mut not_dirs: Vec<PathBuf> = Vec::new();
+        match &self.mode {
+            SelectorMode::Scan(base_path) if is_non_directory(base_path) => {
+                not_dirs.push(base_path.clone());
+            }
+            SelectorMode::Scan(base_path) => {
+                entries = scan_entries(base_path)?;
+                if self.match_paths {
+                    let nested = nested_entries(&entries, |p| {
+                        self.permanent.contains(p.to_string_lossy().as_ref())
+                    });
+                    entries.extend(nested);
+                }
+            }
+            SelectorMode::History(workspaces) => {
+                for record in workspaces {
+                    let path = &record.path;
+                    if is_non_directory(path) {
+                        not_dirs.push(path.clone());
+                        continue;
+                    }
+                    // Show all workspaces, even if path doesn't exist
+                    let metadata = fs::metadata(path).ok();
+                    let mtime = metadata
+                        .and_then(|m| m.modified().ok())
+                        .unwrap_or(SystemTime::UNIX_EPOCH); // Use epoch for non-existent paths
+
+                    // Use full path for history
+                    let basename = path.to_string_lossy().to_string();
+                    let mut entry = TryEntry::new(basename, path.clone(), mtime);
+                    entry.last_accessed = record.last_accessed;
+                    entry.verified = path.exists();
+                    entry.group = record.group.clone();
+                    entry.source = record.source.clone();
+                    (entry.kind, entry.worktrees) = WorkspaceKind::detect_with_worktrees(path);
+                    entry.sort_weight = record.sort_weight;
+                    entry.set_description(read_description(path));
+                    entries.push(entry);
+                }
+                // Reverse to show latest first by default if load order is preserved
+                entries.reverse();
+            }
+            SelectorMode::Archive(archive_dir) => {
+                entries = archive_entries(archive_dir);
+            }
+        }
+        if let Some(first) = not_dirs.first() {
+            self.status = Some(match not_dirs.len() {
+                1 => format!("Skipped {}: not a directory", first.display()),
+                n => format!(
+                    "Skipped {} and {} more: not directories",
+                    first.display(),
+                    n - 1
+                ),
+            });
+        }
+        entries.retain(|e| self.date_range.contains(e));
+        let mut ids_changed = false;
+        for entry in &mut entries {
+            entry.icon = self.icons.icon_for(&entry.path, entry.mtime);
+            if let Some(activity) = &mut self.file_activity {
+                activity.apply(entry);
+            }
+            ids_changed |= self.track_identity(entry);
+            self.apply_state(entry);
+        }
+        if ids_changed {
+            self.save_ids();
+        }
+        self.entries = entries;
+        Ok(())
+    }
+
+    fn refresh_scores(&mut self) {
+        let searching = !self.search().is_empty();
+        let (tokens, negated) = split_negations(&self.search().to_lowercase());
+        let tokens: Vec<(String, Vec<char>)> = tokens
+            .into_iter()
+            .map(|t| {
+                let chars = t.chars().collect();
+                (t, chars)
+            })
+            .collect();
+        let now = SystemTime::now();
+
+        for entry in &mut self.entries {
+            if negated
+                .iter()
+                .any(|n| entry.basename_down.contains(n.as_str()))
+            {
+                entry.score = 0.0;
+                continue;
+            }
+            entry.score = match tokens.as_slice() {
+                [] => self.scorer.score(entry, "", &[], now),
+                [(query, query_chars)] => self.scorer.score(entry, query, query_chars, now),
+                // Every token must match; the entry ranks by their mean.
+                _ => {
+                    let scores: Vec<f64> = tokens
+                        .iter()
+                        .map(|(query, query_chars)| {
+                            self.scorer.score(entry, query, query_chars, now)
+                        })
+                        .collect();
+                    if scores.contains(&0.0) {
+                        0.0
+                    } else {
+                        scores.iter().sum::<f64>() / scores.len() as f64
+                    }
+                }
+            };
+            let matched = tokens.is_empty() || entry.score > 0.0;
+            if matched && !negated.is_empty() {
+                // `!old` alone: everything not excluded stays visible.
+                entry.score = entry.score.max(f64::MIN_POSITIVE);
+            }
+            // Still filtered by the query, but outranks any unpinned match.
+            if entry.pinned && matched {
+                entry.score += PIN_BONUS;
+            }
+            // A negative weight sinks a match without hiding it.
+            if entry.sort_weight != 0.0 && matched {
+                entry.score = (entry.score + entry.sort_weight).max(f64::MIN_POSITIVE);
+            }
+        }
+
+        // The entries a query hides are never shown, so only the matches
+        // need ordering; the rest are parked behind them.
+        let mut hidden = Vec::new();
+        if searching {
+            let (matches, rest) = std::mem::take(&mut self.entries)
+                .into_iter()
+                .partition(|e| e.score > 0.0);
+            self.entries = matches;
+            hidden = rest;
+        }
+
+        let sort_mode = self.sort_mode;
+        // Pins stay on top whatever the order (the sort is stable).
+        self.entries.sort_by(|a, b| {
+            (!a.pinned).cmp(&!b.pinned).then_with(|| match sort_mode {
+                // High score first, then the most recently used
+                SortMode::Score => b.score.total_cmp(&a.score).then_with(|| {
+                    let used = |e: &TryEntry| e.last_accessed.unwrap_or(e.mtime);
+                    used(b).cmp(&used(a))
+                }),
+                SortMode::Mtime => b.mtime.cmp(&a.mtime),
+                SortMode::Name => a.basename_down.cmp(&b.basename_down),
+            })
+        });
+        if self.date_headers {
+            // One contiguous section per date, keeping the order within it.
+            let today = Local::now().date_naive();
+            self.entries
+                .sort_by_cached_key(|e| DateBucket::of(e, today));
+        }
+        self.entries.append(&mut hidden);
+    }
+
+    /// The lines of the list: every selectable row, preceded by a date
+    /// header wherever the section changes. Headers take screen lines but no
+    /// cursor position, so navigation never lands on them. Takes the
+    /// frame's filtered view so `render` filters only once.
+    fn list_rows(&self, groups: usize, filtered: &[&TryEntry]) -> Vec<ListRow> {
+        let mut rows: Vec<ListRow> = (0..groups).map(ListRow::Item).collect();
+        let today = Local::now().date_naive();
+        let mut current = None;
+        for (i, entry) in filtered.iter().enumerate() {
+            if self.date_headers {
+                let bucket = DateBucket::of(entry, today);
+                if current != Some(bucket) {
+                    rows.push(ListRow::Header(bucket));
+                    current = Some(bucket);
+                }
+            }
+            rows.push(ListRow::Item(groups + i));
+        }
+        if self.show_create_new() {
+            rows.push(ListRow::Item(groups + filtered.len()));
+        }
+        rows
+    }
+
+    /// Keep `ids` in step with a freshly loaded entry before its flags are
+    /// applied. A known identity at a new path whose old path is gone means
+    /// the directory was moved: its pin and permanence move with it.
+    /// Returns `true` when `ids` (and maybe the flags) changed.
+    fn track_identity(&mut self, entry: &TryEntry) -> bool {
+        let Some(id) = &entry.file_id else {
+            return false;
+        };
+        let path = entry.path.to_string_lossy().to_string();
+        let remembered = self.pins.contains(&path)
+            || self.permanent.contains(&path)
+            || self.visits.contains_key(&path)
+            || self.bases.contains(&entry.path);
+        match self.ids.get(id) {
+            Some(old) if *old == path => false,
+            Some(old) if !Path::new(old).exists() => {
+                let old = old.clone();
+                if let Some(state_path) = &self.state_path {
+                    let mut event = WorkspaceEvent::now(EventKind::Renamed, &entry.path);
+                    event.from = Some(PathBuf::from(&old));
+                    let _ = WorkspaceManager::log_events(state_path, &[event]);
+                }
+                if self.pins.remove(&old) {
+                    self.pins.insert(path.clone());
+                }
+                if self.permanent.remove(&old) {
+                    self.permanent.insert(path.clone());
+                }
+                if let Some(visit) = self.visits.remove(&old) {
+                    self.visits.insert(path.clone(), visit);
+                }
+                let old_path = PathBuf::from(&old);
+                if let Some(base) = self.bases.iter_mut().find(|b| **b == old_path) {
+                    *base = entry.path.clone();
+                }
+                if let Some(history_path) = &self.history_path {
+                    if let Err(e) =
+                        WorkspaceManager::rename_workspace_to(history_path, &old_path, &entry.path)
+                    {
+                        self.status = Some(format!("Could not update the history: {}", e));
+                    }
+                }
+                self.ids.insert(id.clone(), path);
+                true
+            }
+            // Unknown, or a reused inode: only remembered entries are tracked.
+            _ if remembered => {
+                self.ids.insert(id.clone(), path);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn save_ids(&mut self) {
+        if let Some(state_path) = &self.state_path {
+            if let Err(e) = TryState::remember_ids(
+                state_path,
+                &self.ids,
+                &self.pins,
+                &self.permanent,
+                &self.visits,
+            ) {
+                self.status = Some(format!("Could not save moved entries: {}", e));
+            }
+        }
+    }
+
+    /// Copy pins, permanent marks and the last visit from `state.toml` onto
+    /// a freshly loaded entry. A visit newer than the directory's mtime (or
+    /// history access) counts as its last access for recency.
+    fn apply_state(&self, entry: &mut TryEntry) {
+        let key = entry.path.to_string_lossy();
+        entry.pinned = self.pins.contains(key.as_ref());
+        entry.permanent = self.permanent.contains(key.as_ref());
+        if let Some(&nanos) = self.visits.get(key.as_ref()) {
+            let visit = SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos);
+            entry.last_accessed = Some(entry.last_accessed.unwrap_or(entry.mtime).max(visit));
+        }
+    }
+
+    /// Remember a directory created with Alt+Enter as permanent.
+    fn remember_permanent(&mut self, path: &Path) {
+        self.permanent.insert(path.to_string_lossy().to_string());
+        if let Some(state_path) = &self.state_path {
+            // The selector is about to exit, so there's nowhere to show it.
+            let _ = TryState::remember_permanent(state_path, path);
+        }
+    }
+
+    /// Pin or unpin the highlighted entry and remember it.
+    fn toggle_pin(&mut self) {
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        let key = path.to_string_lossy().to_string();
+        let pinned = !self.pins.contains(&key);
+        if pinned {
+            self.pins.insert(key);
+        } else {
+            self.pins.remove(&key);
+        }
+        let mut file_id = None;
+        for entry in self.entries.iter_mut().filter(|e| e.path == path) {
+            entry.pinned = pinned;
+            file_id = entry.file_id.clone();
+        }
+        if let Some(state_path) = &self.state_path {
+            if let Err(e) = TryState::remember_pin(state_path, &path, pinned) {
+                self.status = Some(format!("Could not save pin: {}", e));
+            }
+        }
+        // Track it right away, in case it is moved before the next run.
+        if let (true, Some(id)) = (pinned, file_id) {
+            self.ids.insert(id, path.to_string_lossy().to_string());
+            self.save_ids();
+        }
+    }
+
+    /// Scan the next base path in `bases` instead, keeping the query. Marks
+    /// and the cursor belong to the old list and are dropped.
+    fn next_base(&mut self) {
+        let SelectorMode::Scan(current) = &self.mode else {
+            return;
+        };
+        if self.bases.len() < 2 {
+            self.status = Some("No other base paths; add one with `try init`.".to_string());
+            return;
+        }
+        let next = match self.bases.iter().position(|b| b == current) {
+            Some(i) => self.bases[(i + 1) % self.bases.len()].clone(),
+            None => self.bases[0].clone(),
+        };
+        self.mode = SelectorMode::Scan(next.clone());
+        self.workspace_path = next.clone();
+        self.delete_mode = false;
+        self.marked_for_deletion.clear();
+        self.delete_armed = None;
+        self.reset_viewport();
+        if let Some(mode) = self
+            .state_path
+            .as_deref()
+            .and_then(|p| TryState::load_from(p).ok())
+            .and_then(|state| state.sort_for(&next))
+        {
+            self.sort_mode = mode;
+        }
+        if let Err(e) = self.load_entries() {
+            self.status = Some(format!("Cannot read {}: {}", next.display(), e));
+        }
+    }
+
+    /// Switch to the next sort mode and remember it for this base path.
+    fn cycle_sort_mode(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.reset_viewport();
+        if let (SelectorMode::Scan(base_path), Some(state_path)) = (&self.mode, &self.state_path) {
+            if let Err(e) = TryState::remember_sort(state_path, base_path, self.sort_mode) {
+                self.status = Some(format!("Could not save sort mode: {}", e));
+            }
+        }
+    }
+
+    /// Draw one frame. The commands are collected in memory and written
+    /// with a single `write_all` + flush, so a slow terminal gets one write
+    /// per frame and never shows a half-drawn one.
+    fn render<W: Write>(&mut self, out: &mut W) -> Result<()> {
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.clear();
+        let drawn = self.draw_frame(&mut frame);
+        let written = out.write_all(&frame).and_then(|()| out.flush());
+        self.frame = frame;
+        drawn?;
+        Ok(written?)
+    }
+
+    fn draw_frame(&mut self, stderr: &mut Vec<u8>) -> Result<()> {
+        // Instead of Clear(All), we move to top and overwrite.
+        // This reduces flickering and bandwidth.
+        stderr.queue(cursor::MoveTo(0, self.top))?;
+
+        if self.height < MIN_HEIGHT {
+            // Not even room for search + one row + footer.
+            self.clear_screen(stderr)?;
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print("terminal too small"))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+            return Ok(());
+        }
+        let compact = self.is_compact();
+
+        let separator = "─".repeat((self.width as usize).saturating_sub(1));
+
+        // Header (dropped in the compact layout)
+        if !compact {
+            stderr.queue(SetForegroundColor(Color::Red))?; // Orange-ish
+            stderr.queue(SetAttribute(Attribute::Bold))?;
+            let title = "📁 Try Selector";
+            stderr.queue(Print(title))?;
+
+            let mut tags = String::new();
+            if let Some(group) = self.active_group() {
+                tags.push_str(&format!(" · @{}", group));
+            }
+            if self.sort_mode != SortMode::Score {
+                tags.push_str(&format!(" · by {}", self.sort_mode.label()));
+            }
+            if self.read_only {
+                tags.push_str(" · read-only");
+            }
+            if matches!(self.mode, SelectorMode::Archive(_)) {
+                tags.push_str(" · archives");
+            }
+            // Show workspace path, cut short rather than wrapped: the tags
+            // keep their room first, then the path gets what's left.
+            let room = (self.width as usize).saturating_sub(1 + title.width() + " @ ".len());
+            let path = self.workspace_path.display().to_string();
+            let path = truncate_to_width(&path, room.saturating_sub(tags.width()).max(room / 2));
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(" @ "))?;
+            stderr.queue(SetForegroundColor(Color::Cyan))?;
+            stderr.queue(Print(&path))?;
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(truncate_to_width(
+                &tags,
+                room.saturating_sub(path.width()),
+            )))?;
+
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+            stderr.queue(Clear(ClearType::UntilNewLine))?; // Clear rest of line
+            stderr.queue(Print("\r\n"))?;
+
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(&separator))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+            stderr.queue(Clear(ClearType::UntilNewLine))?;
+            stderr.queue(Print("\r\n"))?;
+        }
+
+        // Search bar
+        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+        stderr.queue(Print("Search: "))?;
+        stderr.queue(SetAttribute(Attribute::Reset))?;
+
+        // Render search text with cursor
+        // A restored query is dimmed until it is kept or replaced.
+        stderr.queue(SetAttribute(Attribute::Bold))?;
+        stderr.queue(SetForegroundColor(if self.query_restored {
+            Color::DarkGrey
+        } else {
+            Color::Yellow
+        }))?;
+        stderr.queue(Print(&self.input_buffer))?;
+        self.cursor_drawn = self.cursor_on(Instant::now());
+        if self.cursor_drawn {
+            stderr.queue(SetAttribute(Attribute::Reverse))?;
+        }
+        stderr.queue(Print(" "))?; // Cursor block
+        stderr.queue(SetAttribute(Attribute::Reset))?;
+        if self.filter_existing_only {
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(" [existing only]"))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+        }
+        stderr.queue(Clear(ClearType::UntilNewLine))?;
+        stderr.queue(Print("\r\n"))?;
+
+        if !compact {
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(&separator))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+            stderr.queue(Clear(ClearType::UntilNewLine))?;
+            stderr.queue(Print("\r\n"))?;
+        }
+
+        // List
+        let max_visible = self.max_visible();
+
+        // Lay out the rows first to update scroll_offset (in screen lines)
+        let groups = self.group_rows();
+        let filtered = self.get_filtered_entries();
+        let rows = self.list_rows(groups.len(), &filtered);
+        let cursor_line = rows
+            .iter()
+            .position(|r| *r == ListRow::Item(self.cursor_pos))
+            .unwrap_or(0);
+        // Keep the section header above the cursor in view when scrolling up.
+        let top_line = match cursor_line.checked_sub(1).map(|l| rows[l]) {
+            Some(ListRow::Header(_)) => cursor_line - 1,
+            _ => cursor_line,
+        };
+
+        // Adjust scroll (stored once the list is drawn, as `filtered`
+        // borrows the selector until then)
+        let mut scroll = self.scroll_offset;
+        if top_line < scroll {
+            scroll = top_line;
+        } else if cursor_line >= scroll + max_visible {
+            scroll = cursor_line + 1 - max_visible;
+        }
+
+        // Highlight the tokens in turn; exclusions aren't highlighted.
+        let search = split_negations(self.search()).0.concat();
+        let search = search.as_str();
+        let visible_end = (scroll + max_visible).min(rows.len());
+
+        for line in &rows[scroll..visible_end] {
+            let row = match *line {
+                ListRow::Header(bucket) => {
+                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                    stderr.queue(SetAttribute(Attribute::Bold))?;
+                    stderr.queue(Print(format!("── {}", bucket.label())))?;
+                    stderr.queue(SetAttribute(Attribute::Reset))?;
+                    stderr.queue(Clear(ClearType::UntilNewLine))?;
+                    stderr.queue(Print("\r\n"))?;
+                    continue;
+                }
+                ListRow::Item(row) => row,
+            };
+            let is_selected = row == self.cursor_pos;
+            // Columns after the cursor; the last one is left free so a full
+            // row never wraps.
+            let room = (self.width as usize).saturating_sub(3);
+
+            // Cursor
+            if is_selected {
+                stderr.queue(SetAttribute(Attribute::Bold))?;
+                stderr.queue(SetForegroundColor(Color::Yellow))?;
+                stderr.queue(Print("→ "))?;
+                stderr.queue(SetAttribute(Attribute::Reset))?;
+            } else {
+                stderr.queue(Print("  "))?;
+            }
+
+            if let Some(group) = groups.get(row) {
+                let count = filtered
+                    .iter()
+                    .filter(|e| e.group.as_deref() == Some(group.as_str()))
+                    .count();
+                if is_selected {
+                    stderr.queue(SetAttribute(Attribute::Bold))?;
+                }
+                stderr.queue(Print(format!("📂 @{}", group)))?;
+                stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                stderr.queue(Print(format!(" ({})", count)))?;
+                stderr.queue(SetAttribute(Attribute::Reset))?;
+                stderr.queue(Clear(ClearType::UntilNewLine))?;
+                stderr.queue(Print("\r\n"))?;
+                continue;
+            }
+            let i = row - groups.len();
+
+            if i < filtered.len() {
+                let entry = filtered[i];
+                let is_marked = self.marked_for_deletion.contains(&entry.path);
+
+                let flashed = self
+                    .delete_armed
+                    .as_ref()
+                    .is_some_and(|(p, _)| *p == entry.path);
+                if flashed && self.flash_remaining(Instant::now()).is_some() {
+                    let icon = entry.icon.as_deref().unwrap_or(entry.kind.icon());
+                    let row = format!("{} {}", icon, entry.basename);
+                    stderr.queue(SetAttribute(Attribute::Reverse))?;
+                    stderr.queue(Print(truncate_to_width(&row, room)))?;
+                    stderr.queue(SetAttribute(Attribute::Reset))?;
+                    stderr.queue(Clear(ClearType::UntilNewLine))?;
+                    stderr.queue(Print("\r\n"))?;
+                    continue;
+                }
+
+                let prefix = if is_marked {
+                    "🗑️  ".to_string()
+                } else if !entry.verified {
+                    "❌ ".to_string()
+                } else if entry.pinned {
+                    "📌 ".to_string()
+                } else if entry.permanent {
+                    "♾  ".to_string()
+                } else {
+                    let icon = entry.icon.as_deref().unwrap_or(entry.kind.icon());
+                    format!("{} ", icon)
+                };
+                stderr.queue(Print(&prefix))?;
+                if is_marked {
+                    stderr.queue(SetAttribute(Attribute::CrossedOut))?;
+                } else if !entry.verified {
+                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                    stderr.queue(SetAttribute(Attribute::CrossedOut))?;
+                }
+                let room = room.saturating_sub(prefix.width());
+                let name = truncate_to_width(&entry.basename, room);
+
+                if is_selected {
+                    stderr.queue(SetAttribute(Attribute::Bold))?;
+                }
+
+                // Render Name (Name + Date suffix)
+                // Assuming format Name-YYYY-MM-DD
+                if let Some(caps) = date_suffix_regex().captures(&name) {
+                    let name_part = caps.get(1).unwrap().as_str();
+                    let date_part = caps.get(2).unwrap().as_str();
+
+                    let matched = self.print_highlighted(stderr, name_part, search, is_selected)?;
+
+                    if !search.is_empty() && search.contains('-') {
+                        stderr.queue(SetForegroundColor(Color::Yellow))?;
+                        stderr.queue(SetAttribute(Attribute::Bold))?;
+                        stderr.queue(Print("-"))?;
+                        stderr.queue(SetAttribute(Attribute::Reset))?;
+                        if is_selected {
+                            stderr.queue(SetAttribute(Attribute::Bold))?;
+                        }
+                    } else {
+                        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                        stderr.queue(Print("-"))?;
+                    }
+
+                    // Carry on matching where the name left off, so date
+                    // searches like `2024` light up in the suffix too.
+                    let rest: String = search
+                        .chars()
+                        .skip(matched)
+                        .skip_while(|&c| c == '-')
+                        .collect();
+                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                    if rest.contains(|c: char| c.is_ascii_digit()) {
+                        self.print_highlighted_in(
+                            stderr,
+                            date_part,
+                            &rest,
+                            is_selected,
+                            Some(Color::DarkGrey),
+                        )?;
+                    } else {
+                        stderr.queue(Print(date_part))?;
+                    }
+                    if let Some(counter) = caps.get(3) {
+                        stderr.queue(Print(counter.as_str()))?;
+                    }
+
+                    stderr.queue(SetAttribute(Attribute::Reset))?;
+                    if is_selected {
+                        stderr.queue(SetAttribute(Attribute::Bold))?;
+                    }
+                    if is_marked {
+                        stderr.queue(SetAttribute(Attribute::CrossedOut))?;
+                    }
+                } else {
+                    self.print_highlighted(stderr, &name, search, is_selected)?;
+                }
+
+                stderr.queue(SetAttribute(Attribute::Reset))?;
+
+                let mut details = String::new();
+                if let Some(desc) = &entry.description {
+                    details.push_str(&format!("  {}", desc));
+                }
+                if let Some(target) = &entry.link_target {
+                    details.push_str(&format!(" → {}", target.display()));
+                }
+                if let Some(n) = entry.worktrees {
+                    details.push_str(&format!(
+                        " [{} worktree{}]",
+                        n,
+                        if n == 1 { "" } else { "s" }
+                    ));
+                }
+                if let Some(source) = &entry.source {
+                    details.push_str(&format!(" [{}]", source));
+                }
+                if !details.is_empty() {
+                    let room = room.saturating_sub(name.width());
+                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                    stderr.queue(Print(truncate_to_width(&details, room)))?;
+                    stderr.queue(SetAttribute(Attribute::Reset))?;
+                }
+
+                // Meta (Time) - Right aligned simplified
+                // let time_str = format_relative_time(entry.mtime);
+                // Basic alignment logic could go here, omitting for brevity/complexity balance
+                // stderr.queue(cursor::MoveToColumn(self.width - 15))?;
+                // stderr.queue(Print(time_str))?;
+
+                if self.show_scores {
+                    let score_str = format_score(entry.score);
+                    let col = (self.width as usize).saturating_sub(score_str.len() + 1);
+                    stderr.queue(cursor::MoveToColumn(col as u16))?;
+                    stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+                    stderr.queue(Print(score_str))?;
+                    stderr.queue(SetAttribute(Attribute::Reset))?;
+                }
+            } else {
+                // Create New / Add Path Option
+                if is_selected {
+                    stderr.queue(SetAttribute(Attribute::Bold))?;
+                }
+                match &self.mode {
+                    SelectorMode::Scan(base_path) => {
+                        // The suffix as `handle_selection` will pick it,
+                        // counter included.
+                        let suffix = match self.create_target(base_path) {
+                            Some((target, name)) => {
+                                dated_name(&target, &name)[name.len()..].to_string()
+                            }
+                            None => format!("-{}", today_suffix()),
+                        };
+                        let row = format!("✨ Create new: {}{}", search, suffix);
+                        stderr.queue(Print(truncate_to_width(&row, room)))?;
+                    }
+                    SelectorMode::History(_) => {
+                        let row = format!("📌 Add path: {}", search);
+                        stderr.queue(Print(truncate_to_width(&row, room)))?;
+                    }
+                    SelectorMode::Archive(_) => {}
+                }
+                stderr.queue(SetAttribute(Attribute::Reset))?;
+            }
+
+            stderr.queue(Clear(ClearType::UntilNewLine))?;
+            stderr.queue(Print("\r\n"))?;
+        }
+
+        // Fill remaining empty lines in the list area with blanks/clear
+        let shown = filtered.len();
+        self.scroll_offset = scroll;
+        let mut lines_to_clear = max_visible.saturating_sub(visible_end - self.scroll_offset);
+        if let (Some(job), true) = (&self.scan, lines_to_clear > 0) {
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(format!("  {} Scanning…", job.spinner())))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+            stderr.queue(Clear(ClearType::UntilNewLine))?;
+            stderr.queue(Print("\r\n"))?;
+            lines_to_clear -= 1;
+        }
+        for _ in 0..lines_to_clear {
+            stderr.queue(Clear(ClearType::CurrentLine))?;
+            stderr.queue(Print("\r\n"))?;
+        }
+
+        // Footer
+        if compact {
+            stderr.queue(cursor::MoveTo(0, self.top + self.height - 1))?;
+        } else {
+            stderr.queue(cursor::MoveTo(0, self.top + self.height - 2))?;
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(&separator))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+            stderr.queue(Clear(ClearType::UntilNewLine))?;
+            stderr.queue(Print("\r\n"))?;
+        }
+
+        let footer = if let Some(status) = &self.status {
+            stderr.queue(SetAttribute(Attribute::Bold))?;
+            status.clone()
+        } else if self.delete_mode {
+            stderr.queue(SetAttribute(Attribute::Bold))?;
+            stderr.queue(SetForegroundColor(Color::Red))?;
+            format!(
+                "DELETE MODE ({} marked) | Enter: Confirm | Esc: Cancel",
+                self.marked_for_deletion.len()
+            )
+        } else if let Some(path) = self.path_detail().filter(|_| self.show_path) {
+            path
+        } else {
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            let hint = if self.allow_delete {
+                "↑↓: Navigate  Enter: Select  Del: Delete  Esc: Cancel"
+            } else {
+                "↑↓: Navigate  Enter: Select  Esc: Cancel"
+            };
+            if self.match_paths {
+                format!("Matching paths  {}", hint)
+            } else {
+                hint.to_string()
+            }
+        };
+        stderr.queue(Print(&footer))?;
+        stderr.queue(SetAttribute(Attribute::Reset))?;
+        stderr.queue(Clear(ClearType::UntilNewLine))?;
+
+        // Right-aligned scroll position and counts, dropped when they'd run
+        // into the footer.
+        let is_item = |r: &ListRow| matches!(r, ListRow::Item(_));
+        let above = rows[..self.scroll_offset]
+            .iter()
+            .filter(|r| is_item(r))
+            .count();
+        let below = rows[visible_end..].iter().filter(|r| is_item(r)).count();
+        let mut info = Vec::new();
+        if above > 0 {
+            info.push(format!("({} above)", above));
+        }
+        if below > 0 {
+            info.push(format!("({} more below)", below));
+        }
+        if self.show_counts {
+            info.push(self.counts_label(shown));
+        }
+        let info = info.join(" ");
+        let used = footer.width() + info.width() + 2;
+        if !info.is_empty() && used <= self.width as usize {
+            let col = self.width as usize - info.width() - 1;
+            stderr.queue(cursor::MoveToColumn(col as u16))?;
+            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
+            stderr.queue(Print(info))?;
+            stderr.queue(SetAttribute(Attribute::Reset))?;
+        }
+        Ok(())
+    }
+
+    /// The selection's absolute path, as Ctrl-G shows it.
+    fn selected_full_path(&self) -> Option<String> {
+        let path = canonicalize_clean(&self.selected_path()?);
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    /// Footer columns the path may use.
+    fn path_width(&self) -> usize {
+        (self.width as usize).saturating_sub(1).max(3)
+    }
+
+    /// Furthest `path_scroll` that still fills the footer: the path's tail
+    /// behind a leading `…`. Scroll counts characters; fitting counts
+    /// display columns, so wide characters take two.
+    fn max_path_scroll(&self) -> usize {
+        let Some(path) = self.selected_full_path() else {
+            return 0;
+        };
+        let width = self.path_width();
+        if path.width() <= width {
+            return 0;
+        }
+        let mut cols = path.width() + 1;
+        let mut start = 0;
+        for c in path.chars() {
+            if cols <= width {
+                break;
+            }
+            cols -= c.width().unwrap_or(0);
+            start += 1;
+        }
+        start
+    }
+
+    /// The selection's absolute path from `path_scroll` characters in,
+    /// with `…` wherever it is cut off to fit the width.
+    fn path_detail(&self) -> Option<String> {
+        let path = self.selected_full_path()?;
+        let width = self.path_width();
+        if path.width() <= width {
+            return Some(path);
+        }
+        let start = self.path_scroll.min(self.max_path_scroll());
+        if start == 0 {
+            return Some(truncate_to_width(&path, width).into_owned());
+        }
+        let rest: String = path.chars().skip(start).collect();
+        Some(format!("…{}", truncate_to_width(&rest, width - 1)))
+    }
+
+    /// Print `text` with the query's (subsequence) matches in yellow.
+    /// Returns how many query characters were matched.
+    fn print_highlighted<W: Write>(
+        &self,
+        stderr: &mut W,
+        text: &str,
+        query: &str,
+        is_selected: bool,
+    ) -> Result<usize> {
+        self.print_highlighted_in(stderr, text, query, is_selected, None)
+    }
+
+    /// `print_highlighted` for text drawn in `color`, which the highlight
+    /// would otherwise reset.
+    fn print_highlighted_in<W: Write>(
+        &self,
+        stderr: &mut W,
+        text: &str,
+        query: &str,
+        is_selected: bool,
+        color: Option<Color>,
+    ) -> Result<usize> {
+        if query.is_empty() {
+            stderr.queue(Print(text))?;
+            return Ok(0);
+        }
+
+        let text_chars: Vec<char> = text.chars().collect();
+        let query_chars: Vec<char> = query.to_lowercase().chars().collect();
+        let text_lower: Vec<char> = text.to_lowercase().chars().collect();
+
+        let mut query_idx = 0;
+
+        for (i, c) in text_chars.iter().enumerate() {
+            if query_idx < query_chars.len() && text_lower[i] == query_chars[query_idx] {
+                stderr.queue(SetForegroundColor(Color::Yellow))?;
+                stderr.queue(SetAttribute(Attribute::Bold))?;
+                stderr.queue(Print(c))?;
+
+                // Reset attributes but restore selection state if needed
+                stderr.queue(SetAttribute(Attribute::Reset))?;
+                if is_selected {
+                    stderr.queue(SetAttribute(Attribute::Bold))?;
+                }
+                if let Some(color) = color {
+                    stderr.queue(SetForegroundColor(color))?;
+                }
+
+                query_idx += 1;
+            } else {
+                stderr.queue(Print(c))?;
+            }
+        }
+        Ok(query_idx)
+    }
+
+    /// "12 of 340", plus how many are marked for deletion.
+    fn counts_label(&self, shown: usize) -> String {
+        let mut label = format!("{} of {}", shown, self.entries.len());
+        if !self.marked_for_deletion.is_empty() {
+            label.push_str(&format!(" · {} marked", self.marked_for_deletion.len()));
+        }
+        label
+    }
+
+    /// Ask for a single `y`/`n`; anything else (or Esc) cancels.
+    fn confirm_keypress(&self, stderr: &mut Stderr, prompt: &str) -> Result<bool> {
+        self.clear_screen(stderr)?;
+        stderr.execute(cursor::MoveTo(0, self.top))?;
+        stderr.execute(Print(prompt))?;
+        loop {
+            if event::poll(Duration::from_millis(100))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Release {
+                        continue; // ignore key-up events (Windows)
+                    }
+                    return Ok(matches!(key.code, KeyCode::Char('y' | 'Y')));
+                }
+            }
+        }
+    }
+
+    fn confirm_type_yes(&self, stderr: &mut Stderr) -> Result<bool> {
+        // Simple confirmation via raw input (not full UI dialog for brevity)
+        self.clear_screen(stderr)?;
+        stderr.execute(cursor::MoveTo(0, self.top))?;
+        stderr.execute(Print(format!(
+            "Delete {} directories? Type YES to confirm: ",
+            self.marked_for_deletion.len()
+        )))?;
+
+        // We need to temporarily disable raw mode or handle string input manually.
+        // Let's handle manually character by character
+        let mut input = String::new();
+        loop {
+            if event::poll(Duration::from_millis(100))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Release {
+                        continue; // ignore key-up events (Windows)
+                    }
+                    match key.code {
+                        KeyCode::Enter => break,
+                        KeyCode::Char(c) => {
+                            input.push(c);
+                            stderr.execute(Print(c))?;
+                        }
+                        KeyCode::Backspace => {
+                            input.pop();
+                            stderr.execute(cursor::MoveLeft(1))?;
+                            stderr.execute(Print(" "))?;
+                            stderr.execute(cursor::MoveLeft(1))?;
+                        }
+                        KeyCode::Esc => {
+                            input.clear();
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(input == "YES")
+    }
+
+    fn confirm_batch_delete(&mut self, stderr: &mut Stderr) -> Result<()> {
+        let confirmed = match self.delete_confirmation {
+            DeleteConfirmation::None => true,
+            DeleteConfirmation::Keypress => {
+                let prompt = format!(
+                    "Delete {} directories? [y/N] ",
+                    self.marked_for_deletion.len()
+                );
+                self.confirm_keypress(stderr, &prompt)?
+            }
+            DeleteConfirmation::TypeYes => self.confirm_type_yes(stderr)?,
+        };
+        self.finish_batch_delete(confirmed)
+    }
+
+    /// Delete the marked entries (or workspace records) if `confirmed`, then
+    /// leave delete mode and reload.
+    fn finish_batch_delete(&mut self, confirmed: bool) -> Result<()> {
+        if confirmed {
+            let count = self.marked_for_deletion.len();
+            match &self.mode {
+                SelectorMode::History(_) => {
+                    // In History mode, remove from config file
+                    if let Err(e) = WorkspaceManager::remove_workspaces(&self.marked_for_deletion) {
+                        self.status = Some(format!("Error removing workspaces: {}", e));
+                    } else {
+                        self.status = Some(format!("Removed {} workspaces.", count));
+                    }
+                }
+                SelectorMode::Scan(_) => {
+                    // In Scan mode, delete directories from filesystem
+                    for path in &self.marked_for_deletion {
+                        if path.exists() {
+                            fs::remove_dir_all(path)?;
+                        }
+                    }
+                    self.status = Some(format!("Deleted {} items.", count));
+                }
+                SelectorMode::Archive(_) => {
+                    for path in &self.marked_for_deletion {
+                        if path.exists() {
+                            fs::remove_file(path)?;
+                        }
+                    }
+                    self.status = Some(format!("Deleted {} archives.", count));
+                }
+            }
+        } else {
+            self.status = Some("Delete cancelled.".to_string());
+        }
+
+        self.marked_for_deletion.clear();
+        self.delete_mode = false;
+        // Reload entries
+        self.load_entries()?;
+        Ok(())
+    }
+}
+
+/// `s` cut to at most `max_cols` display columns at a character boundary,
+/// ending in `…` when anything was dropped.
+fn truncate_to_width(s: &str, max_cols: usize) -> Cow<'_, str> {
+    if s.width() <= max_cols {
+        return Cow::Borrowed(s);
+    }
+    let Some(budget) = max_cols.checked_sub(1) else {
+        return Cow::Borrowed("");
+    };
+    let mut cols = 0;
+    let mut end = 0;
+    for (i, c) in s.char_indices() {
+        cols += c.width().unwrap_or(0);
+        if cols > budget {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+    Cow::Owned(format!("{}…", &s[..end]))
+}
+
+/// One `--emit-events` input line as a terminal event: `{"key": ...}` with
+/// optional `ctrl` / `alt`, or `{"resize": [width, height]}`. Keys are a
+/// single character or Enter, Esc, Backspace, Delete, Tab, Up, Down.
+fn parse_event_line(line: &str) -> Result<Event, String> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Input {
+        key: Option<String>,
+        #[serde(default)]
+        ctrl: bool,
+        #[serde(default)]
+        alt: bool,
+        resize: Option<(u16, u16)>,
+    }
+    let input: Input = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    if let Some((w, h)) = input.resize {
+        return Ok(Event::Resize(w, h));
+    }
+    let key = input.key.ok_or("expected \"key\" or \"resize\"")?;
+    let code = match key.as_str() {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        _ => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("unknown key {:?}", key)),
+            }
+        }
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    if input.ctrl {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    if input.alt {
+        modifiers |= KeyModifiers::ALT;
+    }
+    Ok(Event::Key(KeyEvent::new(code, modifiers)))
+}
+
+/// The first clipboard tool that suits this session, as argv.
+fn clipboard_command(
+    wayland: bool,
+    x11: bool,
+    on_path: &dyn Fn(&str) -> bool,
+) -> Option<Vec<&'static str>> {
+    let candidates: [(bool, &[&'static str]); 5] = [
+        (wayland, &["wl-copy"]),
+        (x11, &["xclip", "-selection", "clipboard"]),
+        (x11, &["xsel", "--clipboard", "--input"]),
+        (cfg!(target_os = "macos"), &["pbcopy"]),
+        (true, &["clip.exe"]),
+    ];
+    candidates
+        .into_iter()
+        .find(|(usable, argv)| *usable && on_path(argv[0]))
+        .map(|(_, argv)| argv.to_vec())
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Pipe `text` into a clipboard tool.
+fn copy_with_command(text: &str) -> Result<String, String> {
+    let argv = clipboard_command(
+        env::var_os("WAYLAND_DISPLAY").is_some(),
+        env::var_os("DISPLAY").is_some(),
+        &on_path,
+    )
+    .ok_or("no clipboard tool found")?;
+    let mut child = std::process::Command::new(argv[0])
+        .args(&argv[1..])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| format!("{}: {}", argv[0], e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("{}: {}", argv[0], e))?;
+    }
+    match child.wait() {
+        Ok(status) if status.success() => Ok(format!("Copied with {}", argv[0])),
+        Ok(status) => Err(format!("{} failed ({})", argv[0], status)),
+        Err(e) => Err(format!("{}: {}", argv[0], e)),
+    }
+}
+
+/// Whether the terminal is likely to honor OSC 52. Terminals that are
+/// known to drop it do so silently, so those are ruled out up front.
+fn osc52_likely(term: Option<&str>, term_program: Option<&str>) -> bool {
+    let term = term.unwrap_or("");
+    let dumb = term.is_empty() || term == "dumb" || term == "linux";
+    !dumb && term_program != Some("Apple_Terminal")
+}
+
+/// The OSC 52 escape that sets the clipboard to `text`.
+fn osc52_sequence(text: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    format!("\x1b]52;c;{}\x07", encoded)
+}
+
+fn copy_with_osc52(text: &str) -> Result<String, String> {
+    let term = env::var("TERM").ok();
+    let term_program = env::var("TERM_PROGRAM").ok();
+    if !osc52_likely(term.as_deref(), term_program.as_deref()) {
+        return Err("terminal doesn't support OSC 52".to_string());
+    }
+    let mut stderr = io::stderr();
+    stderr
+        .write_all(osc52_sequence(text).as_bytes())
+        .and_then(|()| stderr.flush())
+        .map_err(|e| e.to_string())?;
+    Ok("Sent to the terminal clipboard (OSC 52)".to_string())
+}
+
+/// Where the copy fallback leaves `text`: `copied-path` next to
+/// `state.toml`, or without a state file a fresh file in the temp dir
+/// (created new, since a fixed name there could be planted by anyone).
+fn save_copied_path(state_path: Option<&Path>, text: &str) -> io::Result<PathBuf> {
+    if let Some(dir) = state_path.and_then(Path::parent) {
+        fs::create_dir_all(dir)?;
+        let file = dir.join("copied-path");
+        fs::write(&file, text)?;
+        return Ok(file);
+    }
+    for n in 0u32.. {
+        let name = format!("try-copied-path-{}-{}", std::process::id(), n);
+        let file = env::temp_dir().join(name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file)
+        {
+            Ok(mut f) => {
+                f.write_all(text.as_bytes())?;
+                return Ok(file);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Whether nothing can be created in `dir` (a read-only mount, or a shared
+/// directory owned by someone else). Asks the OS instead of probing with a
+/// throwaway subdirectory, which would bump the base's mtime and wake file
+/// watchers on every launch.
+#[cfg(unix)]
+fn is_read_only_dir(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    match fs::metadata(dir) {
+        Ok(meta) if meta.permissions().readonly() => true,
+        Ok(_) => {
+            let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+                return false;
+            };
+            // SAFETY: `path` is a NUL-terminated string that outlives the call.
+            unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) != 0 }
+        }
+        // Missing: `prepare_base` creates it unless told otherwise.
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_read_only_dir(dir: &Path) -> bool {
+    fs::metadata(dir).is_ok_and(|meta| meta.permissions().readonly())
+}
+
+/// Fail early, with a readable message, when `base_path` is a symlink whose
+/// target is gone (e.g. an unmounted external drive). Creating the directory
+/// or scanning it would otherwise surface as a bare IO error.
+fn check_base_available(base_path: &Path) -> Result<(), TryError> {
+    let is_symlink = fs::symlink_metadata(base_path).is_ok_and(|m| m.file_type().is_symlink());
+    if is_symlink && fs::metadata(base_path).is_err() {
+        let target = fs::read_link(base_path).unwrap_or_default();
+        return Err(TryError::WorkspaceUnavailable {
+            path: base_path.to_path_buf(),
+            reason: format!(
+                "symlink target '{}' does not exist (unmounted drive?)",
+                target.display()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Whether `path` exists but is something other than a directory. Missing
+/// paths are not, so callers can still list workspaces that are gone.
+fn is_non_directory(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| !m.is_dir())
+}
+
+/// List the (non-hidden) directories directly under `base_path`. Returns an
+/// empty list when the base path doesn't exist yet.
+fn scan_entries(base_path: &Path) -> Result<Vec<TryEntry>> {
+    let mut entries = Vec::new();
+    if !base_path.exists() {
+        return Ok(entries);
+    }
+    let ignore = TryIgnore::load(base_path);
+    for entry in fs::read_dir(base_path)? {
+        let entry = entry?;
+        if ignore.matches(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        if let Some(try_entry) = scan_entry(&entry)? {
+            entries.push(try_entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// The workspaces one level inside `entries`, named by their path relative
+/// to the base (`client/idea-2025-01-01`): dated directories, as "Create
+/// new" makes them, and the `permanent` ones. Other subdirectories are a
+/// project's own and stay out.
+fn nested_entries(entries: &[TryEntry], permanent: impl Fn(&Path) -> bool) -> Vec<TryEntry> {
+    let mut nested = Vec::new();
+    for parent in entries {
+        let Ok(dir) = fs::read_dir(&parent.path) else {
+            continue;
+        };
+        for child in dir.flatten() {
+            let Ok(Some(entry)) = scan_entry(&child) else {
+                continue;
+            };
+            if !date_suffix_regex().is_match(&entry.basename) && !permanent(&entry.path) {
+                continue;
+            }
+            let basename = format!("{}/{}", parent.basename, entry.basename);
+            nested.push(TryEntry {
+                basename_down: basename.to_lowercase(),
+                basename,
+                ..entry
+            });
+        }
+    }
+    nested
+}
+
+/// Name of the per-workspace file holding a short description of it.
+const DESCRIPTION_FILE: &str = ".try-desc";
+
+/// The first non-blank line of `dir`'s `.try-desc`, trimmed. Only the start
+/// of the file is read, so a stray large file can't slow the scan.
+fn read_description(dir: &Path) -> Option<String> {
+    use std::io::Read;
+    let file = fs::File::open(dir.join(DESCRIPTION_FILE)).ok()?;
+    let mut head = Vec::new();
+    file.take(1024).read_to_end(&mut head).ok()?;
+    String::from_utf8_lossy(&head)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+/// Name of the per-base file listing directories to leave out of the list.
+const TRY_IGNORE_FILE: &str = ".tryignore";
+
+/// Patterns from a base path's `.tryignore`: one glob per line (`*`, `?`,
+/// `[abc]`), matched against directory names. Blank lines and `#` comments
+/// are skipped; a trailing `/` is allowed.
+#[derive(Debug, Default)]
+struct TryIgnore {
+    patterns: Vec<Regex>,
+}
+
+impl TryIgnore {
+    /// A missing or unreadable file ignores nothing.
+    fn load(base_path: &Path) -> Self {
+        fs::read_to_string(base_path.join(TRY_IGNORE_FILE))
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    fn parse(text: &str) -> Self {
+        let patterns = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .filter_map(|l| Regex::new(&glob_to_regex(l.trim_end_matches('/'))).ok())
+            .collect();
+        Self { patterns }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.patterns.iter().any(|re| re.is_match(name))
+    }
+}
+
+/// `size_exclude` when config.toml doesn't set it: version control data,
+/// dependencies and build output, which dwarf the work itself.
+const DEFAULT_SIZE_EXCLUDE: &[&str] = &[".git", "node_modules", "target", "__pycache__"];
+
+/// Bytes under a workspace, split into what `size_exclude` leaves out and
+/// the rest.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct WorkspaceSize {
+    included: u64,
+    excluded: u64,
+}
+
+/// The bytes under `dir` outside `exclude`, for sorting by size; 0 when it
+/// can't be read.
+fn dir_size(dir: &Path, exclude: &[String]) -> u64 {
+    compute_workspace_size(dir, exclude).map_or(0, |s| s.included)
+}
+
+/// Sum file sizes under `dir`, like `du --apparent-size`. Entries whose
+/// name matches an `exclude` glob, at any depth, count as `excluded` along
+/// with everything below them. Symlinks aren't followed, and unreadable
+/// subdirectories are skipped.
+fn compute_workspace_size(dir: &Path, exclude: &[String]) -> io::Result<WorkspaceSize> {
+    let exclude = TryIgnore::parse(&exclude.join("\n"));
+    let mut size = WorkspaceSize::default();
+    let mut stack = vec![(dir.to_path_buf(), false)];
+    let mut top = true;
+    while let Some((dir, excluded)) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if top => return Err(e),
+            Err(_) => continue,
+        };
+        top = false;
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let excluded = excluded || exclude.matches(&entry.file_name().to_string_lossy());
+            if meta.is_dir() {
+                stack.push((entry.path(), excluded));
+            } else if excluded {
+                size.excluded += meta.len();
+            } else {
+                size.included += meta.len();
+            }
+        }
+    }
+    Ok(size)
+}
+
+/// Byte count in binary units, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Anchored regex for a shell glob. An unclosed `[` is taken literally.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' => {
+                let class: String = chars.clone().take_while(|&c| c != ']').collect();
+                if class.is_empty() || chars.clone().nth(class.chars().count()).is_none() {
+                    re.push_str(r"\[");
+                    continue;
+                }
+                chars.nth(class.chars().count()); // skip the class and `]`
+                let (negate, class) = match class.strip_prefix('!') {
+                    Some(rest) => ("^", rest),
+                    None => ("", class.as_str()),
+                };
+                re.push('[');
+                re.push_str(negate);
+                re.push_str(&class.replace('\\', r"\\").replace('[', r"\["));
+                re.push(']');
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+/// One directory listing item as an entry; `None` for files and hidden dirs.
+fn scan_entry(entry: &fs::DirEntry) -> io::Result<Option<TryEntry>> {
+    let path = entry.path();
+    if !path.is_dir() {
+        return Ok(None);
+    }
+    let basename = path.file_name().unwrap().to_string_lossy().to_string();
+    if basename.starts_with(".") {
+        return Ok(None);
+    }
+
+    let metadata = fs::metadata(&path)?;
+    let mtime = metadata.modified()?;
+    let link_target = entry
+        .file_type()
+        .is_ok_and(|t| t.is_symlink())
+        .then(|| fs::read_link(&path).ok())
+        .flatten();
+
+    let mut try_entry = TryEntry::new(basename, path, mtime);
+    try_entry.link_target = link_target;
+    (try_entry.kind, try_entry.worktrees) = WorkspaceKind::detect_with_worktrees(&try_entry.path);
+    try_entry.file_id = file_id(&metadata);
+    try_entry.set_description(read_description(&try_entry.path));
+    Ok(Some(try_entry))
+}
+
+/// A directory's identity that survives renames and moves within one
+/// filesystem: device and inode. Not available off Unix.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    Some(format!("{}:{}", metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<String> {
+    None
+}
+
+/// For a git worktree checkout, the main repository's git dir and the
+/// checkout's own: its `.git` is a file whose `gitdir:` names the latter,
+/// whose `commondir` names the former. `None` for anything else.
+fn worktree_git_dirs(dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    let dot_git = fs::read_to_string(dir.join(".git")).ok()?;
+    let gitdir = dir.join(dot_git.trim().strip_prefix("gitdir:")?.trim());
+    let commondir = fs::read_to_string(gitdir.join("commondir")).ok()?;
+    Some((gitdir.join(commondir.trim()), gitdir))
+}
+
+/// How many worktrees the repository with git dir `common` has.
+fn worktree_count(common: &Path) -> Option<usize> {
+    let worktrees = fs::read_dir(common.join("worktrees")).ok()?;
+    Some(
+        worktrees
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .count(),
+    )
+}
+
+/// How a directory came about, as far as its `.git` tells: a checkout with
+/// an `origin` remote was cloned, one whose `.git` is a file is a worktree,
+/// and anything else was made by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WorkspaceKind {
+    Clone { url: String },
+    Worktree { main_repo: PathBuf, branch: String },
+    Manual,
+}
+
+impl WorkspaceKind {
+    fn detect(dir: &Path) -> Self {
+        Self::detect_with_worktrees(dir).0
+    }
+
+    /// `detect`, plus how many worktrees the main repository has when `dir`
+    /// is one of them, from a single read of the `.git` links.
+    fn detect_with_worktrees(dir: &Path) -> (Self, Option<usize>) {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            let kind = fs::read_to_string(dot_git.join("config"))
+                .ok()
+                .and_then(|config| origin_url(&config))
+                .map_or(WorkspaceKind::Manual, |url| WorkspaceKind::Clone { url });
+            return (kind, None);
+        }
+        let Some((common, gitdir)) = worktree_git_dirs(dir) else {
+            return (WorkspaceKind::Manual, None);
+        };
+        let count = worktree_count(&common);
+        let common = canonicalize_clean(&common);
+        let main_repo = match (common.file_name(), common.parent()) {
+            (Some(name), Some(parent)) if name == ".git" => parent.to_path_buf(),
+            _ => common.clone(),
+        };
+        let head = fs::read_to_string(gitdir.join("HEAD")).unwrap_or_default();
+        let branch = head
+            .trim()
+            .strip_prefix("ref: refs/heads/")
+            .unwrap_or("(detached)")
+            .to_string();
+        (WorkspaceKind::Worktree { main_repo, branch }, count)
+    }
+
+    /// List icon when no project icon applies.
+    fn icon(&self) -> &'static str {
+        match self {
+            WorkspaceKind::Clone { .. } => "📦",
+            WorkspaceKind::Worktree { .. } => "🌿",
+            WorkspaceKind::Manual => DEFAULT_ICON,
+        }
+    }
+
+    /// One-line description for `try info`.
+    fn describe(&self) -> String {
+        match self {
+            WorkspaceKind::Clone { url } => format!("clone of {}", url),
+            WorkspaceKind::Worktree { main_repo, branch } => {
+                format!("worktree of {} on {}", main_repo.display(), branch)
+            }
+            WorkspaceKind::Manual => "manual".to_string(),
+        }
+    }
+}
+
+/// The `url` of `[remote "origin"]` in a git config file.
+fn origin_url(config: &str) -> Option<String> {
+    let mut in_origin = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_origin = line == r#"[remote "origin"]"#;
+        } else if let Some((key, value)) = line.split_once('=') {
+            if in_origin && key.trim() == "url" {
+                return Some(value.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// How long `git_log_since` waits for git.
+const GIT_LOG_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Commits `git_log_since` lists at most.
+const GIT_LOG_LIMIT: usize = 10;
+
+/// When a workspace was started: the date in its name, else the
+/// directory's creation (or, where that isn't recorded, modification) date.
+fn workspace_created(dir: &Path) -> io::Result<NaiveDate> {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    if let Some(date) = date_suffix_regex()
+        .captures(&name)
+        .and_then(|caps| NaiveDate::parse_from_str(&caps[2], "%Y-%m-%d").ok())
+    {
+        return Ok(date);
+    }
+    let metadata = fs::metadata(dir)?;
+    let time = metadata.created().or_else(|_| metadata.modified())?;
+    Ok(DateTime::<Local>::from(time).date_naive())
+}
+
+/// `git log --oneline --since=<since>` in `dir`, at most `GIT_LOG_LIMIT`
+/// lines. `None` when git fails or takes longer than `timeout` (it is
+/// killed then), so a slow repository can't hold anything up.
+fn git_log_since(dir: &Path, since: NaiveDate, timeout: Duration) -> Option<Vec<String>> {
+    let mut child = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["log", "--oneline", "--no-color"])
+        .arg(format!("--max-count={}", GIT_LOG_LIMIT))
+        .arg(format!("--since={} 00:00", since))
+        .arg("HEAD")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .ok()?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+    let mut out = String::new();
+    io::Read::read_to_string(&mut child.stdout.take()?, &mut out).ok()?;
+    Some(out.lines().map(String::from).collect())
+}
+
+/// Braille spinner shown in the list while a background scan runs.
+const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_TICK: Duration = Duration::from_millis(80);
+/// Streamed entries are added to the list (and redrawn) this many at a time.
+const SCAN_BATCH: usize = 10;
+
+/// A base-path scan running on a worker thread, so a huge root doesn't block
+/// the first frame. Entries (with icons) are streamed back over a channel;
+/// the thread hands the icon and file-activity caches back when it finishes.
+struct ScanJob {
+    rx: mpsc::Receiver<TryEntry>,
+    handle: std::thread::JoinHandle<(ProjectIcons, Option<FileActivity>)>,
+    pending: Vec<TryEntry>,
+    frame: usize,
+    last_tick: Instant,
+}
+
+impl ScanJob {
+    fn spawn(
+        dir: fs::ReadDir,
+        ignore: TryIgnore,
+        mut icons: ProjectIcons,
+        mut activity: Option<FileActivity>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            // Unreadable items are skipped rather than failing the whole list.
+            for entry in dir.flatten() {
+                if ignore.matches(&entry.file_name().to_string_lossy()) {
+                    continue;
+                }
+                if let Ok(Some(mut try_entry)) = scan_entry(&entry) {
+                    try_entry.icon = icons.icon_for(&try_entry.path, try_entry.mtime);
+                    if let Some(activity) = &mut activity {
+                        activity.apply(&mut try_entry);
+                    }
+                    if tx.send(try_entry).is_err() {
+                        break; // selector went away
+                    }
+                }
+            }
+            (icons, activity)
+        });
+        Self {
+            rx,
+            handle,
+            pending: Vec::new(),
+            frame: 0,
+            last_tick: Instant::now(),
+        }
+    }
+
+    fn spinner(&self) -> char {
+        SPINNER_FRAMES[self.frame % SPINNER_FRAMES.len()]
+    }
+}
+
+/// How deep `newest_file_mtime` looks below a directory.
+const FILE_ACTIVITY_DEPTH: usize = 3;
+/// Directory entries `newest_file_mtime` reads before settling for what it
+/// has, so one huge checkout can't stall the list.
+const FILE_ACTIVITY_LIMIT: usize = 2_000;
+
+/// `recency_from_files`: when an entry was last worked on, judged by the
+/// newest file inside it. A directory's own mtime only changes when files
+/// are added or removed, not when they're edited. Walks are cached by path
+/// for the session, so reloads don't repeat them.
+#[derive(Default)]
+struct FileActivity {
+    cache: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileActivity {
+    /// Take over the cache, for a worker thread.
+    fn take(&mut self) -> FileActivity {
+        FileActivity {
+            cache: std::mem::take(&mut self.cache),
+        }
+    }
+
+    /// Fold the newest file's mtime into `entry.last_accessed`, which the
+    /// recency bonus reads.
+    fn apply(&mut self, entry: &mut TryEntry) {
+        let newest = *self
+            .cache
+            .entry(entry.path.clone())
+            .or_insert_with(|| newest_file_mtime(&entry.path));
+        if let Some(newest) = newest {
+            entry.last_accessed = Some(entry.last_accessed.unwrap_or(entry.mtime).max(newest));
+        }
+    }
+}
+
+/// Newest mtime among the files under `dir`, up to `FILE_ACTIVITY_DEPTH`
+/// levels down and `FILE_ACTIVITY_LIMIT` entries in. Symlinks and the
+/// `DEFAULT_SIZE_EXCLUDE` directories (`.git`, `node_modules`, ...) are
+/// skipped.
+fn newest_file_mtime(dir: &Path) -> Option<SystemTime> {
+    let mut newest = None;
+    let mut budget = FILE_ACTIVITY_LIMIT;
+    let mut stack = vec![(dir.to_path_buf(), 1)];
+    while let Some((dir, depth)) = stack.pop() {
+        let Ok(read) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read.flatten() {
+            if budget == 0 {
+                return newest;
+            }
+            budget -= 1;
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                if depth < FILE_ACTIVITY_DEPTH
+                    && !DEFAULT_SIZE_EXCLUDE.contains(&name.to_string_lossy().as_ref())
+                {
+                    stack.push((entry.path(), depth + 1));
+                }
+            } else if file_type.is_file() {
+                let mtime = entry.metadata().and_then(|m| m.modified()).ok();
+                newest = newest.max(mtime);
+            }
+        }
+    }
+    newest
+}
+
+/// Icon shown for a directory that matches no project marker.
+const DEFAULT_ICON: &str = "📁";
+
+/// Built-in marker files and their icons, checked in order.
+const DEFAULT_PROJECT_ICONS: &[(&str, &str)] = &[
+    ("Cargo.toml", "🦀"),
+    ("package.json", "🟨"),
+    ("go.mod", "🐹"),
+    ("pyproject.toml", "🐍"),
+    ("requirements.txt", "🐍"),
+    ("Gemfile", "💎"),
+    ("pom.xml", "☕"),
+    ("build.gradle", "☕"),
+    ("CMakeLists.txt", "🔧"),
+    ("Makefile", "🔧"),
+];
+
+/// Picks a per-entry icon from the marker files at the top of each directory.
+/// Results are cached by path and directory mtime, so a reload only re-reads
+/// directories whose contents changed.
+#[derive(Default)]
+struct ProjectIcons {
+    /// `(marker file, icon)`, first match wins.
+    rules: Vec<(String, String)>,
+    cache: HashMap<PathBuf, (SystemTime, Option<String>)>,
+}
+
+impl ProjectIcons {
+    /// Built-in rules, with `overrides` (from `[icons]` in `config.toml`)
+    /// taking precedence. Overrides are checked in marker-name order.
+    fn new(overrides: Option<&HashMap<String, String>>) -> Self {
+        let mut rules: Vec<(String, String)> = overrides
+            .map(|o| o.iter().map(|(m, i)| (m.clone(), i.clone())).collect())
+            .unwrap_or_default();
+        rules.sort();
+        for (marker, icon) in DEFAULT_PROJECT_ICONS {
+            if !rules.iter().any(|(m, _)| m == marker) {
+                rules.push((marker.to_string(), icon.to_string()));
+            }
+        }
+        Self {
+            rules,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// A copy of the rules that takes over the cache, for a worker thread.
+    fn take(&mut self) -> ProjectIcons {
+        ProjectIcons {
+            rules: self.rules.clone(),
+            cache: std::mem::take(&mut self.cache),
+        }
+    }
+
+    /// Icon for the directory at `path`, or `None` for the generic one.
+    fn icon_for(&mut self, path: &Path, mtime: SystemTime) -> Option<String> {
+        if let Some((cached_mtime, icon)) = self.cache.get(path) {
+            if *cached_mtime == mtime {
+                return icon.clone();
+            }
+        }
+        let icon = self.detect(path);
+        self.cache.insert(path.to_path_buf(), (mtime, icon.clone()));
+        icon
+    }
+
+    fn detect(&self, path: &Path) -> Option<String> {
+        let names: Vec<_> = fs::read_dir(path)
+            .ok()?
+            .filter_map(|e| e.ok().map(|e| e.file_name()))
+            .collect();
+        self.rules
+            .iter()
+            .find(|(marker, _)| names.iter().any(|n| n.as_os_str() == marker.as_str()))
+            .map(|(_, icon)| icon.clone())
+    }
+}
+
+/// The `limit` most recently touched entries, in `sort` order. Only those
+/// are sized for `RecentSort::Size`, skipping `size_exclude` as `try info` does.
+fn recent_entries(
+    mut entries: Vec<TryEntry>,
+    limit: usize,
+    sort: RecentSort,
+    size_exclude: &[String],
+) -> Vec<TryEntry> {
+    entries.sort_by_key(|e| std::cmp::Reverse(e.mtime));
+    entries.truncate(limit);
+    match sort {
+        RecentSort::Mtime => {}
+        RecentSort::Name => entries.sort_by(|a, b| a.basename_down.cmp(&b.basename_down)),
+        RecentSort::Size => {
+            entries.sort_by_cached_key(|e| std::cmp::Reverse(dir_size(&e.path, size_exclude)))
+        }
+    }
+    entries
+}
+
+// ============================================================================
+// Scoring
+//
+// `Scorer` abstracts how an entry is ranked against the query. The default is
+// the fuzzy subsequence scorer ported from the Ruby `try`; `TRY_SCORER` selects
+// an alternative.
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScorerKind {
+    Fuzzy,
+    Substring,
+}
+
+impl ScorerKind {
+    /// Parse a scorer name (case-insensitive).
+    fn parse(name: &str) -> Option<ScorerKind> {
+        match name.trim().to_lowercase().as_str() {
+            "fuzzy" | "default" => Some(ScorerKind::Fuzzy),
+            "substring" | "substr" => Some(ScorerKind::Substring),
+            _ => None,
+        }
+    }
+
+    /// Scorer selected by `TRY_SCORER`, falling back to fuzzy.
+    fn detect() -> ScorerKind {
+        env::var("TRY_SCORER")
+            .ok()
+            .and_then(|s| ScorerKind::parse(&s))
+            .unwrap_or(ScorerKind::Fuzzy)
+    }
+
+    fn scorer(self, recency: Recency) -> Box<dyn Scorer> {
+        match self {
+            ScorerKind::Fuzzy => Box::new(FuzzyScorer { recency }),
+            ScorerKind::Substring => Box::new(SubstringScorer { recency }),
+        }
+    }
+}
+
+/// Ranks an entry against the (lowercased) query. A score of `0.0` with a
+/// non-empty query means "no match" and hides the entry.
+trait Scorer {
+    fn score(&self, entry: &TryEntry, query: &str, query_chars: &[char], now: SystemTime) -> f64;
+    /// How well `text_down` alone matches, without the date and recency
+    /// bonuses; `0.0` is no match.
+    fn match_text(&self, text_down: &str, query: &str, query_chars: &[char]) -> f64;
+}
+
+/// Fuzzy subsequence matching with boundary/proximity/density bonuses.
+#[derive(Default)]
+struct FuzzyScorer {
+    recency: Recency,
+}
+
+impl Scorer for FuzzyScorer {
+    fn score(&self, entry: &TryEntry, query: &str, query_chars: &[char], now: SystemTime) -> f64 {
+        calculate_score(entry, query, query_chars, now, &self.recency)
+    }
+
+    fn match_text(&self, text_down: &str, _query: &str, query_chars: &[char]) -> f64 {
+        fuzzy_match(text_down, text_down.len(), query_chars, 0.0)
+    }
+}
+
+/// Contiguous substring matching: the query must appear verbatim, and matches
+/// closer to the start (or on a word boundary) rank higher. Predictable on
+/// long names where fuzzy subsequences match almost anything.
+#[derive(Default)]
+struct SubstringScorer {
+    recency: Recency,
+}
+
+impl Scorer for SubstringScorer {
+    fn score(&self, entry: &TryEntry, query: &str, query_chars: &[char], now: SystemTime) -> f64 {
+        let mut score = date_suffix_bonus(entry);
+
+        if !query.is_empty() {
+            let base = score;
+            score = substring_match(
+                &entry.basename_down,
+                entry.basename.len(),
+                query,
+                query_chars,
+                base,
+            );
+            score = or_description(entry, base, score, |desc_down, len| {
+                substring_match(desc_down, len, query, query_chars, 0.0)
+            });
+            if score == 0.0 {
+                return 0.0;
+            }
+        }
+
+        score + self.recency.bonus(entry, now)
+    }
+
+    fn match_text(&self, text_down: &str, query: &str, query_chars: &[char]) -> f64 {
+        substring_match(text_down, text_down.len(), query, query_chars, 0.0)
+    }
+}
+
+/// The matching half of `SubstringScorer`: `base` plus the match bonuses,
+/// scaled down for a long `len`; `0.0` when `query` doesn't appear.
+fn substring_match(
+    text_down: &str,
+    len: usize,
+    query: &str,
+    query_chars: &[char],
+    base: f64,
+) -> f64 {
+    let Some(byte_pos) = text_down.find(query) else {
+        return 0.0;
+    };
+    let pos = text_down[..byte_pos].chars().count();
+    let is_boundary = text_down[..byte_pos]
+        .chars()
+        .last()
+        .is_none_or(|c| !c.is_alphanumeric());
+
+    let mut score = base + 2.0 * query_chars.len() as f64;
+    if is_boundary {
+        score += 2.0;
+    }
+    // Position bonus: earlier matches rank higher
+    score += 5.0 / (pos as f64 + 1.0);
+
+    // Length penalty
+    score * 10.0 / (len as f64 + 10.0)
+}
+
+/// How much more a match in a history path's last component counts than
+/// one spread over its parents.
+const LEAF_WEIGHT: f64 = 3.0;
+
+/// `try set`: history entries are full paths, so a query like `proj` would
+/// match anything under `~/projects`. This adds the match in the last
+/// component (the project's own name), weighted by `LEAF_WEIGHT`, on top of
+/// the full-path score.
+struct LeafScorer {
+    inner: Box<dyn Scorer>,
+}
+
+impl LeafScorer {
+    /// `scorer` as the selector in `mode` should use it.
+    fn for_mode(scorer: Box<dyn Scorer>, mode: &SelectorMode) -> Box<dyn Scorer> {
+        match mode {
+            SelectorMode::History(_) => Box::new(LeafScorer { inner: scorer }),
+            SelectorMode::Scan(_) | SelectorMode::Archive(_) => scorer,
+        }
+    }
+}
+
+impl Scorer for LeafScorer {
+    fn score(&self, entry: &TryEntry, query: &str, query_chars: &[char], now: SystemTime) -> f64 {
+        let score = self.inner.score(entry, query, query_chars, now);
+        if query.is_empty() || score == 0.0 {
+            return score;
+        }
+        let leaf = entry
+            .basename_down
+            .trim_end_matches(['/', '\\'])
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default();
+        score + LEAF_WEIGHT * self.inner.match_text(leaf, query, query_chars)
+    }
+
+    fn match_text(&self, text_down: &str, query: &str, query_chars: &[char]) -> f64 {
+        self.inner.match_text(text_down, query, query_chars)
+    }
+}
+
+/// How much a match in a workspace's description counts next to the same
+/// match in its name.
+const DESCRIPTION_WEIGHT: f64 = 0.5;
+
+/// `name_score`, or `base` plus the description's match (`match_text` on the
+/// lowercased text and its length) scaled by `DESCRIPTION_WEIGHT`, whichever
+/// is higher. An entry whose name misses can still match on its description.
+fn or_description(
+    entry: &TryEntry,
+    base: f64,
+    name_score: f64,
+    match_text: impl Fn(&str, usize) -> f64,
+) -> f64 {
+    let (Some(desc), Some(desc_down)) = (&entry.description, &entry.description_down) else {
+        return name_score;
+    };
+    let desc_score = match_text(desc_down, desc.len());
+    if desc_score == 0.0 {
+        return name_score;
+    }
+    name_score.max(base + DESCRIPTION_WEIGHT * desc_score)
+}
+
+/// Render a score for the Ctrl-D debug overlay. Fixed precision so entries
+/// that only differ by float noise visibly tie.
+fn format_score(score: f64) -> String {
+    format!("{:.3}", score)
+}
+
+/// Default date suffix bonus (ends with digit)
+fn date_suffix_bonus(entry: &TryEntry) -> f64 {
+    if entry
+        .basename
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_numeric())
+    {
+        2.0
+    } else {
+        0.0
+    }
+}
+
+/// Added to pinned entries' scores: more than any match plus recency can earn.
+const PIN_BONUS: f64 = 1000.0;
+
+/// `try open --print` only picks a winner that outscores the runner-up by
+/// more than this factor; closer races are reported as ambiguous.
+const AMBIGUITY_RATIO: f64 = 0.9;
+
+/// Shortest query `auto_accept_single` acts on, so the first keystroke or
+/// two can't whisk the user away.
+const AUTO_ACCEPT_MIN_QUERY: usize = 3;
+
+/// Shape of the recency bonus shared by all scorers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Recency {
+    /// Bonus for an entry touched just now.
+    weight: f64,
+    /// Hours after which the bonus has halved. Longer keeps old workspaces
+    /// near the top; shorter favours whatever was touched last.
+    half_life_hours: f64,
+}
+
+impl Default for Recency {
+    /// `3 / sqrt(hours + 1)`, the curve of the original Ruby `try`.
+    fn default() -> Self {
+        Recency {
+            weight: 3.0,
+            half_life_hours: 3.0,
+        }
+    }
+}
+
+impl Recency {
+    /// Decays with the square root of hours since last touch (last access
+    /// for workspaces, else mtime).
+    fn bonus(&self, entry: &TryEntry, now: SystemTime) -> f64 {
+        match now.duration_since(entry.last_accessed.unwrap_or(entry.mtime)) {
+            Ok(duration) => {
+                let hours = duration.as_secs_f64() / 3600.0;
+                self.weight / (1.0 + 3.0 * hours / self.half_life_hours).sqrt()
+            }
+            Err(_) => 0.0,
+        }
+    }
+}
+
+/// The matching half of `calculate_score`: `base` plus the match bonuses,
+/// scaled by density and down for a long `len`; `0.0` when `query_chars`
+/// isn't a subsequence of `text_down`.
+fn fuzzy_match(text_down: &str, len: usize, query_chars: &[char], base: f64) -> f64 {
+    let mut score = base;
+    let query_len = query_chars.len();
+
+    let mut last_pos: isize = -1;
+    let mut query_idx = 0;
+    // Walked in place: collecting the name into a Vec<char> first cost an
+    // allocation per entry per keystroke.
+    let mut prev = None;
+
+    for (i, char) in text_down.chars().enumerate() {
+        if query_idx == query_len {
+            break;
+        }
+        if char == query_chars[query_idx] {
+            score += 1.0;
+
+            // Boundary bonus
+            let is_boundary = prev.is_none_or(|p: char| !p.is_alphanumeric());
+            if is_boundary {
+                score += 1.0;
+            }
+
+            // Proximity bonus
+            if last_pos >= 0 {
+                let gap = (i as isize) - last_pos - 1;
+                score += 2.0 / ((gap + 1) as f64).sqrt();
+            }
+
+            last_pos = i as isize;
+            query_idx += 1;
+        }
+        prev = Some(char);
+    }
+
+    if query_idx < query_len {
+        return 0.0;
+    }
+
+    // Density bonus
+    if last_pos >= 0 {
+        score *= query_len as f64 / (last_pos as f64 + 1.0);
+    }
+
+    // Length penalty
+    score * 10.0 / (len as f64 + 10.0)
+}
+
+// Scoring Algorithm Port
+fn calculate_score(
+    entry: &TryEntry,
+    query: &str,
+    query_chars: &[char],
+    now: SystemTime,
+    recency: &Recency,
+) -> f64 {
+    let mut score = date_suffix_bonus(entry);
+
+    if !query.is_empty() {
+        let base = score;
+        score = fuzzy_match(
+            &entry.basename_down,
+            entry.basename.len(),
+            query_chars,
+            base,
+        );
+        score = or_description(entry, base, score, |desc_down, len| {
+            fuzzy_match(desc_down, len, query_chars, 0.0)
+        });
+        if score == 0.0 {
+            return 0.0;
+        }
+    }
+
+    score + recency.bonus(entry, now)
+}
+
+#[derive(Debug)]
+enum ShellAction {
+    Cd(PathBuf),
+    MkdirCd(PathBuf),
+    /// `try set`: export the workspace as `TRY_PATH`, then `cd` into it.
+    Set(PathBuf),
+}
+
+/// The command that opens a path in the platform's file manager.
+fn file_opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Expand `$VAR`/`${VAR}` references, then a leading `~/` (or `~\`) and the
+/// Windows-style `%USERPROFILE%` and `%APPDATA%` variables, which users paste
+/// from Explorer and `cmd`.
+fn expand_path(path: &str) -> PathBuf {
+    let mut unset = Vec::new();
+    let expanded = expand_env_vars_with(path, &|k| env::var(k).ok(), &mut unset);
+    for name in unset {
+        eprintln!("Warning: ${} is not set; leaving it unexpanded", name);
+    }
+    let path = expanded.as_str();
+    let (base, rest) = if path == "~" {
+        (dirs::home_dir(), "")
+    } else if let Some(rest) = path.strip_prefix("~/").or(path.strip_prefix("~\\")) {
+        (dirs::home_dir(), rest)
+    } else if let Some(rest) = strip_prefix_ignore_case(path, "%USERPROFILE%") {
+        (dirs::home_dir(), rest)
+    } else if let Some(rest) = strip_prefix_ignore_case(path, "%APPDATA%") {
+        (dirs::config_dir(), rest)
+    } else {
+        return PathBuf::from(path);
+    };
+    let base = base.expect("Could not find home directory");
+    let rest = rest.trim_start_matches(['/', '\\']);
+    if rest.is_empty() {
+        base
+    } else {
+        base.join(rest)
+    }
+}
+
+/// Replace `$VAR`, `${VAR}`, `${VAR:-default}` (unset or empty) and
+/// `${VAR-default}` (unset) with values from `lookup`; defaults may contain
+/// references themselves. Undefined variables, and a `$` not followed by a
+/// variable name, are left as written; the undefined names go to `unset`.
+fn expand_env_vars_with(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    unset: &mut Vec<String>,
+) -> String {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let is_name = |name: &str| {
+        name.chars().next().is_some_and(|c| !c.is_ascii_digit()) && name.chars().all(is_name_char)
+    };
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let expansion = if let Some(braced) = after.strip_prefix('{') {
+            closing_brace(braced).and_then(|end| {
+                let inner = &braced[..end];
+                let name_end = inner.find(|c| !is_name_char(c)).unwrap_or(inner.len());
+                let (name, op) = inner.split_at(name_end);
+                if !is_name(name) {
+                    return None;
+                }
+                let value = lookup(name);
+                let value = if let Some(word) = op.strip_prefix(":-") {
+                    value
+                        .filter(|v| !v.is_empty())
+                        .or_else(|| Some(expand_env_vars_with(word, lookup, unset)))
+                } else if let Some(word) = op.strip_prefix('-') {
+                    value.or_else(|| Some(expand_env_vars_with(word, lookup, unset)))
+                } else if op.is_empty() {
+                    if value.is_none() {
+                        unset.push(name.to_string());
+                    }
+                    value
+                } else {
+                    None
+                };
+                value.map(|v| (v, end + 2))
+            })
+        } else {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            let name = &after[..end];
+            match is_name(name).then(|| lookup(name)) {
+                Some(Some(value)) => Some((value, end)),
+                Some(None) => {
+                    unset.push(name.to_string());
+                    None
+                }
+                None => None,
+            }
+        };
+        match expansion {
+            Some((value, consumed)) => {
+                out.push_str(&value);
+                rest = &after[consumed..];
+            }
+            None => {
+                out.push('$');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Index of the `}` closing a `${`, skipping nested `${...}` in defaults.
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `%VAR%` names are case-insensitive on Windows.
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &s[prefix.len()..])
+}
+
+/// Characters accepted into the search/path input buffer. Includes `:` and `\`
+/// so Windows absolute paths (e.g. `D:\tests`) can be typed in History mode.
+fn is_allowed_input_char(c: char) -> bool {
+    c.is_alphanumeric()
+        || matches!(
+            c,
+            '-' | '_' | '.' | ' ' | '/' | '~' | ':' | '\\' | '@' | '!'
+        )
+}
+
+/// Whether a search has a `!word` exclusion in it.
+fn has_negation(search: &str) -> bool {
+    search.split(' ').any(|t| t.starts_with('!'))
+}
+
+/// Split `!word` exclusions off a search: `rust web !2023` is the tokens
+/// `rust` and `web`, each matched on its own, over entries whose names don't
+/// contain `2023`. Without any, the search is one token, exactly as typed.
+fn split_negations(search: &str) -> (Vec<String>, Vec<String>) {
+    if !has_negation(search) {
+        let query = Some(search.to_string()).filter(|q| !q.is_empty());
+        return (query.into_iter().collect(), Vec::new());
+    }
+    let (negated, kept): (Vec<&str>, Vec<&str>) = search
+        .split(' ')
+        .filter(|t| !t.is_empty())
+        .partition(|t| t.starts_with('!'));
+    let negated = negated
+        .iter()
+        .map(|t| t[1..].to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    (kept.into_iter().map(String::from).collect(), negated)
+}
+
+/// Strip Windows extended-length (verbatim) path prefixes. `std::fs::canonicalize`
+/// returns paths like `\\?\D:\foo`, which PowerShell can't map back to a drive
+/// and which leak into prompts. Returns the path unchanged when no prefix is
+/// present (always the case on non-Windows).
+fn strip_verbatim_prefix(path: &Path) -> PathBuf {
+    let s = path.to_string_lossy();
+    if let Some(rest) = s.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", rest))
+    } else if let Some(rest) = s.strip_prefix(r"\\?\") {
+        PathBuf::from(rest)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Canonicalize a path for storage, without the verbatim prefix. Falls back to
+/// the input path if canonicalization fails (e.g. the path doesn't exist yet),
+/// with `\` separators on Windows so `C:/x` and `C:\x` are stored alike.
+fn canonicalize_clean(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path)
+        .map(|p| strip_verbatim_prefix(&p))
+        .unwrap_or_else(|_| native_separators(path, cfg!(windows)))
+}
+
+/// `path` with `/` turned into `\` when `windows`; untouched otherwise, as a
+/// backslash is an ordinary filename character on Unix.
+fn native_separators(path: &Path, windows: bool) -> PathBuf {
+    if windows {
+        PathBuf::from(path.to_string_lossy().replace('/', "\\"))
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Whether a typed path names its own location instead of one under the
+/// base path: absolute, `~`, `%USERPROFILE%` or `%APPDATA%`, and on Windows
+/// also rooted (`\x`) or drive (`D:x`) paths, which Rust doesn't count as
+/// absolute.
+fn is_explicit_location(s: &str, windows: bool) -> bool {
+    if s.starts_with('~')
+        || strip_prefix_ignore_case(s, "%USERPROFILE%").is_some()
+        || strip_prefix_ignore_case(s, "%APPDATA%").is_some()
+    {
+        return true;
+    }
+    if !windows {
+        return Path::new(s).is_absolute();
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some('/' | '\\'), _) => true,
+        (Some(drive), Some(':')) => drive.is_ascii_alphabetic(),
+        _ => false,
+    }
+}
+
+/// Parse a repository name out of a git URL (the last path segment, sans `.git`).
+fn parse_repo_name(url: &str) -> Option<String> {
+    git_url_regex()
+        .captures(url)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Whether a bare `try <query>` is a git URL to clone rather than a search:
+/// `http(s)://`, `ssh://` or `git://` with a host and a repository path, or
+/// scp-style `user@host:path`. A workspace merely named `http-notes` is not.
+fn looks_like_clone_url(query: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(
+            r"^(?:(?:https?|ssh|git)://(?:[^@/\s]+@)?[A-Za-z0-9.-]+(?::\d+)?/[^\s]*[^\s/]|[A-Za-z0-9._-]+@[A-Za-z0-9.-]+:[^\s]*[^\s/])$",
+        )
+        .unwrap()
+    });
+    re.is_match(query)
+}
+
+/// Host part of a git URL (`https://`, `ssh://user@host:port/`, or scp-style
+/// `git@host:`), lowercased. `None` for local paths and `file://` URLs.
+fn parse_repo_host(url: &str) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:[A-Za-z][A-Za-z0-9+.-]*://(?:[^@/]+@)?([^/:]+)|[^@/:]+@([^/:]+):)")
+            .unwrap()
+    });
+    let caps = re.captures(url)?;
+    let host = caps.get(1).or_else(|| caps.get(2))?.as_str();
+    Some(host.to_ascii_lowercase())
+}
+
+/// Built-in `{host}` short names; `host_aliases` in config.toml adds to or
+/// overrides these.
+const DEFAULT_HOST_ALIASES: &[(&str, &str)] = &[
+    ("github.com", "gh"),
+    ("gitlab.com", "gl"),
+    ("bitbucket.org", "bb"),
+];
+
+/// The clone directory name when neither a name nor a template is given.
+const DEFAULT_CLONE_NAME_TEMPLATE: &str = "{repo}-{date}";
+
+/// Short name for `host`: the configured alias, else the built-in one, else
+/// the host itself.
+fn host_alias(host: &str, aliases: Option<&HashMap<String, String>>) -> String {
+    if let Some(alias) = aliases.and_then(|m| m.get(host)) {
+        return alias.clone();
+    }
+    DEFAULT_HOST_ALIASES
+        .iter()
+        .find(|(h, _)| *h == host)
+        .map_or_else(|| host.to_string(), |(_, alias)| alias.to_string())
+}
+
+/// Fill in a clone name template's `{repo}`, `{host}` and `{date}`.
+fn expand_clone_name(template: &str, repo: &str, host: &str, date: &str) -> String {
+    template
+        .replace("{repo}", repo)
+        .replace("{host}", host)
+        .replace("{date}", date)
+}
+
+// ============================================================================
+// Shell integration layer
+//
+// The TUI (crossterm) is cross-platform, but the *scripts* `try` emits for the
+// shell wrapper to `eval` are shell-specific. `Shell` selects the right
+// `ScriptGenerator` so the exact same core logic drives Bash/Zsh and fish on
+// Unix and PowerShell on Windows.
+// ============================================================================
+
+// `PowerShell` is the product's name, not a `Shell` suffix.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    Bash,
+    Fish,
+    PowerShell,
+}
+
+impl Shell {
+    /// Parse a shell name (case-insensitive). Recognizes bash/zsh/sh, fish
+    /// and powershell/pwsh/ps.
+    fn parse(name: &str) -> Option<Shell> {
+        match name.trim().to_lowercase().as_str() {
+            "bash" | "zsh" | "sh" => Some(Shell::Bash),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" | "ps" | "ps1" => Some(Shell::PowerShell),
+            _ => None,
+        }
+    }
+
+    /// Detect the active shell from the environment.
+    ///
+    /// Priority: explicit `TRY_SHELL` (set by our own init wrapper) > presence
+    /// of POSIX `SHELL` (fish if it names fish, else bash/zsh) > PowerShell
+    /// markers > compile-time OS.
+    fn detect() -> Shell {
+        if let Ok(s) = env::var("TRY_SHELL") {
+            if let Some(shell) = Shell::parse(&s) {
+                return shell;
+            }
+        }
+        Self::detect_from(|k| env::var(k).ok())
+    }
+
+    /// Pure detection helper, parameterized over an env lookup for testability.
+    fn detect_from(get: impl Fn(&str) -> Option<String>) -> Shell {
+        // A POSIX-style $SHELL strongly implies bash/zsh, even on Windows
+        // (e.g. Git Bash / WSL).
+        if let Some(shell) = get("SHELL") {
+            let name = shell.rsplit(['/', '\\']).next().unwrap_or(&shell);
+            if name == "fish" {
+                return Shell::Fish;
+            }
+            return Shell::Bash;
+        }
+        // PowerShell sets PSModulePath; cmd does not export $SHELL either.
+        if get("PSModulePath").is_some() {
+            return Shell::PowerShell;
+        }
+        if cfg!(windows) {
+            Shell::PowerShell
+        } else {
+            Shell::Bash
+        }
+    }
+
+    /// The program name in a process name or path: `/bin/zsh`, `-bash`
+    /// (a login shell) and `pwsh.exe` give `zsh`, `bash` and `pwsh`.
+    fn program(s: &str) -> String {
+        let name = s.rsplit(['/', '\\']).next().unwrap_or(s);
+        let name = name.trim_start_matches('-').to_lowercase();
+        name.strip_suffix(".exe")
+            .map(str::to_string)
+            .unwrap_or(name)
+    }
+
+    /// Which program `shell` is for `init --install`: the parent process or
+    /// `$SHELL`, whichever is that kind of shell (zsh and bash share one
+    /// `Shell` but not an rc file).
+    fn program_for_init(
+        self,
+        parent: Option<&str>,
+        get: impl Fn(&str) -> Option<String>,
+    ) -> Option<String> {
+        [
+            parent.map(Self::program),
+            get("SHELL").map(|s| Self::program(&s)),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|name| Shell::parse(name) == Some(self))
+    }
+
+    /// `try init` without `--shell`. `eval "$(try init)"` runs try as a
+    /// child of the shell being set up, so that process's name (the shell's
+    /// `$0`) says the most; `$SHELL` is only the login shell. Returns the
+    /// shell, what gave it away, and a warning when it's a guess.
+    fn detect_for_init(
+        parent: Option<&str>,
+        get: impl Fn(&str) -> Option<String>,
+    ) -> (Shell, String, Option<String>) {
+        if let Some(shell) = get("TRY_SHELL").as_deref().and_then(Shell::parse) {
+            return (shell, "TRY_SHELL".to_string(), None);
+        }
+        let hints = [
+            parent.map(|p| ("the parent process", Self::program(p))),
+            get("SHELL").map(|s| ("$SHELL", Self::program(&s))),
+        ];
+        // Anything else (sudo, tmux, an IDE) says nothing; look further.
+        for (source, name) in hints.into_iter().flatten() {
+            if let Some(shell) = Shell::parse(&name) {
+                return (shell, format!("{} is {}", source, name), None);
+            }
+            if name == "nu" {
+                let warning = "nushell isn't supported yet; emitting the POSIX script. \
+                               Pass --shell to choose another.";
+                return (
+                    Shell::Bash,
+                    format!("{} is nu", source),
+                    Some(warning.to_string()),
+                );
+            }
+        }
+        if get("PSModulePath").is_some() {
+            return (Shell::PowerShell, "PSModulePath is set".to_string(), None);
+        }
+        let shell = if cfg!(windows) {
+            Shell::PowerShell
+        } else {
+            Shell::Bash
+        };
+        let warning = format!(
+            "couldn't tell which shell this is; emitting the {} script. \
+             Pass --shell bash|fish|powershell to choose.",
+            shell.label()
+        );
+        (shell, "no shell detected".to_string(), Some(warning))
+    }
+
+    /// The script family, for the comment atop `try init`'s output.
+    fn label(self) -> &'static str {
+        match self {
+            Shell::Bash => "POSIX (bash/zsh)",
+            Shell::Fish => "fish",
+            Shell::PowerShell => "PowerShell",
+        }
+    }
+
+    fn generator(self) -> Box<dyn ScriptGenerator> {
+        match self {
+            Shell::Bash => Box::new(BashGenerator),
+            Shell::Fish => Box::new(FishGenerator),
+            Shell::PowerShell => Box::new(PowerShellGenerator),
+        }
+    }
+}
+
+/// Default wrapper command name per shell. `try` is a reserved keyword in
+/// PowerShell, so PowerShell uses `tr` instead.
+fn default_fn_name(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash | Shell::Fish => "try",
+        Shell::PowerShell => "tr",
+    }
+}
+
+/// Abstraction over emitting shell commands. Each method returns a single
+/// command string; `join` combines a sequence into one script.
+trait ScriptGenerator {
+    /// Quote/escape a path for safe interpolation into a command.
+    fn escape(&self, path: &Path) -> String;
+    fn cd(&self, path: &Path) -> String;
+    fn mkdir(&self, path: &Path) -> String;
+    /// Update a directory's mtime so recency scoring bumps it to the top.
+    fn touch(&self, path: &Path) -> String;
+    fn set_env(&self, key: &str, value: &str) -> String;
+    /// Combine commands into a single line the shell can `eval`.
+    fn join(&self, cmds: &[String]) -> String;
+    /// Wrap `cmd` so its failure doesn't stop the rest of a `join`ed chain.
+    fn best_effort(&self, cmd: &str) -> String;
+    /// The shell function + env setup printed by `try init`.
+    /// `fn_name` is the wrapper command the user will type.
+    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String;
+    /// `init --eval`: one line that runs `exe args...` and evaluates its
+    /// output, quoted so it can be pasted as is.
+    fn eval_line(&self, exe: &str, args: &[String]) -> String;
+    /// `init --watch`: a prompt hook that runs `try set --silent` each time
+    /// the prompt shows up in a new directory.
+    fn watch_hook(&self, exe: &str) -> String;
+
+    /// Open `path` with an editor command (which may carry its own flags,
+    /// e.g. `code -n`).
+    fn open_editor(&self, editor: &str, path: &Path) -> String {
+        format!("{} '{}'", editor, self.escape(path))
+    }
+}
+
+/// Escape `s` for use inside POSIX single quotes: each `'` becomes `'\''`.
+/// NUL bytes can't appear in paths or argv, so they are dropped.
+fn shell_escape(s: &str) -> String {
+    s.replace('\0', "").replace('\'', "'\\''")
+}
+
+/// POSIX single-quote `s` as one shell word. Everything inside single quotes
+/// (newlines, `$`, backticks, backslashes) is literal, so the result is safe
+/// to `eval`.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", shell_escape(s))
+}
+
+struct BashGenerator;
+
+impl ScriptGenerator for BashGenerator {
+    fn escape(&self, path: &Path) -> String {
+        let s = path.to_string_lossy();
+        // On Windows (Git Bash), normalize mixed separators to '/' so paths
+        // joined by PathBuf don't break `cd`. On real Unix, a backslash is a
+        // legal filename character and must be left untouched.
+        let s = if cfg!(windows) {
+            s.replace('\\', "/")
+        } else {
+            s.into_owned()
+        };
+        shell_escape(&s)
+    }
+
+    fn cd(&self, path: &Path) -> String {
+        format!("cd '{}'", self.escape(path))
+    }
+
+    fn mkdir(&self, path: &Path) -> String {
+        format!("mkdir -p '{}'", self.escape(path))
+    }
+
+    fn touch(&self, path: &Path) -> String {
+        format!("touch '{}'", self.escape(path))
+    }
+
+    fn set_env(&self, key: &str, value: &str) -> String {
+        format!("export {}={}", key, shell_quote(value))
+    }
+
+    fn join(&self, cmds: &[String]) -> String {
+        cmds.join(" && \\\n  ")
+    }
+
+    fn best_effort(&self, cmd: &str) -> String {
+        format!("{{ {} 2>/dev/null || true; }}", cmd)
+    }
+
+    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String {
+        format!(
+            r#"
+{name}() {{
+    local out
+    # Use absolute path to the binary to ensure consistency; `command` never
+    # resolves to this function, even if the path degrades to a bare `try`.
+    out=$(command '{exe}' "$@" 2>/dev/tty)
+    if [ $? -eq 0 ]; then
+        eval "$out"
+    else
+        # Echo error to stderr if needed, or do nothing on cancel
+        :
+    fi
+}}
+export TRY_PATH="{path}"
+export TRY_SHELL="bash"
+"#,
+            name = fn_name,
+            exe = shell_escape(exe),
+            path = default_path
+        )
+    }
+
+    fn eval_line(&self, exe: &str, args: &[String]) -> String {
+        let words: Vec<String> = std::iter::once(exe)
+            .chain(args.iter().map(String::as_str))
+            .map(shell_quote)
+            .collect();
+        format!("eval \"$(command {})\"", words.join(" "))
+    }
+
+    fn watch_hook(&self, exe: &str) -> String {
+        format!(
+            r#"
+__try_watch() {{
+    [ "$PWD" = "${{__try_watched-}}" ] && return
+    __try_watched=$PWD
+    command '{exe}' set --silent "$PWD" >/dev/null 2>&1
+}}
+if [ -n "${{ZSH_VERSION-}}" ]; then
+    autoload -Uz add-zsh-hook && add-zsh-hook precmd __try_watch
+else
+    case ";${{PROMPT_COMMAND-}};" in
+        *";__try_watch;"*) ;;
+        *) PROMPT_COMMAND="__try_watch${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}" ;;
+    esac
+fi
+"#,
+            exe = shell_escape(exe)
+        )
+    }
+}
+
+struct FishGenerator;
+
+impl FishGenerator {
+    /// fish single-quote escaping: `\` and `'` take a backslash. NUL bytes
+    /// are dropped as in `shell_escape`.
+    fn fish_escape(s: &str) -> String {
+        s.replace('\0', "")
+            .replace('\\', "\\\\")
+            .replace('\'', "\\'")
+    }
+}
+
+impl ScriptGenerator for FishGenerator {
+    fn escape(&self, path: &Path) -> String {
+        Self::fish_escape(&path.to_string_lossy())
+    }
+
+    fn cd(&self, path: &Path) -> String {
+        format!("cd '{}'", self.escape(path))
+    }
+
+    fn mkdir(&self, path: &Path) -> String {
+        format!("mkdir -p '{}'", self.escape(path))
+    }
+
+    fn touch(&self, path: &Path) -> String {
+        format!("touch '{}'", self.escape(path))
+    }
+
+    fn set_env(&self, key: &str, value: &str) -> String {
+        format!("set -gx {} '{}'", key, Self::fish_escape(value))
+    }
+
+    // fish has `&&` only since 3.0; `; and` works everywhere.
+    fn join(&self, cmds: &[String]) -> String {
+        cmds.join("; and ")
+    }
+
+    fn best_effort(&self, cmd: &str) -> String {
+        format!("begin; {} 2>/dev/null; or true; end", cmd)
+    }
+
+    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String {
+        format!(
+            r#"
+function {name}
+    # `command` never resolves to this function.
+    set -l out (command '{exe}' $argv 2>/dev/tty)
+    if test $status -eq 0
+        string join \n -- $out | source
+    end
+end
+set -gx TRY_PATH '{path}'
+set -gx TRY_SHELL fish
+"#,
+            name = fn_name,
+            exe = Self::fish_escape(exe),
+            path = Self::fish_escape(default_path)
+        )
+    }
+
+    fn eval_line(&self, exe: &str, args: &[String]) -> String {
+        let words: Vec<String> = std::iter::once(exe)
+            .chain(args.iter().map(String::as_str))
+            .map(|w| format!("'{}'", Self::fish_escape(w)))
+            .collect();
+        format!("command {} | source", words.join(" "))
+    }
+
+    fn watch_hook(&self, exe: &str) -> String {
+        format!(
+            r#"
+function __try_watch --on-variable PWD
+    command '{exe}' set --silent "$PWD" >/dev/null 2>&1
+end
+"#,
+            exe = Self::fish_escape(exe)
+        )
+    }
+}
+
+struct PowerShellGenerator;
+
+impl PowerShellGenerator {
+    /// PowerShell single-quote escaping: ' -> ''
+    fn ps_quote(s: &str) -> String {
+        s.replace('\'', "''")
+    }
+}
+
+impl ScriptGenerator for PowerShellGenerator {
+    fn escape(&self, path: &Path) -> String {
+        // Normalize separators to '/' (PowerShell accepts them) then
+        // PowerShell single-quote escape: ' -> ''
+        Self::ps_quote(&path.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn cd(&self, path: &Path) -> String {
+        format!("Set-Location -LiteralPath '{}'", self.escape(path))
+    }
+
+    fn mkdir(&self, path: &Path) -> String {
+        format!(
+            "New-Item -ItemType Directory -Force -Path '{}' | Out-Null",
+            self.escape(path)
+        )
+    }
+
+    fn touch(&self, path: &Path) -> String {
+        // Bump LastWriteTime so recency scoring promotes the directory.
+        format!(
+            "(Get-Item -LiteralPath '{}').LastWriteTime = Get-Date",
+            self.escape(path)
+        )
+    }
+
+    fn set_env(&self, key: &str, value: &str) -> String {
+        format!("$env:{} = '{}'", key, Self::ps_quote(value))
+    }
+
+    fn join(&self, cmds: &[String]) -> String {
+        cmds.join("; ")
+    }
+
+    fn best_effort(&self, cmd: &str) -> String {
+        format!("try {{ {} }} catch {{ }}", cmd)
+    }
+
+    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String {
+        // NOTE: `try` is a reserved keyword in PowerShell, so the wrapper must
+        // use a different name (default `tr`).
+        format!(
+            r#"
+function {name} {{
+    # Only ever run an executable, never this (or another) function.
+    $out = & (Get-Command -CommandType Application -Name '{exe}' | Select-Object -First 1) @args
+    if ($LASTEXITCODE -eq 0 -and $out) {{
+        Invoke-Expression ($out -join "`n")
+    }}
+}}
+$env:TRY_PATH = '{path}'
+$env:TRY_SHELL = 'powershell'
+"#,
+            name = fn_name,
+            exe = PowerShellGenerator::ps_quote(exe),
+            path = PowerShellGenerator::ps_quote(default_path)
+        )
+    }
+
+    fn eval_line(&self, exe: &str, args: &[String]) -> String {
+        let words: Vec<String> = std::iter::once(exe)
+            .chain(args.iter().map(String::as_str))
+            .map(|w| format!("'{}'", Self::ps_quote(w)))
+            .collect();
+        format!("Invoke-Expression ((& {}) -join \"`n\")", words.join(" "))
+    }
+
+    fn watch_hook(&self, exe: &str) -> String {
+        format!(
+            r#"
+if (-not $global:__tryPrompt) {{
+    $global:__tryPrompt = $function:prompt
+    function global:prompt {{
+        if ($PWD.Path -ne $global:__tryWatched) {{
+            $global:__tryWatched = $PWD.Path
+            & (Get-Command -CommandType Application -Name '{exe}' | Select-Object -First 1) set --silent $PWD.Path *> $null
+        }}
+        & $global:__tryPrompt
+    }}
+}}
+"#,
+            exe = PowerShellGenerator::ps_quote(exe)
+        )
+    }
+}
+
+/// Build the command sequence for a resolved user action. `touch` is
+/// `touch_on_cd` from the config.
+fn build_action_script(gen: &dyn ScriptGenerator, action: &ShellAction, touch: bool) -> String {
+    // The `touch` only feeds recency scoring (and tools that sort by mtime);
+    // it runs after the `cd` and may fail (e.g. read-only filesystem)
+    // without blocking navigation.
+    let touch = |path: &Path| touch.then(|| gen.best_effort(&gen.touch(path)));
+    match action {
+        ShellAction::Cd(path) => {
+            let cmds: Vec<String> = [Some(gen.cd(path)), touch(path)]
+                .into_iter()
+                .flatten()
+                .collect();
+            gen.join(&cmds)
+        }
+        ShellAction::MkdirCd(path) => {
+            let cmds: Vec<String> = [Some(gen.mkdir(path)), Some(gen.cd(path)), touch(path)]
+                .into_iter()
+                .flatten()
+                .collect();
+            gen.join(&cmds)
+        }
+        ShellAction::Set(path) => {
+            // Update the live shell's TRY_PATH to the chosen workspace, then cd.
+            let env_cmd = gen.set_env("TRY_PATH", &path.to_string_lossy());
+            gen.join(&[env_cmd, gen.cd(path)])
+        }
+    }
+}
+
+/// `try open`: like `build_action_script`, but the editor takes the place of
+/// the `cd`.
+fn build_open_script(gen: &dyn ScriptGenerator, action: &ShellAction, editor: &str) -> String {
+    match action {
+        ShellAction::MkdirCd(path) => gen.join(&[gen.mkdir(path), gen.open_editor(editor, path)]),
+        ShellAction::Cd(path) | ShellAction::Set(path) => gen.open_editor(editor, path),
+    }
+}
+
+/// Editor for `try open`: `--editor`, then `editor` in config.toml, then
+/// `$EDITOR`.
+fn resolve_editor(
+    flag: Option<String>,
+    config: Option<&str>,
+    env_editor: Option<String>,
+) -> Option<String> {
+    flag.or_else(|| config.map(String::from))
+        .or(env_editor)
+        .filter(|e| !e.trim().is_empty())
+}
+
+/// The entry `name` refers to in the Scan or Archive list of `mode`, ranked
+/// exactly as the selector would rank it for `name`: an exact basename
+/// wins, otherwise the unique best match. Everything that takes a name
+/// (`open`, `info`, `pull`, `unarchive`) resolves it here.
+fn resolve_path(
+    mode: SelectorMode,
+    name: &str,
+    date_range: DateRange,
+    recency: Recency,
+) -> Result<PathBuf> {
+    let base_path = match &mode {
+        SelectorMode::Scan(dir) | SelectorMode::Archive(dir) => dir.clone(),
+        SelectorMode::History(_) => PathBuf::new(),
+    };
+    let mut selector = TrySelector::new(mode, name.to_string(), base_path.clone());
+    selector.scorer = ScorerKind::detect().scorer(recency);
+    selector.date_range = date_range;
+    selector.load_entries()?;
+    selector.refresh_scores();
+    let path = match selector.unique_match() {
+        Ok(entry) => entry.path.clone(),
+        Err(contenders) if contenders.is_empty() => {
+            return Err(TryError::NoMatchingEntry {
+                base: base_path.to_path_buf(),
+                name: name.to_string(),
+            }
+            .into())
+        }
+        Err(contenders) => {
+            return Err(TryError::AmbiguousEntry {
+                name: name.to_string(),
+                candidates: contenders.iter().map(|e| e.basename.clone()).collect(),
+            }
+            .into())
+        }
+    };
+    Ok(path)
+}
+
+/// Expand a post-create hook template. Runs after the `cd`, so the command
+/// executes inside the new directory; `{path}` becomes the quoted path.
+fn after_create_command(gen: &dyn ScriptGenerator, template: &str, path: &Path) -> String {
+    template.replace("{path}", &format!("'{}'", gen.escape(path)))
+}
+
+fn main() -> Result<()> {
+    // Manually check for subcommands to redirect execution flow similar to Ruby script
+    // Or use Clap properly.
+    // The Ruby script uses a clever `try exec` pattern. We will emulate that.
+
+    let cli = Cli::parse();
+    if cli.out_file.is_some() && cli.command.as_ref().is_some_and(|c| !c.emits_script()) {
+        anyhow::bail!("--out-file only applies to commands that print a script to eval");
+    }
+
+    // Resolve base path: workspaces config takes priority over TRY_PATH env var
+    let base_path = {
+        let workspaces = WorkspaceManager::get_workspaces()?;
+        if let Some(first) = workspaces.first() {
+            // Use the first workspace from config (set by `try set`).
+            // Strip any stale verbatim prefix from older configs.
+            strip_verbatim_prefix(first)
+        } else if let Ok(p) = env::var("TRY_PATH") {
+            // Fall back to TRY_PATH env var if no workspaces configured
+            expand_path(&p)
+        } else {
+            // Ultimate fallback
+            expand_path("~/project/test")
+        }
+    };
+
+    let config = TryConfig::load().unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring config: {}", e);
+        TryConfig::default()
+    });
+    if cli.fix_permissions {
+        match WorkspaceManager::fix_config_permissions() {
+            Ok(path) => eprintln!("{} is now readable by you only.", path.display()),
+            Err(e) => eprintln!("Warning: cannot fix permissions: {}", e),
+        }
+    } else if let Some(warning) = WorkspaceManager::check_config_permissions() {
+        WorkspaceManager::warn_once(&WorkspaceManager::get_config_path(), &warning);
+    }
+    let out_file = cli.out_file.as_deref();
+    let date_range = DateRange {
+        since: cli.since,
+        until: cli.until,
+    };
+    let base_path = if config.follow_symlinks {
+        canonicalize_clean(&base_path)
+    } else {
+        base_path
+    };
+    // Selector settings every interactive command shares; a branch
+    // overrides only what differs for it.
+    let interactive = InteractiveOptions {
+        out_file,
+        fifo: cli.fifo.as_deref(),
+        emit_events: cli.emit_events.as_deref(),
+        icons: Some(&config.icons),
+        delete_confirmation: config.delete_confirmation,
+        recency: config.recency(),
+        show_counts: config.show_counts,
+        copy_method: config.copy_method,
+        sort: config.sort,
+        auto_accept_single: config.auto_accept_single,
+        delete_double_tap: config.delete_double_tap(),
+        height: cli.height,
+        no_touch: !config.touch_on_cd(),
+        no_create: cli.no_create,
+        no_delete: cli.no_delete,
+        read_only: cli.read_only,
+        recency_from_files: config.recency_from_files,
+        archive_dir: Some(config.archive_dir()),
+        date_range,
+        no_restore_query: cli.no_restore_query,
+        ..Default::default()
+    };
+
+    // If command is None, it defaults to interactive (or query)
+    match cli.command {
+        Some(Commands::Init {
+            path,
+            shell: shell_arg,
+            name,
+            print_only,
+            group,
+            watch,
+            eval,
+            install,
+        }) => {
+            let parent = parent_process_name();
+            let (shell, origin) = match shell_arg.as_deref().and_then(Shell::parse) {
+                Some(shell) => (shell, "--shell".to_string()),
+                None => {
+                    let (shell, origin, warning) =
+                        Shell::detect_for_init(parent.as_deref(), |k| env::var(k).ok());
+                    if let Some(warning) = warning {
+                        eprintln!("Warning: {}", warning);
+                    }
+                    (shell, origin)
+                }
+            };
+            if eval || install {
+                let mut args = vec!["init".to_string(), path];
+                for (flag, value) in [("--name", name), ("--group", group)] {
+                    if let Some(value) = value {
+                        args.extend([flag.to_string(), value]);
+                    }
+                }
+                args.extend(
+                    [("--print-only", print_only), ("--watch", watch)]
+                        .iter()
+                        .filter(|(_, on)| *on)
+                        .map(|(flag, _)| flag.to_string()),
+                );
+                let exe = wrapper_exe(env::current_exe());
+                let line = shell.generator().eval_line(&exe, &args);
+                if eval {
+                    println!("{}", line);
+                } else {
+                    let program = shell_arg
+                        .as_deref()
+                        .map(Shell::program)
+                        .filter(|name| Shell::parse(name) == Some(shell))
+                        .or_else(|| {
+                            shell.program_for_init(parent.as_deref(), |k| env::var(k).ok())
+                        });
+                    install_init_line(shell, program.as_deref(), &line)?;
+                }
+                return Ok(());
+            }
+            let path_buf = expand_path(&path);
+            // Only add workspace if the list is empty (first time init)
+            let workspaces = WorkspaceManager::get_workspaces()?;
+            if !print_only && workspaces.is_empty() {
+                if let Err(e) = WorkspaceManager::add_workspace(&path_buf, group.as_deref()) {
+                    eprintln!("Warning: Failed to save workspace: {}", e);
+                }
+            }
+            let fn_name = name.unwrap_or_else(|| default_fn_name(shell).to_string());
+            println!("# try init: {} script ({})", shell.label(), origin);
+            print_init_script(shell, &fn_name, &path, group.as_deref(), watch);
+        }
+        Some(Commands::Clone {
+            url,
+            name,
+            proxy,
+            sparse,
+            nest,
+            into,
+            base,
+            name_template,
+        }) => {
+            let base_path = clone_base(base.as_deref(), base_path)?;
+            let opts = CloneOptions {
+                name,
+                proxy,
+                sparse: &sparse,
+                nest,
+                into: into.as_deref(),
+                name_template: name_template
+                    .as_deref()
+                    .or(config.clone_name_template.as_deref()),
+                host_aliases: Some(&config.host_aliases),
+                post_clone: config.post_clone_cmd.as_deref(),
+                unsafe_post_clone: config.unsafe_post_clone,
+            };
+            clone_and_cd(&base_path, &url, opts, out_file)?;
+        }
+        Some(Commands::Set { silent: true, path }) => {
+            let dir = match path {
+                Some(p) => expand_path(&p),
+                None => env::current_dir()?,
+            };
+            // Runs from a prompt hook: failures must not get in the way.
+            let _ = WorkspaceManager::touch_workspace(&dir);
+        }
+        Some(Commands::Set { .. }) => {
+            let workspaces = WorkspaceManager::get_records()?;
+
+            run_interactive(
+                SelectorMode::History(workspaces),
+                String::new(),
+                base_path,
+                &interactive,
+            )?;
+        }
+        Some(Commands::Open {
+            name: Some(name),
+            print: true,
+            ..
+        }) => {
+            check_base_available(&base_path)?;
+            let path = resolve_path(
+                SelectorMode::Scan(base_path.clone()),
+                &name,
+                date_range,
+                config.recency(),
+            )?;
+            println!("{}", path.display());
+        }
+        Some(Commands::Open { name, editor, .. }) => {
+            let editor = resolve_editor(editor, config.editor.as_deref(), env::var("EDITOR").ok())
+                .ok_or(TryError::EditorNotSet)?;
+            match name {
+                Some(name) => {
+                    check_base_available(&base_path)?;
+                    let path = resolve_path(
+                        SelectorMode::Scan(base_path.clone()),
+                        &name,
+                        date_range,
+                        config.recency(),
+                    )?;
+                    let gen = Shell::detect().generator();
+                    let script = gen.open_editor(&editor, &path);
+                    emit_script(&script, out_file)?;
+                }
+                None => {
+                    // The editor replaces the `cd`, which a pipe reader
+                    // or embedder would never see.
+                    let opts = InteractiveOptions {
+                        editor: Some(&editor),
+                        fifo: None,
+                        emit_events: None,
+                        ..interactive
+                    };
+                    run_interactive(
+                        SelectorMode::Scan(base_path.clone()),
+                        String::new(),
+                        base_path,
+                        &opts,
+                    )?;
+                }
+            }
+        }
+        Some(Commands::Gc { aggressive }) => {
+            let files = [
+                WorkspaceManager::get_config_path(),
+                TryState::get_state_path(),
+            ];
+            let size = || -> u64 {
+                files
+                    .iter()
+                    .filter_map(|f| fs::metadata(f).ok())
+                    .map(|m| m.len())
+                    .sum()
+            };
+            let before = size();
+            let workspaces = WorkspaceManager::gc(aggressive)?;
+            let state = TryState::gc_at(&files[1])?;
+            let after = size();
+            eprintln!(
+                "Removed {} missing workspaces and {} stale state entries: {} -> {} bytes ({} freed)",
+                workspaces,
+                state,
+                before,
+                after,
+                before.saturating_sub(after)
+            );
+        }
+        Some(Commands::Info { name }) => {
+            check_base_available(&base_path)?;
+            let path = resolve_path(
+                SelectorMode::Scan(base_path.clone()),
+                &name,
+                date_range,
+                config.recency(),
+            )?;
+            let exclude = config.size_exclude();
+            let size = compute_workspace_size(&path, &exclude)?;
+            let modified: DateTime<Local> = fs::metadata(&path)?.modified()?.into();
+            println!("Path:     {}", path.display());
+            println!("Modified: {}", modified.format("%Y-%m-%d %H:%M"));
+            let kind = WorkspaceKind::detect(&path);
+            println!("Kind:     {}", kind.describe());
+            println!("Size:     {}", format_size(size.included));
+            if !exclude.is_empty() {
+                println!(
+                    "Excluded: {} ({})",
+                    format_size(size.excluded),
+                    exclude.join(", ")
+                );
+            }
+            if kind != WorkspaceKind::Manual {
+                let since = workspace_created(&path)?;
+                println!("Commits since {}:", since);
+                match git_log_since(&path, since, GIT_LOG_TIMEOUT) {
+                    Some(lines) if lines.is_empty() => println!("  (none)"),
+                    Some(lines) => lines.iter().for_each(|l| println!("  {}", l)),
+                    None => println!("  (git log unavailable)"),
+                }
+            }
+        }
+        Some(Commands::Unarchive { name }) => {
+            if cli.read_only {
+                anyhow::bail!("--read-only won't restore archives");
+            }
+            check_base_available(&base_path)?;
+            let archive_dir = config.archive_dir();
+            match name {
+                Some(name) => {
+                    let archive = resolve_path(
+                        SelectorMode::Archive(archive_dir.clone()),
+                        &name,
+                        date_range,
+                        config.recency(),
+                    )?;
+                    let restored = restore_archive(&archive, &base_path)?;
+                    let gen = Shell::detect().generator();
+                    let script = build_action_script(
+                        gen.as_ref(),
+                        &ShellAction::Cd(restored),
+                        config.touch_on_cd(),
+                    );
+                    emit_script(&script, out_file)?;
+                }
+                None => {
+                    run_interactive(
+                        SelectorMode::Archive(archive_dir),
+                        String::new(),
+                        base_path,
+                        &interactive,
+                    )?;
+                }
+            }
+        }
+        Some(Commands::Pull {
+            name,
+            all,
+            parallel,
+        }) => {
+            let is_clone =
+                |d: &Path| matches!(WorkspaceKind::detect(d), WorkspaceKind::Clone { .. });
+            let dirs = if all {
+                let mut dirs: Vec<PathBuf> = scan_entries(&base_path)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|e| e.path)
+                    .chain(WorkspaceManager::get_workspaces()?)
+                    .map(|p| canonicalize_clean(&p))
+                    .filter(|p| is_clone(p))
+                    .collect();
+                dirs.sort();
+                dirs.dedup();
+                dirs
+            } else {
+                let dir = match name {
+                    Some(name) => {
+                        check_base_available(&base_path)?;
+                        resolve_path(
+                            SelectorMode::Scan(base_path.clone()),
+                            &name,
+                            date_range,
+                            config.recency(),
+                        )?
+                    }
+                    None => env::current_dir()?,
+                };
+                let clone = dir.ancestors().find(|d| is_clone(d));
+                vec![clone.ok_or(TryError::NotAClone(dir.clone()))?.to_path_buf()]
+            };
+            let failed = pull_workspaces(&dirs, parallel);
+            if !failed.is_empty() {
+                return Err(TryError::PullsFailed {
+                    failed,
+                    total: dirs.len(),
+                }
+                .into());
+            }
+            eprintln!(
+                "Pulled {} workspace{}",
+                dirs.len(),
+                if dirs.len() == 1 { "" } else { "s" }
+            );
+        }
+        Some(Commands::Weight { name, weight }) => {
+            let path = WorkspaceManager::set_weight(&name, weight)?;
+            eprintln!("Weight of '{}' set to {}", path.display(), weight);
+        }
+        Some(Commands::Recent {
+            limit,
+            format,
+            sort,
+        }) => {
+            check_base_available(&base_path)?;
+            let mut entries = scan_entries(&base_path)?;
+            entries.retain(|e| date_range.contains(e));
+            let entries = recent_entries(entries, limit, sort, &config.size_exclude());
+            print!("{}", format_recent(&entries, format));
+        }
+        Some(Commands::List { sort }) => {
+            for record in WorkspaceManager::list_sorted(sort, &config.size_exclude())? {
+                println!("{}", record.path.display());
+            }
+        }
+        None => {
+            // Default: try [query] -> mapped to try exec cd [query] by the shell wrapper
+            // But if called directly without wrapper:
+            let query_str = cli.query.unwrap_or_default();
+
+            // A full git URL clones unless `clone_on_url = false`;
+            // anything else (and `--initial-query`) opens the selector.
+            if config.clone_on_url() && looks_like_clone_url(&query_str) {
+                let opts = CloneOptions {
+                    name_template: config.clone_name_template.as_deref(),
+                    host_aliases: Some(&config.host_aliases),
+                    post_clone: config.post_clone_cmd.as_deref(),
+                    unsafe_post_clone: config.unsafe_post_clone,
+                    ..Default::default()
+                };
+                clone_and_cd(&base_path, &query_str, opts, out_file)?;
+            } else {
+                // The wrapper usually calls `try exec ...`.
+                // If we are here, we should output the script for the wrapper to eval.
+                let after_create = cli
+                    .after
+                    .or_else(|| config.after_create_for(&base_path).map(String::from));
+                let opts = InteractiveOptions {
+                    after_create: after_create.as_deref(),
+                    ..interactive
+                };
+                run_interactive(
+                    SelectorMode::Scan(base_path.clone()),
+                    cli.initial_query.unwrap_or(query_str),
+                    base_path,
+                    &opts,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Output and behavior knobs for one interactive selector run.
+#[derive(Default)]
+struct InteractiveOptions<'a> {
+    /// Write the script here instead of stdout.
+    out_file: Option<&'a Path>,
+    /// Write an action line (not a script) to this named pipe.
+    fifo: Option<&'a Path>,
+    /// Headless: JSON key events on stdin, JSON states written here.
+    emit_events: Option<&'a Path>,
+    /// Post-create hook template appended to `MkdirCd` scripts.
+    after_create: Option<&'a str>,
+    /// `[icons]` overrides from the config.
+    icons: Option<&'a HashMap<String, String>>,
+    delete_confirmation: DeleteConfirmation,
+    /// `--no-create`
+    no_create: bool,
+    /// `--no-delete`
+    no_delete: bool,
+    /// `--read-only`
+    read_only: bool,
+    /// `try open`: hand the chosen directory to this editor instead of `cd`.
+    editor: Option<&'a str>,
+    /// `--since` / `--until`
+    date_range: DateRange,
+    /// `--no-restore-query`
+    no_restore_query: bool,
+    /// Recency scoring from the config / environment.
+    recency: Recency,
+    /// `show_counts` from the config.
+    show_counts: bool,
+    /// `copy_method` from the config.
+    copy_method: CopyMethod,
+    /// `sort` from the config.
+    sort: SortMode,
+    /// `auto_accept_single` from the config.
+    auto_accept_single: bool,
+    /// `delete_confirm_required` from the config.
+    delete_double_tap: bool,
+    /// `touch_on_cd = false` in the config.
+    no_touch: bool,
+    /// `--height`
+    height: Option<u16>,
+    /// `archive_dir` from the config, for Ctrl-A.
+    archive_dir: Option<PathBuf>,
+    /// `recency_from_files` from the config.
+    recency_from_files: bool,
+}
+
+fn run_interactive(
+    mode: SelectorMode,
+    query: String,
+    workspace_path: PathBuf,
+    opts: &InteractiveOptions,
+) -> Result<()> {
+    // Validate the pipe before taking over the terminal.
+    if let Some(fifo) = opts.fifo {
+        check_fifo(fifo)?;
+    }
+
+    let mut selector = TrySelector::new(mode, query, workspace_path);
+    selector.icons = ProjectIcons::new(opts.icons);
+    if opts.recency_from_files {
+        selector.file_activity = Some(FileActivity::default());
+    }
+    selector.delete_confirmation = opts.delete_confirmation;
+    selector.scorer =
+        LeafScorer::for_mode(ScorerKind::detect().scorer(opts.recency), &selector.mode);
+    selector.show_counts = opts.show_counts;
+    selector.auto_accept_single = opts.auto_accept_single;
+    selector.delete_double_tap = opts.delete_double_tap;
+    selector.copy_method = opts.copy_method;
+    selector.sort_mode = opts.sort;
+    let state_path = TryState::get_state_path();
+    // An unreadable state file just means default sorting and no pins.
+    if let Ok(state) = TryState::load_from(&state_path) {
+        if let SelectorMode::Scan(base_path) = &selector.mode {
+            if let Some(mode) = state.sort_for(base_path) {
+                selector.sort_mode = mode;
+            }
+        }
+        selector.pins = state.pins;
+        selector.permanent = state.permanent;
+        selector.ids = state.ids;
+        selector.visits = state.visits;
+        // Only the plain selector offers the last query back; `try set`
+        // picks among workspaces, not entries.
+        let restore = matches!(selector.mode, SelectorMode::Scan(_))
+            && !opts.no_restore_query
+            && selector.input_buffer.is_empty();
+        if let Some(query) = state.last_query.filter(|_| restore) {
+            selector.input_buffer = query;
+            selector.query_restored = true;
+        }
+    }
+    selector.state_path = Some(state_path);
+    selector.history_path = Some(WorkspaceManager::get_config_path());
+    if let SelectorMode::Scan(base_path) = &selector.mode {
+        let mut bases: Vec<PathBuf> = WorkspaceManager::get_workspaces()
+            .unwrap_or_default()
+            .iter()
+            .map(|p| strip_verbatim_prefix(p))
+            .filter(|p| p.is_dir())
+            .collect();
+        if !bases.contains(base_path) {
+            bases.insert(0, base_path.clone());
+        }
+        bases.dedup();
+        selector.bases = bases;
+    }
+    selector.group_filter = env::var("TRY_GROUP").ok().filter(|g| !g.is_empty());
+    selector.read_only = match &selector.mode {
+        SelectorMode::Scan(base_path) => opts.read_only || is_read_only_dir(base_path),
+        SelectorMode::History(_) | SelectorMode::Archive(_) => false,
+    };
+    selector.allow_create = !opts.no_create
+        && !selector.read_only
+        && !matches!(selector.mode, SelectorMode::Archive(_));
+    selector.allow_delete = !opts.no_delete && !selector.read_only;
+    selector.date_range = opts.date_range;
+    selector.inline_height = opts.height;
+    if matches!(selector.mode, SelectorMode::Scan(_)) {
+        selector.archive_dir = opts.archive_dir.clone();
+    }
+    let action = match opts.emit_events {
+        Some(path) => {
+            let mut events = fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .with_context(|| format!("cannot open {}", path.display()))?;
+            selector.run_headless(io::stdin().lock(), &mut events)?
+        }
+        None => selector.run()?,
+    };
+    // Picking an archive restores it into the base path and goes there.
+    let action = match (&selector.mode, action) {
+        (SelectorMode::Archive(_), Some(ShellAction::Cd(archive))) => Some(ShellAction::Cd(
+            restore_archive(&archive, &selector.workspace_path)?,
+        )),
+        (_, action) => action,
+    };
+    if let Some(action) = action {
+        if matches!(selector.mode, SelectorMode::Scan(_)) && !opts.no_restore_query {
+            if let Some(state_path) = &selector.state_path {
+                let query = Some(selector.input_buffer.as_str()).filter(|q| !q.is_empty());
+                let _ = TryState::remember_query(state_path, query);
+            }
+        }
+        if let (ShellAction::Cd(path) | ShellAction::MkdirCd(path), Some(state_path)) =
+            (&action, &selector.state_path)
+        {
+            let _ = TryState::remember_visit(state_path, path);
+        }
+        // For `Set`, update workspace history before emitting the cd script.
+        if let ShellAction::Set(path) = &action {
+            let _ = WorkspaceManager::add_workspace(path, selector.active_group());
+        }
+        if let Some(fifo) = opts.fifo {
+            write_fifo_line(fifo, &action_line(&action))?;
+            return Ok(());
+        }
+        let gen = Shell::detect().generator();
+        if let Some(editor) = opts.editor {
+            emit_script(
+                &build_open_script(gen.as_ref(), &action, editor),
+                opts.out_file,
+            )?;
+            return Ok(());
+        }
+        let touch = !opts.no_touch && !selector.read_only;
+        let mut script = build_action_script(gen.as_ref(), &action, touch);
+        if let (ShellAction::MkdirCd(path), Some(hook)) = (&action, opts.after_create) {
+            script = gen.join(&[script, after_create_command(gen.as_ref(), hook, path)]);
+        }
+        emit_script(&script, opts.out_file)?;
+    } else {
+        // Cancelled. Tell a pipe reader, which would otherwise block forever.
+        if let Some(fifo) = opts.fifo {
+            write_fifo_line(fifo, "cancel")?;
+        }
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// One-line, eval-free description of an action for `--fifo` readers:
+/// `<verb>\t<path>`.
+fn action_line(action: &ShellAction) -> String {
+    let (verb, path) = match action {
+        ShellAction::Cd(p) => ("cd", p),
+        ShellAction::MkdirCd(p) => ("mkdir", p),
+        ShellAction::Set(p) => ("set", p),
+    };
+    format!("{}\t{}", verb, path.display())
+}
+
+/// Ensure `path` exists and is a named pipe.
+fn check_fifo(path: &Path) -> Result<(), TryError> {
+    let metadata = fs::metadata(path).map_err(|_| TryError::FifoMissing(path.to_path_buf()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if !metadata.file_type().is_fifo() {
+            return Err(TryError::NotAFifo(path.to_path_buf()));
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        // No `mkfifo` outside Unix; anything but a directory is writable.
+        if metadata.is_dir() {
+            return Err(TryError::NotAFifo(path.to_path_buf()));
+        }
+    }
+    Ok(())
+}
+
+/// Write one line to the pipe. Blocks until a reader opens the other end.
+fn write_fifo_line(path: &Path, line: &str) -> Result<(), TryError> {
+    let emit_err = |source| TryError::ShellEmitFailed {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut pipe = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(emit_err)?;
+    writeln!(pipe, "{}", line).map_err(emit_err)
+}
+
+fn format_recent(entries: &[TryEntry], format: RecentFormat) -> String {
+    match format {
+        RecentFormat::Path => entries
+            .iter()
+            .map(|e| format!("{}\n", e.path.display()))
+            .collect(),
+        RecentFormat::Name => entries
+            .iter()
+            .map(|e| format!("{}\n", e.basename))
+            .collect(),
+        RecentFormat::Json => {
+            let items: Vec<serde_json::Value> = entries
+                .iter()
+                .map(|e| {
+                    let mtime = e
+                        .mtime
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    serde_json::json!({
+                        "name": e.basename,
+                        "path": e.path.to_string_lossy(),
+                        "mtime": mtime,
+                    })
+                })
+                .collect();
+            format!("{}\n", serde_json::Value::Array(items))
+        }
+    }
+}
+
+/// Absolute path of the running binary for the shell wrapper to call. When
+/// it can't be trusted (lookup failed, or the file is gone, e.g. replaced
+/// during an upgrade) the wrapper falls back to `try` on `PATH`.
+fn wrapper_exe(current: io::Result<PathBuf>) -> String {
+    match current {
+        Ok(exe) if exe.is_file() => exe.to_string_lossy().to_string(),
+        Ok(exe) => {
+            eprintln!(
+                "Warning: '{}' does not exist; the wrapper will run `try` from PATH",
+                exe.display()
+            );
+            "try".to_string()
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: cannot locate the try binary ({}); the wrapper will run `try` from PATH",
+                e
+            );
+            "try".to_string()
+        }
+    }
+}
+
+/// The rc file `init --install` appends to for `shell`. `name` (a path or
+/// program name such as `$SHELL`) only picks between `.zshrc` and
+/// `.bashrc`, since `Shell::Bash` covers both.
+fn shell_rc_file(shell: Shell, name: Option<&str>, home: &Path, zdotdir: Option<&Path>) -> PathBuf {
+    let name = name.map(|s| s.rsplit(['/', '\\']).next().unwrap_or(s));
+    match shell {
+        Shell::Fish => home.join(".config").join("fish").join("config.fish"),
+        Shell::Bash if name == Some("zsh") => zdotdir.unwrap_or(home).join(".zshrc"),
+        _ => home.join(".bashrc"),
+    }
+}
+
+/// Whether `rc` already sets up try: the same line, or any `eval` of a
+/// `try init` (or, for fish, a `try init` piped to `source`).
+fn has_init_line(rc: &str, line: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r#"eval.*\btry(\.exe)?'?\s+'?init\b|\btry(\.exe)?'?\s+'?init\b.*\|\s*source\b"#)
+            .unwrap()
+    });
+    rc.lines().any(|l| {
+        let l = l.trim();
+        !l.starts_with('#') && (l == line || re.is_match(l))
+    })
+}
+
+/// `init --install`: append `line` to the shell's rc file once the user
+/// agrees, unless it is already set up. `program` is the shell's name if
+/// known (see `Shell::program_for_init`).
+fn install_init_line(shell: Shell, program: Option<&str>, line: &str) -> Result<()> {
+    if shell == Shell::PowerShell {
+        anyhow::bail!("add this line to your $PROFILE:\n{}", line);
+    }
+    let home = dirs::home_dir().context("cannot find the home directory")?;
+    let rc = shell_rc_file(
+        shell,
+        program,
+        &home,
+        env::var_os("ZDOTDIR").map(PathBuf::from).as_deref(),
+    );
+    let existing = match fs::read_to_string(&rc) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("cannot read {}", rc.display())),
+    };
+    if has_init_line(&existing, line) {
+        eprintln!("{} already sets up try; nothing to do.", rc.display());
+        return Ok(());
+    }
+
+    eprint!("Append to {}?\n  {}\n[y/N] ", rc.display(), line);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        eprintln!("Cancelled.");
+        return Ok(());
+    }
+    if let Some(dir) = rc.parent() {
+        fs::create_dir_all(dir)?; // ~/.config/fish may not exist yet
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(&rc)?;
+    let sep = if existing.is_empty() || existing.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    write!(file, "{}\n# try-rs\n{}\n", sep, line)?;
+    eprintln!("Added. Open a new shell or run: source {}", rc.display());
+    Ok(())
+}
+
+/// The name of the process that started this one, e.g. the shell running
+/// `eval "$(try init)"`. Only Linux exposes it cheaply (`/proc`).
+#[cfg(target_os = "linux")]
+fn parent_process_name() -> Option<String> {
+    let comm = fs::read_to_string(format!(
+        "/proc/{}/comm",
+        std::os::unix::process::parent_id()
+    ));
+    comm.ok()
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn parent_process_name() -> Option<String> {
+    None
+}
+
+fn print_init_script(
+    shell: Shell,
+    fn_name: &str,
+    default_path: &str,
+    group: Option<&str>,
+    watch: bool,
+) {
+    let exe_str = wrapper_exe(env::current_exe());
+    let gen = shell.generator();
+    print!("{}", gen.init_script(fn_name, &exe_str, default_path));
+    if let Some(group) = group {
+        println!("{}", gen.set_env("TRY_GROUP", group));
+    }
+    if watch {
+        print!("{}", gen.watch_hook(&exe_str));
+    }
+}
+
+/// Where `try clone` puts the checkout: `--base` (expanded, relative to the
+/// current directory) or else the base path.
+fn clone_base(base: Option<&str>, base_path: PathBuf) -> io::Result<PathBuf> {
+    match base {
+        Some(dir) => Ok(env::current_dir()?.join(expand_path(dir))),
+        None => Ok(base_path),
+    }
+}
+
+/// `try clone` flags.
+#[derive(Debug, Default)]
+struct CloneOptions<'a> {
+    /// Directory name instead of `repo-YYYY-MM-DD`.
+    name: Option<String>,
+    /// `--proxy` (falls back to `$TRY_PROXY`).
+    proxy: Option<String>,
+    /// `--sparse` patterns.
+    sparse: &'a [String],
+    /// `--nest`: clone into `<name>/<repo>`.
+    nest: bool,
+    /// `--into`: clone into this relative subdirectory of the target.
+    into: Option<&'a str>,
+    /// Template for the directory name when `name` is unset.
+    name_template: Option<&'a str>,
+    /// `host_aliases` from config.toml.
+    host_aliases: Option<&'a HashMap<String, String>>,
+    /// `post_clone_cmd` from config.toml.
+    post_clone: Option<&'a str>,
+    /// `unsafe_post_clone` from config.toml.
+    unsafe_post_clone: bool,
+}
+
+/// Clone `url` into the base path by running git directly, so its progress
+/// reaches the terminal, then emit the `cd` into the new checkout. With
+/// `nest`, the checkout goes one level down (`base/name/repo`), leaving the
+/// dated directory free for notes; an existing checkout there is reused.
+/// `into` goes further down still, and git creates the directories. A
+/// `post_clone` command follows the `cd` when git did clone.
+fn clone_and_cd(
+    base_path: &Path,
+    url: &str,
+    opts: CloneOptions,
+    out_file: Option<&Path>,
+) -> Result<(), TryError> {
+    if let (Some(cmd), false) = (opts.post_clone, opts.unsafe_post_clone) {
+        check_post_clone_cmd(cmd)?;
+    }
+    let repo_name = || parse_repo_name(url).ok_or_else(|| TryError::InvalidGitUrl(url.to_string()));
+    let into = opts.into.map(checked_subdir).transpose()?;
+    let dir_name = if let Some(n) = opts.name {
+        n
+    } else {
+        // Parse git url for name; Ruby version produces repo-date style.
+        let host = parse_repo_host(url)
+            .map(|h| host_alias(&h, opts.host_aliases))
+            .unwrap_or_else(|| "local".to_string());
+        expand_clone_name(
+            opts.name_template.unwrap_or(DEFAULT_CLONE_NAME_TEMPLATE),
+            &repo_name()?,
+            &host,
+            &today_suffix(),
+        )
+    };
+    checked_clone_name(&dir_name)?;
+
+    let mut full_path = base_path.join(&dir_name);
+    if opts.nest {
+        full_path.push(repo_name()?);
+    }
+    if let Some(into) = &into {
+        full_path.push(into);
+    }
+
+    // Determine proxy command: CLI option > environment variable
+    let proxy_cmd = opts.proxy.or_else(|| env::var("TRY_PROXY").ok());
+
+    if into.is_none() {
+        fs::create_dir_all(full_path.parent().unwrap_or(base_path))?;
+    }
+    let reused = opts.nest && full_path.join(".git").exists();
+    if reused {
+        eprintln!("'{}' is already cloned; reusing it", full_path.display());
+    } else {
+        for argv in clone_commands(url, &full_path, proxy_cmd.as_deref(), opts.sparse) {
+            run_command(&argv)?;
+        }
+    }
+    let gen = Shell::detect().generator();
+    let mut script = gen.cd(&full_path);
+    if let (Some(cmd), false) = (opts.post_clone, reused) {
+        script = gen.join(&[script, after_create_command(gen.as_ref(), cmd, &full_path)]);
+    }
+    emit_script(&script, out_file)
+}
+
+/// Shell syntax `post_clone_cmd` may not use without `unsafe_post_clone`:
+/// anything that runs a second command, in any of the shells the script
+/// may be for: separators, `&` (background, or PowerShell's call
+/// operator), pipes, and substitutions (`$(...)`, backticks, fish's
+/// `(...)`). Plain redirections are fine.
+const POST_CLONE_FORBIDDEN: &[&str] = &[";", "&", "|", "\n", "`", "("];
+
+/// Reject a `post_clone_cmd` that is more than one command.
+fn check_post_clone_cmd(cmd: &str) -> Result<(), TryError> {
+    match POST_CLONE_FORBIDDEN.iter().find(|p| cmd.contains(*p)) {
+        Some(pattern) => Err(TryError::UnsafePostClone { pattern }),
+        None => Ok(()),
+    }
+}
+
+/// A clone's directory name (from `--name` or the name template) must stay
+/// one plain component, so `Path::join` can't replace or escape the base.
+fn checked_clone_name(name: &str) -> Result<(), TryError> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(()),
+        _ => Err(TryError::InvalidCloneName(name.to_string())),
+    }
+}
+
+/// `--into` must stay inside the clone target: a relative path with no
+/// `..` (or root/prefix) components.
+fn checked_subdir(subdir: &str) -> Result<PathBuf, TryError> {
+    let path = PathBuf::from(subdir);
+    let plain = path.components().all(|c| {
+        matches!(
+            c,
+            std::path::Component::Normal(_) | std::path::Component::CurDir
+        )
+    });
+    if subdir.is_empty() || !plain {
+        return Err(TryError::InvalidSubdir(subdir.to_string()));
+    }
+    Ok(path)
+}
+
+/// The commands that clone `url` into `dest`, as argv lists. A proxy tool
+/// (which may carry flags, e.g. `proxychains4 -q`) wraps the clone. With
+/// `sparse` patterns the clone skips the checkout, narrows the worktree in
+/// cone mode, then checks out.
+fn clone_commands(
+    url: &str,
+    dest: &Path,
+    proxy: Option<&str>,
+    sparse: &[String],
+) -> Vec<Vec<String>> {
+    let dest_str = dest.to_string_lossy().to_string();
+    let mut clone: Vec<String> = proxy
+        .map(|p| p.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    clone.extend(["git".to_string(), "clone".to_string()]);
+    if !sparse.is_empty() {
+        clone.push("--no-checkout".to_string());
+    }
+    clone.extend([url.to_string(), dest_str.clone()]);
+    if sparse.is_empty() {
+        return vec![clone];
+    }
+    let git_in = |args: &[&str]| -> Vec<String> {
+        ["git", "-C", &dest_str]
+            .iter()
+            .chain(args)
+            .map(|a| a.to_string())
+            .collect()
+    };
+    let mut set = git_in(&["sparse-checkout", "set"]);
+    set.extend(sparse.iter().cloned());
+    vec![
+        clone,
+        git_in(&["sparse-checkout", "init", "--cone"]),
+        set,
+        git_in(&["checkout", "HEAD"]),
+    ]
+}
+
+/// Run `argv` with its output on the terminal. Stdout goes to stderr as
+/// well: our own stdout is the script the shell wrapper evals.
+fn run_command(argv: &[String]) -> Result<(), TryError> {
+    let command = argv.join(" ");
+    let failed = |reason: String| TryError::CommandFailed {
+        command: command.clone(),
+        reason,
+    };
+    let status = std::process::Command::new(&argv[0])
+        .args(&argv[1..])
+        .stdout(io::stderr())
+        .status()
+        .map_err(|e| failed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failed(status.to_string()))
+    }
+}
+
+/// Archives are `<directory name>.tar.gz`.
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+
+/// Run `tar` and return its stdout. Output is captured, not relayed, so a
+/// failure inside the selector doesn't scribble over the screen.
+fn run_tar(args: &[&std::ffi::OsStr]) -> Result<String, TryError> {
+    let command = format!(
+        "tar {}",
+        args.iter()
+            .map(|a| a.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let output = std::process::Command::new("tar")
+        .args(args)
+        .output()
+        .map_err(|e| TryError::CommandFailed {
+            command: command.clone(),
+            reason: e.to_string(),
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(TryError::CommandFailed {
+            command,
+            reason: if stderr.is_empty() {
+                output.status.to_string()
+            } else {
+                stderr
+            },
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Tar and gzip `dir` into `archive_dir` as `<name>.tar.gz` (with a
+/// counter if that's taken), then remove `dir`. A failed `tar` leaves the
+/// directory alone and no partial archive behind.
+fn archive_workspace(dir: &Path, archive_dir: &Path) -> Result<PathBuf, TryError> {
+    let (Some(parent), Some(name)) = (dir.parent(), dir.file_name()) else {
+        return Err(TryError::WorkspaceUnavailable {
+            path: dir.to_path_buf(),
+            reason: "not a named directory".to_string(),
+        });
+    };
+    fs::create_dir_all(archive_dir)?;
+    let stem = name.to_string_lossy();
+    let mut archive = archive_dir.join(format!("{}{}", stem, ARCHIVE_SUFFIX));
+    let mut counter = 2;
+    while archive.exists() {
+        archive = archive_dir.join(format!("{}-{}{}", stem, counter, ARCHIVE_SUFFIX));
+        counter += 1;
+    }
+    let args = [
+        "-czf".as_ref(),
+        archive.as_os_str(),
+        "-C".as_ref(),
+        parent.as_os_str(),
+        name,
+    ];
+    if let Err(e) = run_tar(&args) {
+        let _ = fs::remove_file(&archive);
+        return Err(e);
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(archive)
+}
+
+/// The archives in `archive_dir`, named without `.tar.gz`. A missing
+/// directory just has none.
+fn archive_entries(archive_dir: &Path) -> Vec<TryEntry> {
+    let Ok(dir) = fs::read_dir(archive_dir) else {
+        return Vec::new();
+    };
+    dir.flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            let stem = name.strip_suffix(ARCHIVE_SUFFIX)?.to_string();
+            let mtime = e.metadata().ok()?.modified().ok()?;
+            Some(TryEntry::new(stem, e.path(), mtime))
+        })
+        .collect()
+}
+
+/// Unpack `archive` into `base` and remove it, returning the restored
+/// directory. The archive must hold one top-level directory that doesn't
+/// exist in `base` yet.
+fn restore_archive(archive: &Path, base: &Path) -> Result<PathBuf, TryError> {
+    let listing = run_tar(&["-tzf".as_ref(), archive.as_os_str()])?;
+    let tops: BTreeSet<&str> = listing
+        .lines()
+        .filter_map(|l| l.trim_start_matches("./").split('/').next())
+        .filter(|c| !c.is_empty())
+        .collect();
+    let name = match tops.into_iter().collect::<Vec<_>>()[..] {
+        [name] if name != "." && name != ".." => name.to_string(),
+        _ => return Err(TryError::NotAnArchive(archive.to_path_buf())),
+    };
+    let target = base.join(&name);
+    if target.exists() {
+        return Err(TryError::RestoreTargetExists(target));
+    }
+    fs::create_dir_all(base)?;
+    run_tar(&[
+        "-xzf".as_ref(),
+        archive.as_os_str(),
+        "-C".as_ref(),
+        base.as_os_str(),
+    ])?;
+    fs::remove_file(archive)?;
+    Ok(target)
+}
+
+/// `git -C dir pull --ff-only`, its output relayed to stderr line by line
+/// behind a `[label]` prefix so concurrent pulls stay readable. Git gets no
+/// stdin and may not prompt for credentials.
+fn pull_workspace(dir: &Path, label: &str) -> Result<(), TryError> {
+    let command = format!("git -C {} pull --ff-only", dir.display());
+    let failed = |reason: String| TryError::CommandFailed {
+        command: command.clone(),
+        reason,
+    };
+    let mut child = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["pull", "--ff-only"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    std::thread::scope(|s| {
+        s.spawn(|| relay_lines(stderr, label));
+        relay_lines(stdout, label);
+    });
+    let status = child.wait().map_err(|e| failed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(failed(status.to_string()))
+    }
+}
+
+fn relay_lines<R: io::Read>(pipe: Option<R>, label: &str) {
+    if let Some(pipe) = pipe {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            eprintln!("[{}] {}", label, line);
+        }
+    }
+}
+
+/// Pulls `try pull --parallel` runs at once.
+const PULL_JOBS: usize = 4;
+
+/// `try pull`: pull each of `dirs`, one after another or `PULL_JOBS` at a
+/// time. Returns a `name: reason` line per failure, in `dirs` order.
+fn pull_workspaces(dirs: &[PathBuf], parallel: bool) -> Vec<String> {
+    let pull = |dir: &PathBuf| {
+        let label = dir.file_name().map_or_else(
+            || dir.display().to_string(),
+            |n| n.to_string_lossy().to_string(),
+        );
+        pull_workspace(dir, &label)
+            .err()
+            .map(|e| format!("{}: {}", label, e))
+    };
+    if !parallel {
+        return dirs.iter().filter_map(pull).collect();
+    }
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let mut failed: Vec<(usize, String)> = std::thread::scope(|s| {
+        let workers: Vec<_> = (0..PULL_JOBS.min(dirs.len()))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut failed = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let Some(dir) = dirs.get(i) else {
+                            break failed;
+                        };
+                        failed.extend(pull(dir).map(|e| (i, e)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| {
+                w.join()
+                    .unwrap_or_else(|_| vec![(usize::MAX, "pull panicked".to_string())])
+            })
+            .collect()
+    });
+    failed.sort();
+    failed.into_iter().map(|(_, e)| e).collect()
+}
+
+/// Deliver a generated script: printed to stdout for the shell wrapper to
+/// `eval`, or written to `out_file` when the caller can't eval (tmux popups,
+/// other multiplexer bindings).
+fn emit_script(script: &str, out_file: Option<&Path>) -> Result<(), TryError> {
+    match out_file {
+        Some(path) => {
+            fs::write(path, format!("{}\n", script)).map_err(|source| TryError::ShellEmitFailed {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+        None => {
+            println!("{}", script);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn unique_tmp_dir(tag: &str) -> PathBuf {
+        let n = TMP_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("try-rs-test-{}-{}-{}", tag, std::process::id(), n));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(basename: &str, mtime: SystemTime) -> TryEntry {
+        TryEntry::new(basename.to_string(), PathBuf::from(basename), mtime)
+    }
+
+    fn score_for(basename: &str, query: &str) -> f64 {
+        let q = query.to_lowercase();
+        let qc: Vec<char> = q.chars().collect();
+        calculate_score(
+            &entry(basename, SystemTime::UNIX_EPOCH),
+            &q,
+            &qc,
+            SystemTime::now(),
+            &Recency::default(),
+        )
+    }
+
+    #[test]
+    fn parse_repo_name_https_with_git_suffix() {
+        assert_eq!(
+            parse_repo_name("https://github.com/user/repo.git").as_deref(),
+            Some("repo")
+        );
+    }
+
+    #[test]
+    fn parse_repo_name_https_without_git_suffix() {
+        assert_eq!(
+            parse_repo_name("https://github.com/user/cool-thing").as_deref(),
+            Some("cool-thing")
+        );
+    }
+
+    #[test]
+    fn parse_repo_name_ssh_scp_syntax() {
+        assert_eq!(
+            parse_repo_name("git@github.com:user/repo.git").as_deref(),
+            Some("repo")
+        );
+    }
+
+    #[test]
+    fn only_full_git_urls_clone_from_a_bare_query() {
+        for url in [
+            "https://github.com/user/repo.git",
+            "http://git.local:8080/team/repo",
+            "ssh://git@host.example/repo",
+            "git@github.com:user/repo.git",
+            "deploy@gitlab.example.com:group/sub/repo",
+        ] {
+            assert!(looks_like_clone_url(url), "{}", url);
+        }
+        for query in [
+            "http-notes",
+            "https-proxy-2024-05-14",
+            "https://",
+            "https://github.com/",
+            "git@",
+            "me@home",
+            "https://github.com/user/repo with-space",
+        ] {
+            assert!(!looks_like_clone_url(query), "{}", query);
+        }
+    }
+
+    #[test]
+    fn parse_repo_host_handles_url_styles() {
+        for (url, host) in [
+            ("https://github.com/user/repo.git", Some("github.com")),
+            ("ssh://git@GitLab.com:2222/user/repo", Some("gitlab.com")),
+            ("git@bitbucket.org:user/repo.git", Some("bitbucket.org")),
+            ("file:///srv/git/repo", None),
+            ("/srv/git/repo", None),
+        ] {
+            assert_eq!(parse_repo_host(url).as_deref(), host, "{}", url);
+        }
+    }
+
+    #[test]
+    fn clone_name_template_uses_host_aliases() {
+        let mut aliases = HashMap::new();
+        aliases.insert("git.corp.example".to_string(), "corp".to_string());
+        aliases.insert("github.com".to_string(), "hub".to_string());
+        assert_eq!(host_alias("gitlab.com", None), "gl");
+        assert_eq!(host_alias("github.com", Some(&aliases)), "hub");
+        assert_eq!(host_alias("git.corp.example", Some(&aliases)), "corp");
+        assert_eq!(host_alias("codeberg.org", Some(&aliases)), "codeberg.org");
+        assert_eq!(
+            expand_clone_name("{host}-{repo}-{date}", "try", "gh", "2024-01-02"),
+            "gh-try-2024-01-02"
+        );
+        assert_eq!(
+            expand_clone_name(DEFAULT_CLONE_NAME_TEMPLATE, "try", "gh", "2024-01-02"),
+            "try-2024-01-02"
+        );
+    }
+
+    #[test]
+    fn expand_path_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path("~/foo/bar"), home.join("foo/bar"));
+    }
+
+    #[test]
+    fn expand_path_windows_variables() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path("%USERPROFILE%/tries"), home.join("tries"));
+        assert_eq!(expand_path("%userprofile%"), home);
+        assert_eq!(expand_path("~\\tries"), home.join("tries"));
+        let appdata = dirs::config_dir().unwrap();
+        assert_eq!(expand_path("%APPDATA%/try"), appdata.join("try"));
+        assert_eq!(expand_path("%OTHER%/x"), PathBuf::from("%OTHER%/x"));
+    }
+
+    #[test]
+    fn expand_env_vars_dollar_and_braces() {
+        let lookup = |k: &str| match k {
+            "HOME" => Some("/home/me".to_string()),
+            "USER" => Some("me".to_string()),
+            _ => None,
+        };
+        let expand = |s: &str| expand_env_vars_with(s, &lookup, &mut Vec::new());
+        assert_eq!(expand("$HOME/scratch"), "/home/me/scratch");
+        assert_eq!(expand("~/${USER}_tmp"), "~/me_tmp");
+        assert_eq!(expand("$USER-$USER"), "me-me");
+        // Undefined or malformed references stay literal.
+        assert_eq!(expand("$NOPE/x"), "$NOPE/x");
+        assert_eq!(expand("${HOME"), "${HOME");
+        assert_eq!(expand("a$/b$1$"), "a$/b$1$");
+        assert_eq!(expand("${}"), "${}");
+    }
+
+    #[test]
+    fn expand_env_vars_defaults_nest() {
+        let lookup = |k: &str| match k {
+            "HOME" => Some("/home/me".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let expand = |s: &str| expand_env_vars_with(s, &lookup, &mut Vec::new());
+        assert_eq!(
+            expand("${XDG_DATA_HOME:-$HOME/.local/share}/projects"),
+            "/home/me/.local/share/projects"
+        );
+        assert_eq!(expand("${A:-${B:-${HOME}}}/x"), "/home/me/x");
+        assert_eq!(expand("${EMPTY:-d}"), "d");
+        assert_eq!(expand("${EMPTY-d}"), "");
+        assert_eq!(expand("${HOME:-d}"), "/home/me");
+        assert_eq!(expand("${HOME?x}"), "${HOME?x}");
+
+        let mut unset = Vec::new();
+        let out = expand_env_vars_with("$NOPE/${GONE}/${X:-y}", &lookup, &mut unset);
+        assert_eq!(out, "$NOPE/${GONE}/y");
+        assert_eq!(unset, ["NOPE", "GONE"]);
+    }
+
+    #[test]
+    fn expand_path_bare_tilde_is_home() {
+        assert_eq!(expand_path("~"), dirs::home_dir().unwrap());
+    }
+
+    #[test]
+    fn windows_paths_count_as_explicit_locations() {
+        for path in [
+            r"C:\tries\x",
+            "d:/x",
+            r"\x",
+            "/x",
+            "~/x",
+            "%USERPROFILE%\\x",
+        ] {
+            assert!(is_explicit_location(path, true), "{}", path);
+        }
+        for path in ["x", r"x\y", "1:x"] {
+            assert!(!is_explicit_location(path, true), "{}", path);
+        }
+        // On Unix these are just odd directory names.
+        assert!(!is_explicit_location(r"C:\tries", false));
+        assert!(is_explicit_location("/x", false));
+        assert!(is_explicit_location("%appdata%/x", false));
+    }
+
+    #[test]
+    fn native_separators_only_rewrites_on_windows() {
+        let p = Path::new("C:/tries/a\\b");
+        assert_eq!(native_separators(p, true), PathBuf::from(r"C:\tries\a\b"));
+        assert_eq!(native_separators(p, false), p.to_path_buf());
+    }
+
+    #[test]
+    fn expand_path_absolute_untouched() {
+        assert_eq!(expand_path("/abs/path"), PathBuf::from("/abs/path"));
+    }
+
+    #[test]
+    fn score_subsequence_match_is_positive() {
+        assert!(score_for("myproject-2025-01-01", "myp") > 0.0);
+    }
+
+    #[test]
+    fn score_non_subsequence_is_zero() {
+        assert_eq!(score_for("myproject", "zzz"), 0.0);
+    }
+
+    #[test]
+    fn score_empty_query_still_ranks_by_recency_and_suffix() {
+        let e = entry("proj-2025-01-01", SystemTime::now());
+        let s = calculate_score(&e, "", &[], SystemTime::now(), &Recency::default());
+        assert!(s > 0.0);
+    }
+
+    #[test]
+    fn score_prefers_contiguous_match() {
+        let contiguous = score_for("test-2025-01-01", "test");
+        let spread = score_for("t-e-s-t-2025-01-01", "test");
+        assert!(contiguous > spread, "{} !> {}", contiguous, spread);
+    }
+
+    /// Fixed "now" so recency-sensitive assertions never depend on the clock.
+    fn fixed_now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    fn hours_ago(h: u64) -> SystemTime {
+        fixed_now() - Duration::from_secs(h * 3600)
+    }
+
+    fn score_at(basename: &str, query: &str, mtime: SystemTime) -> f64 {
+        let q = query.to_lowercase();
+        let qc: Vec<char> = q.chars().collect();
+        calculate_score(
+            &entry(basename, mtime),
+            &q,
+            &qc,
+            fixed_now(),
+            &Recency::default(),
+        )
+    }
+
+    #[test]
+    fn score_exact_word_beats_embedded_match() {
+        let exact = score_at("api-2025-01-01", "api", hours_ago(1));
+        let embedded = score_at("rapid-2025-01-01", "api", hours_ago(1));
+        assert!(exact > embedded, "{} !> {}", exact, embedded);
+    }
+
+    #[test]
+    fn score_newer_entry_beats_older_entry() {
+        let newer = score_at("proj-2025-01-01", "proj", hours_ago(1));
+        let older = score_at("proj-2025-01-01", "proj", hours_ago(500));
+        assert!(newer > older, "{} !> {}", newer, older);
+    }
+
+    #[test]
+    fn score_recency_decays_monotonically() {
+        let s1 = score_at("proj", "", hours_ago(1));
+        let s10 = score_at("proj", "", hours_ago(10));
+        let s100 = score_at("proj", "", hours_ago(100));
+        assert!(s1 > s10 && s10 > s100, "{} {} {}", s1, s10, s100);
+    }
+
+    #[test]
+    fn score_recency_only_value_for_empty_query() {
+        // No query, no trailing digit: just 3 / sqrt(hours + 1).
+        let s = score_at("proj", "", hours_ago(3));
+        assert!((s - 1.5).abs() < 1e-9, "{}", s);
+    }
+
+    fn set_mtime(path: &Path, mtime: SystemTime) {
+        fs::File::open(path).unwrap().set_modified(mtime).unwrap();
+    }
+
+    #[test]
+    fn newest_file_mtime_looks_inside_but_not_too_deep() {
+        let dir = unique_tmp_dir("newest-file");
+        fs::create_dir_all(dir.join("src/deep/deeper")).unwrap();
+        fs::create_dir_all(dir.join("node_modules")).unwrap();
+        for (file, h) in [
+            ("README.md", 48),
+            ("src/deep/lib.rs", 5),
+            ("src/deep/deeper/x.rs", 1),
+            ("node_modules/y.js", 0),
+        ] {
+            fs::write(dir.join(file), "x").unwrap();
+            set_mtime(&dir.join(file), hours_ago(h));
+        }
+        assert_eq!(newest_file_mtime(&dir), Some(hours_ago(5)));
+        assert_eq!(newest_file_mtime(&dir.join("missing")), None);
+    }
+
+    #[test]
+    fn recency_from_files_ranks_edited_workspaces_up() {
+        let ago = |h: u64| SystemTime::now() - Duration::from_secs(h * 3600);
+        let dir = unique_tmp_dir("file-recency");
+        // "edited" had a file changed an hour ago; "touched" only had its
+        // directory entry changed, ten hours ago.
+        for (name, dir_hours, file_hours) in [("edited", 200, 1), ("touched", 10, 300)] {
+            let path = dir.join(name);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("notes.md"), "x").unwrap();
+            set_mtime(&path.join("notes.md"), ago(file_hours));
+            set_mtime(&path, ago(dir_hours));
+        }
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir);
+        let order = |sel: &mut TrySelector| {
+            sel.load_entries().unwrap();
+            sel.refresh_scores();
+            let names: Vec<_> = sel.entries.iter().map(|e| e.basename.clone()).collect();
+            names
+        };
+        assert_eq!(order(&mut sel), ["touched", "edited"]);
+        sel.file_activity = Some(FileActivity::default());
+        assert_eq!(order(&mut sel), ["edited", "touched"]);
+        let cached = sel.file_activity.as_ref().unwrap().cache.len();
+        assert_eq!(cached, 2);
+    }
+
+    #[test]
+    fn score_recency_prefers_last_accessed_over_mtime() {
+        let mut visited = entry("/ws/a", hours_ago(500));
+        visited.last_accessed = Some(hours_ago(3));
+        let (q, qc) = (String::new(), Vec::new());
+        let s = calculate_score(&visited, &q, &qc, fixed_now(), &Recency::default());
+        assert!((s - 1.5).abs() < 1e-9, "{}", s);
+    }
+
+    #[test]
+    fn score_future_mtime_gets_no_recency_bonus() {
+        let future = fixed_now() + Duration::from_secs(3600);
+        assert_eq!(score_at("proj", "", future), 0.0);
+    }
+
+    #[test]
+    fn recency_default_keeps_original_curve() {
+        let e = entry("proj", hours_ago(8));
+        let bonus = Recency::default().bonus(&e, fixed_now());
+        assert!((bonus - 3.0 / 9f64.sqrt()).abs() < 1e-9, "{}", bonus);
+    }
+
+    #[test]
+    fn recency_halves_after_half_life() {
+        // A month-long half-life keeps a week-old workspace well ahead of
+        // what the default gives it.
+        let slow = Recency {
+            weight: 3.0,
+            half_life_hours: 720.0,
+        };
+        let at_half_life = slow.bonus(&entry("proj", hours_ago(720)), fixed_now());
+        assert!((at_half_life - 1.5).abs() < 1e-9, "{}", at_half_life);
+        let week_old = entry("proj", hours_ago(168));
+        assert!(
+            slow.bonus(&week_old, fixed_now())
+                > 4.0 * Recency::default().bonus(&week_old, fixed_now())
+        );
+    }
+
+    #[test]
+    fn recency_from_config_and_env() {
+        let config =
+            TryConfig::parse("recency_weight = 5.0\nrecency_half_life_hours = 48\n").unwrap();
+        let no_env = |_: &str| None;
+        assert_eq!(
+            config.recency_with(no_env),
+            Recency {
+                weight: 5.0,
+                half_life_hours: 48.0
+            }
+        );
+        let env = |k: &str| (k == "TRY_RECENCY_HALF_LIFE").then(|| "12".to_string());
+        assert_eq!(config.recency_with(env).half_life_hours, 12.0);
+        // Nonsense falls back to the default rather than dividing by zero.
+        let zero = |_: &str| Some("0".to_string());
+        assert_eq!(TryConfig::default().recency_with(zero), Recency::default());
+        assert_eq!(config.recency_with(zero).weight, 5.0);
+    }
+
+    #[test]
+    fn score_boundary_bonus_after_hyphen() {
+        let boundary = score_at("foo-bar", "b", hours_ago(1));
+        let inner = score_at("fooxbar", "b", hours_ago(1));
+        assert!(boundary > inner, "{} !> {}", boundary, inner);
+    }
+
+    #[test]
+    fn score_length_penalty_favors_shorter_names() {
+        let short = score_at("abc", "abc", hours_ago(1));
+        let long = score_at("abcdefghijkl", "abc", hours_ago(1));
+        assert!(short > long, "{} !> {}", short, long);
+    }
+
+    #[test]
+    fn score_density_favors_early_match() {
+        let early = score_at("abc-xxxxxx", "abc", hours_ago(1));
+        let late = score_at("xxxxxx-abc", "abc", hours_ago(1));
+        assert!(early > late, "{} !> {}", early, late);
+    }
+
+    #[test]
+    fn score_trailing_digit_bonus() {
+        let dated = score_at("proj1", "", hours_ago(1));
+        let plain = score_at("proja", "", hours_ago(1));
+        assert!((dated - plain - 2.0).abs() < 1e-9, "{} vs {}", dated, plain);
+    }
+
+    #[test]
+    fn score_out_of_order_query_is_zero() {
+        assert_eq!(score_at("abc", "cba", hours_ago(1)), 0.0);
+    }
+
+    #[test]
+    fn score_query_longer_than_name_is_zero() {
+        assert_eq!(score_at("ab", "abc", hours_ago(1)), 0.0);
+    }
+
+    #[test]
+    fn score_is_case_insensitive() {
+        let upper = score_at("MyProject", "MYP", hours_ago(1));
+        let lower = score_at("myproject", "myp", hours_ago(1));
+        assert!(upper > 0.0);
+        assert!((upper - lower).abs() < 1e-9);
+    }
+
+    #[test]
+    fn score_unicode_names_do_not_panic() {
+        assert!(score_at("日本語-プロジェクト", "プロ", hours_ago(1)) > 0.0);
+        assert_eq!(score_at("日本語", "x", hours_ago(1)), 0.0);
+        // Lowercasing can change the char count ('İ' -> "i̇").
+        let _ = score_at("İstanbul-2025-01-01", "ist", hours_ago(1));
+    }
+
+    #[test]
+    fn score_description_match_is_weighted_below_name_match() {
+        let mut described = entry("xyz", hours_ago(1));
+        described.set_description(Some("Parser experiments".to_string()));
+        let q = "parser";
+        let qc: Vec<char> = q.chars().collect();
+        let recency = Recency::default();
+        let by_desc = calculate_score(&described, q, &qc, fixed_now(), &recency);
+        let by_name = score_at("parser", q, hours_ago(1));
+        let bonus = recency.bonus(&described, fixed_now());
+
+        assert!(by_desc > bonus, "the description alone should match");
+        let expected = DESCRIPTION_WEIGHT * fuzzy_match("parser experiments", 18, &qc, 0.0);
+        assert!((by_desc - bonus - expected).abs() < 1e-9);
+        assert!(by_name > by_desc, "{} !> {}", by_name, by_desc);
+    }
+
+    #[test]
+    fn score_is_deterministic() {
+        let a = score_at("proj-2025-01-01", "pj", hours_ago(7));
+        let b = score_at("proj-2025-01-01", "pj", hours_ago(7));
+        assert_eq!(a, b);
+    }
+
+    fn substring_score_at(basename: &str, query: &str, mtime: SystemTime) -> f64 {
+        let q = query.to_lowercase();
+        let qc: Vec<char> = q.chars().collect();
+        SubstringScorer::default().score(&entry(basename, mtime), &q, &qc, fixed_now())
+    }
+
+    #[test]
+    fn scorer_kind_parse() {
+        assert_eq!(ScorerKind::parse("fuzzy"), Some(ScorerKind::Fuzzy));
+        assert_eq!(
+            ScorerKind::parse(" Substring "),
+            Some(ScorerKind::Substring)
+        );
+        assert_eq!(ScorerKind::parse("smith-waterman"), None);
+    }
+
+    #[test]
+    fn fuzzy_scorer_matches_calculate_score() {
+        let e = entry("proj-2025-01-01", hours_ago(2));
+        let qc: Vec<char> = "pj".chars().collect();
+        assert_eq!(
+            FuzzyScorer::default().score(&e, "pj", &qc, fixed_now()),
+            calculate_score(&e, "pj", &qc, fixed_now(), &Recency::default())
+        );
+    }
+
+    #[test]
+    fn substring_scorer_rejects_scattered_match() {
+        assert!(score_at("t-e-s-t", "test", hours_ago(1)) > 0.0);
+        assert_eq!(substring_score_at("t-e-s-t", "test", hours_ago(1)), 0.0);
+        assert!(substring_score_at("my-test", "test", hours_ago(1)) > 0.0);
+    }
+
+    #[test]
+    fn substring_scorer_prefers_early_and_boundary_matches() {
+        let early = substring_score_at("api-xxxxxxxx", "api", hours_ago(1));
+        let late = substring_score_at("xxxxxxxx-api", "api", hours_ago(1));
+        let inner = substring_score_at("xxxxxxxxxapi", "api", hours_ago(1));
+        assert!(early > late, "{} !> {}", early, late);
+        assert!(late > inner, "{} !> {}", late, inner);
+    }
+
+    #[test]
+    fn substring_scorer_empty_query_is_recency_only() {
+        let s = substring_score_at("proj", "", hours_ago(3));
+        assert!((s - 1.5).abs() < 1e-9, "{}", s);
+    }
+
+    #[test]
+    fn format_score_fixed_precision() {
+        assert_eq!(format_score(1.5), "1.500");
+        assert_eq!(format_score(0.1 + 0.2), format_score(0.3));
+    }
+
+    fn ansi_regex() -> &'static Regex {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        RE.get_or_init(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap())
+    }
+
+    /// A Scan-mode selector over real directories named `names`, sized to a
+    /// fixed 40x12 terminal. Scores descend in the given order.
+    fn snapshot_selector(tag: &str, names: &[&str], query: &str) -> TrySelector {
+        let dir = unique_tmp_dir(tag);
+        let mut sel = TrySelector::new(
+            SelectorMode::Scan(dir.clone()),
+            query.to_string(),
+            PathBuf::from("/home/me/tries"),
+        );
+        sel.width = 40;
+        sel.height = 12;
+        sel.entries = names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let path = dir.join(n);
+                fs::create_dir_all(&path).unwrap();
+                let mut e = entry(n, SystemTime::UNIX_EPOCH);
+                e.path = path;
+                e.score = (names.len() - i) as f64;
+                e
+            })
+            .collect();
+        sel
+    }
+
+    /// Render into memory and strip ANSI escapes (the date of "today" is
+    /// redacted so snapshots are stable).
+    fn render_plain(sel: &mut TrySelector) -> String {
+        let mut buf = Vec::new();
+        sel.render(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        ansi_regex()
+            .replace_all(&text, "")
+            .replace(&today_suffix(), "YYYY-MM-DD")
+            .replace('\r', "")
+    }
+
+    /// Snapshot files are named after the test alone; the default module
+    /// prefix would be `r#try__tests__` since the binary is named `try`.
+    fn assert_render_snapshot(name: &str, sel: &mut TrySelector) {
+        let mut settings = insta::Settings::clone_current();
+        settings.set_prepend_module_to_snapshot(false);
+        settings.bind(|| insta::assert_snapshot!(name, render_plain(sel)));
+    }
+
+    #[test]
+    fn render_snapshot_empty_list() {
+        let mut sel = snapshot_selector("render-empty", &[], "");
+        assert_render_snapshot("render_empty_list", &mut sel);
+    }
+
+    #[test]
+    fn render_snapshot_single_entry_selected() {
+        let mut sel = snapshot_selector("render-single", &["alpha-2025-01-01"], "");
+        assert_render_snapshot("render_single_entry_selected", &mut sel);
+    }
+
+    #[test]
+    fn render_snapshot_scrolled() {
+        let names = [
+            "a-2025-01-01",
+            "b-2025-01-02",
+            "c-2025-01-03",
+            "d",
+            "e",
+            "f",
+        ];
+        let mut sel = snapshot_selector("render-scroll", &names, "");
+        sel.cursor_pos = 5;
+        assert_render_snapshot("render_scrolled", &mut sel);
+    }
+
+    #[test]
+    fn render_snapshot_delete_mode() {
+        let mut sel = snapshot_selector("render-delete", &["keep", "doomed"], "");
+        sel.cursor_pos = 1;
+        sel.toggle_delete_mark();
+        assert_render_snapshot("render_delete_mode", &mut sel);
+    }
+
+    #[test]
+    fn render_snapshot_create_new() {
+        let mut sel = snapshot_selector("render-create", &["alpha"], "newthing");
+        sel.entries[0].score = 0.0; // filtered out by the query
+        assert_render_snapshot("render_create_new", &mut sel);
+    }
+
+    #[test]
+    fn date_digits_are_highlighted_in_the_suffix() {
+        let highlighted = |query: &str| {
+            let mut sel = snapshot_selector("date-highlight", &["proj-2024-05-14"], query);
+            let mut buf = Vec::new();
+            sel.render(&mut buf).unwrap();
+            let yellow = format!("{}", SetForegroundColor(Color::Yellow));
+            let text = String::from_utf8(buf).unwrap();
+            let row = &text[text.rfind(DEFAULT_ICON).unwrap()..];
+            row.split(&yellow)
+                .skip(1)
+                .map(|s| ansi_regex().replace_all(s, "").chars().next().unwrap())
+                .collect::<String>()
+        };
+        assert_eq!(highlighted("2024"), "2024");
+        assert_eq!(highlighted("pj-05"), "pj-05");
+        assert_eq!(highlighted("proj"), "proj");
+    }
+
+    #[test]
+    fn footer_tells_how_many_rows_are_scrolled_off() {
+        let names: Vec<String> = (0..12).map(|i| format!("entry-{:02}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut sel = snapshot_selector("footer-scroll", &names, "");
+        sel.width = 90;
+        let out = render_plain(&mut sel);
+        assert!(out.contains("(8 more below)"), "{}", out);
+        assert!(!out.contains("above"), "{}", out);
+
+        sel.cursor_pos = 8;
+        let out = render_plain(&mut sel);
+        assert!(out.contains("(5 above) (3 more below)"), "{}", out);
+
+        sel.cursor_pos = 11;
+        assert!(render_plain(&mut sel).contains("(8 above)"));
+    }
+
+    #[test]
+    fn worktrees_get_a_badge_with_their_repository_count() {
+        let dir = unique_tmp_dir("worktrees");
+        let common = dir.join("main").join(".git");
+        for name in ["feature", "hotfix"] {
+            let gitdir = common.join("worktrees").join(name);
+            fs::create_dir_all(&gitdir).unwrap();
+            fs::write(gitdir.join("commondir"), "../..\n").unwrap();
+        }
+        fs::create_dir_all(dir.join("feature")).unwrap();
+        fs::write(
+            dir.join("feature").join(".git"),
+            "gitdir: ../main/.git/worktrees/feature\n",
+        )
+        .unwrap();
+        let worktrees = |name: &str| WorkspaceKind::detect_with_worktrees(&dir.join(name)).1;
+        assert_eq!(worktrees("feature"), Some(2));
+        assert_eq!(worktrees("main"), None);
+
+        let mut sel = snapshot_selector("worktree-badge", &[], "");
+        sel.width = 60;
+        sel.entries = scan_entries(&dir).unwrap();
+        sel.refresh_scores();
+        let out = render_plain(&mut sel);
+        assert!(out.contains("feature [2 worktrees]"), "{}", out);
+        assert!(!out.contains("main ["), "{}", out);
+    }
+
+    #[test]
+    fn workspace_kind_comes_from_the_git_metadata() {
+        let dir = unique_tmp_dir("workspace-kind");
+        let main = dir.join("main");
+        let gitdir = main.join(".git").join("worktrees").join("feature");
+        fs::create_dir_all(&gitdir).unwrap();
+        fs::write(gitdir.join("commondir"), "../..\n").unwrap();
+        fs::write(gitdir.join("HEAD"), "ref: refs/heads/feat/x\n").unwrap();
+        fs::write(
+            main.join(".git").join("config"),
+            "[core]\n\tbare = false\n[remote \"origin\"]\n\turl = git@github.com:u/main.git\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("feature")).unwrap();
+        fs::write(
+            dir.join("feature").join(".git"),
+            "gitdir: ../main/.git/worktrees/feature\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("notes")).unwrap();
+
+        let main_kind = WorkspaceKind::detect(&main);
+        assert_eq!(
+            main_kind,
+            WorkspaceKind::Clone {
+                url: "git@github.com:u/main.git".to_string()
+            }
+        );
+        assert_eq!(main_kind.icon(), "📦");
+        let feature = WorkspaceKind::detect(&dir.join("feature"));
+        assert_eq!(
+            feature,
+            WorkspaceKind::Worktree {
+                main_repo: canonicalize_clean(&main),
+                branch: "feat/x".to_string()
+            }
+        );
+        assert!(feature.describe().ends_with(" on feat/x"));
+        assert_eq!(
+            WorkspaceKind::detect(&dir.join("notes")),
+            WorkspaceKind::Manual
+        );
+    }
+
+    #[test]
+    fn footer_counts_follow_the_filter() {
+        let mut sel = snapshot_selector("footer-counts", &["alpha", "beta", "gamma"], "a");
+        sel.show_counts = true;
+        sel.refresh_scores();
+        let gamma = sel.entries.iter().find(|e| e.basename == "gamma").unwrap();
+        sel.marked_for_deletion.push(gamma.path.clone());
+        // Too narrow to fit beside the key hints.
+        assert!(!render_plain(&mut sel).contains(" of 3"));
+
+        sel.width = 80;
+        sel.input_buffer = "et".to_string();
+        sel.refresh_scores();
+        assert!(render_plain(&mut sel).contains("1 of 3 · 1 marked"));
+    }
+
+    #[test]
+    fn footer_counts_measure_wide_characters_by_column() {
+        let mut sel = snapshot_selector("footer-counts-wide", &["alpha"], "");
+        sel.show_counts = true;
+        sel.width = 30;
+        // Twelve characters but 24 columns: no room left for "1 of 1".
+        sel.status = Some("已复制路径到剪贴板请粘贴".to_string());
+        assert!(!render_plain(&mut sel).contains("1 of 1"));
+
+        sel.status = Some("已复制".to_string());
+        assert!(render_plain(&mut sel).contains("1 of 1"));
+    }
+
+    #[test]
+    fn render_snapshot_compact_layout() {
+        let mut sel = snapshot_selector("render-compact", &["a", "b", "c", "d"], "");
+        sel.height = 5;
+        sel.cursor_pos = 3;
+        assert_render_snapshot("render_compact_layout", &mut sel);
+    }
+
+    #[test]
+    fn render_snapshot_too_small() {
+        let mut sel = snapshot_selector("render-tiny", &["a"], "");
+        sel.height = 2;
+        assert_render_snapshot("render_too_small", &mut sel);
+    }
+
+    #[test]
+    fn inline_selector_draws_only_in_its_rows() {
+        let mut sel = snapshot_selector("render-inline", &["a", "b"], "");
+        sel.inline_height = Some(5);
+        sel.height = 5;
+        sel.top = 10;
+        let mut buf = Vec::new();
+        sel.render(&mut buf).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.starts_with("\x1b[11;1H"));
+        assert!(out.contains("\x1b[15;1H")); // footer on the last row
+        assert!(!out.contains("\x1b[2J"));
+
+        // A shrinking terminal pulls the rows up to keep them on screen.
+        sel.step(&Event::Resize(40, 12)).unwrap();
+        assert_eq!((sel.height, sel.top), (5, 7));
+        sel.step(&Event::Resize(40, 4)).unwrap();
+        assert_eq!((sel.height, sel.top), (4, 0));
+    }
+
+    #[test]
+    fn read_only_selector_says_so_and_offers_no_create_row() {
+        let mut sel = snapshot_selector("render-read-only", &["alpha"], "beta");
+        sel.width = 60;
+        sel.read_only = true;
+        sel.allow_create = false;
+        let text = render_plain(&mut sel);
+        assert!(text.contains("· read-only"));
+        assert!(!text.contains("Create new"));
+    }
+
+    #[test]
+    fn render_survives_every_small_height() {
+        let mut sel = snapshot_selector("render-heights", &["a", "b", "c"], "q");
+        for h in 0..=12 {
+            sel.height = h;
+            sel.width = h * 3;
+            render_plain(&mut sel);
+        }
+    }
+
+    #[test]
+    fn search_cursor_blinks_and_shows_on_keypress() {
+        let mut sel = snapshot_selector("blink", &["a"], "");
+        let t0 = sel.blink_epoch;
+        assert!(sel.cursor_on(t0));
+        assert_eq!(sel.until_blink(t0), CURSOR_BLINK);
+        let hidden = t0 + CURSOR_BLINK + Duration::from_millis(100);
+        assert!(!sel.cursor_on(hidden));
+        assert_eq!(sel.until_blink(hidden), Duration::from_millis(400));
+        assert!(sel.cursor_on(t0 + CURSOR_BLINK * 2));
+
+        sel.blink_epoch = Instant::now() - CURSOR_BLINK - Duration::from_millis(100);
+        assert!(!sel.cursor_on(Instant::now()));
+        press(&mut sel, &type_keys("x"));
+        assert!(sel.cursor_on(Instant::now()));
+    }
+
+    #[test]
+    fn resize_debounce_collapses_burst() {
+        let t0 = Instant::now();
+        let mut d = ResizeDebouncer::new(80, 24);
+        assert_eq!(d.poll_timeout(t0), IDLE_POLL);
+
+        for i in 0..5u16 {
+            let t = t0 + Duration::from_millis(i as u64 * 10);
+            assert!(d.on_resize(81 + i, 24, t));
+            assert!(!d.settled(t));
+        }
+        let last = t0 + Duration::from_millis(40);
+        assert_eq!(
+            d.poll_timeout(last + Duration::from_millis(20)),
+            Duration::from_millis(30)
+        );
+        assert!(!d.settled(last + Duration::from_millis(49)));
+        assert!(d.settled(last + RESIZE_DEBOUNCE));
+        // Only once per burst.
+        assert!(!d.settled(last + Duration::from_millis(200)));
+        assert_eq!(d.poll_timeout(last + Duration::from_millis(200)), IDLE_POLL);
+    }
+
+    #[test]
+    fn resizes_only_clear_the_screen_when_it_shrinks() {
+        let t0 = Instant::now();
+        let mut d = ResizeDebouncer::new(80, 24);
+        // A burst that only grows: `run` redraws in place, no full clear.
+        for i in 0..10u16 {
+            let (w, h) = (60 + i * 4, 24 + i % 3);
+            let t = t0 + Duration::from_millis(i as u64 * 5);
+            d.on_resize(w, h, t);
+        }
+        assert!(d.settled(t0 + Duration::from_secs(1)));
+        assert!(!d.shrank());
+
+        let t1 = t0 + Duration::from_secs(2);
+        d.on_resize(100, 20, t1);
+        assert!(d.settled(t1 + RESIZE_DEBOUNCE));
+        assert!(d.shrank());
+        assert!(!d.shrank());
+    }
+
+    #[test]
+    fn resize_debounce_ignores_unchanged_size() {
+        let t0 = Instant::now();
+        let mut d = ResizeDebouncer::new(80, 24);
+        assert!(!d.on_resize(80, 24, t0));
+        assert!(!d.settled(t0 + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn recent_entries_sorted_newest_first_and_capped() {
+        let base = SystemTime::UNIX_EPOCH;
+        let entries = vec![
+            entry("old", base + Duration::from_secs(10)),
+            entry("newest", base + Duration::from_secs(30)),
+            entry("middle", base + Duration::from_secs(20)),
+        ];
+        let recent = recent_entries(entries.clone(), 2, RecentSort::Mtime, &[]);
+        let names: Vec<&str> = recent.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["newest", "middle"]);
+
+        let recent = recent_entries(entries, 2, RecentSort::Name, &[]);
+        let names: Vec<&str> = recent.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["middle", "newest"]);
+    }
+
+    #[test]
+    fn recent_entries_by_size_sizes_only_the_most_recent() {
+        let dir = unique_tmp_dir("recent-size");
+        let base = SystemTime::UNIX_EPOCH;
+        let entries: Vec<TryEntry> = [("small", 10, 30), ("big", 100, 20), ("oldest", 1000, 10)]
+            .iter()
+            .map(|&(name, bytes, secs)| {
+                let path = dir.join(name);
+                fs::create_dir_all(&path).unwrap();
+                fs::write(path.join("f"), vec![0u8; bytes]).unwrap();
+                let mut e = entry(name, base + Duration::from_secs(secs));
+                e.path = path;
+                e
+            })
+            .collect();
+        let recent = recent_entries(entries, 2, RecentSort::Size, &[]);
+        let names: Vec<&str> = recent.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["big", "small"]);
+    }
+
+    #[test]
+    fn format_recent_name_and_json() {
+        let entries = vec![entry(
+            "proj-2025-01-01",
+            SystemTime::UNIX_EPOCH + Duration::from_secs(42),
+        )];
+        assert_eq!(
+            format_recent(&entries, RecentFormat::Name),
+            "proj-2025-01-01\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format_recent(&entries, RecentFormat::Json)).unwrap();
+        assert_eq!(json[0]["name"], "proj-2025-01-01");
+        assert_eq!(json[0]["mtime"], 42);
+    }
+
+    #[test]
+    fn files_are_refused_as_workspaces_and_skipped_in_history() {
+        let dir = unique_tmp_dir("not-a-dir");
+        let cfg = dir.join("workspaces");
+        let file = dir.join("hosts");
+        fs::write(&file, "127.0.0.1 localhost\n").unwrap();
+        let ws = dir.join("ws");
+        fs::create_dir_all(&ws).unwrap();
+
+        let err = WorkspaceManager::add_workspace_to(&cfg, &file, None).unwrap_err();
+        assert!(matches!(err, TryError::NotADirectory(ref p) if *p == file));
+        WorkspaceManager::add_workspace_to(&cfg, &ws, None).unwrap();
+        // A history written by an older try can still hold a file.
+        let mut records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        records.push(WorkspaceRecord {
+            path: file.clone(),
+            ..WorkspaceRecord::default()
+        });
+
+        let mut sel = TrySelector::new(SelectorMode::History(records), String::new(), dir.clone());
+        sel.load_entries().unwrap();
+        let paths: Vec<&Path> = sel.entries.iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(paths, [ws.as_path()]);
+        assert!(sel.status.as_deref().unwrap().contains("not a directory"));
+
+        let mut sel =
+            TrySelector::new(SelectorMode::Scan(file.clone()), String::new(), dir.clone());
+        sel.load_entries().unwrap();
+        assert!(sel.entries.is_empty());
+        assert!(sel.status.is_some());
+        // The interactive selector's streaming scan skips it too.
+        let mut sel = TrySelector::new(SelectorMode::Scan(file), String::new(), dir);
+        sel.start_loading().unwrap();
+        assert!(sel.scan.is_none() && sel.entries.is_empty());
+        assert!(sel.status.as_deref().unwrap().contains("not a directory"));
+    }
+
+    #[test]
+    fn descriptions_are_searched_after_names_and_shown_dim() {
+        let dir = unique_tmp_dir("descriptions");
+        for name in ["k8s-2024-01-01", "kube-notes", "misc"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        fs::write(
+            dir.join("k8s-2024-01-01").join(DESCRIPTION_FILE),
+            "\n  kubernetes operator spike  \nmore notes\n",
+        )
+        .unwrap();
+        fs::write(dir.join("misc").join(DESCRIPTION_FILE), "kubectl plugin").unwrap();
+
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir);
+        sel.load_entries().unwrap();
+        sel.width = 80;
+        sel.height = 12;
+        let k8s = sel
+            .entries
+            .iter()
+            .find(|e| e.basename.starts_with("k8s"))
+            .unwrap();
+        assert_eq!(
+            k8s.description.as_deref(),
+            Some("kubernetes operator spike")
+        );
+
+        // `kube` is in one name and two descriptions; the name match wins.
+        press(&mut sel, &type_keys("kube"));
+        let names: Vec<&str> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.basename.as_str())
+            .collect();
+        assert_eq!(names[0], "kube-notes");
+        assert_eq!(names.len(), 3);
+
+        press(&mut sel, &type_keys("rnetes"));
+        let names: Vec<&str> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.basename.as_str())
+            .collect();
+        assert_eq!(names, ["k8s-2024-01-01"]);
+        assert!(render_plain(&mut sel).contains("k8s-2024-01-01  kubernetes operator spike"));
+    }
+
+    #[test]
+    fn scan_entries_skips_hidden_and_files() {
+        let dir = unique_tmp_dir("scan");
+        fs::create_dir_all(dir.join("visible")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(dir.join("file.txt"), "x").unwrap();
+        let entries = scan_entries(&dir).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].basename, "visible");
+    }
+
+    #[test]
+    fn tryignore_globs_match_names() {
+        let ignore =
+            TryIgnore::parse("# build junk\n\nnode_modules/\n*-scratch\ntmp?\n[!a]old\na[b\n");
+        for name in ["node_modules", "x-scratch", "tmp1", "bold", "a[b"] {
+            assert!(ignore.matches(name), "{}", name);
+        }
+        for name in ["node", "scratch", "tmp12", "aold", "# build junk"] {
+            assert!(!ignore.matches(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn scan_entries_respects_tryignore() {
+        let dir = unique_tmp_dir("tryignore");
+        for name in ["keep", "cache-2024-01-01", "vendor"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        fs::write(dir.join(TRY_IGNORE_FILE), "cache-*\nvendor\n").unwrap();
+        let names: Vec<String> = scan_entries(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.basename)
+            .collect();
+        assert_eq!(names, ["keep"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_entries_records_symlink_targets() {
+        let dir = unique_tmp_dir("scan-links");
+        let real = dir.join("real");
+        fs::create_dir_all(&real).unwrap();
+        std::os::unix::fs::symlink(&real, dir.join("alias")).unwrap();
+        // Dangling entry links are skipped rather than failing the scan.
+        std::os::unix::fs::symlink(dir.join("gone"), dir.join("broken")).unwrap();
+
+        let mut entries = scan_entries(&dir).unwrap();
+        entries.sort_by(|a, b| a.basename.cmp(&b.basename));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].basename, "alias");
+        assert_eq!(entries[0].link_target.as_deref(), Some(real.as_path()));
+        assert_eq!(entries[1].link_target, None);
+    }
+
+    #[test]
+    fn sort_modes_order_entries() {
+        let mut sel = snapshot_selector("sort-modes", &["beta", "alpha", "gamma"], "");
+        let mtimes = [hours_ago(5), hours_ago(1), hours_ago(9)];
+        for (e, t) in sel.entries.iter_mut().zip(mtimes) {
+            e.mtime = t;
+        }
+        let names = |sel: &TrySelector| -> Vec<String> {
+            sel.entries.iter().map(|e| e.basename.clone()).collect()
+        };
+
+        sel.sort_mode = SortMode::Mtime;
+        sel.refresh_scores();
+        assert_eq!(names(&sel), ["alpha", "beta", "gamma"]);
+        sel.sort_mode = SortMode::Name;
+        sel.refresh_scores();
+        assert_eq!(names(&sel), ["alpha", "beta", "gamma"]);
+        sel.entries[0].mtime = hours_ago(20);
+        sel.sort_mode = SortMode::Mtime;
+        sel.refresh_scores();
+        assert_eq!(names(&sel), ["beta", "gamma", "alpha"]);
+        assert!(render_plain(&mut sel).contains("· by mtime"));
+    }
+
+    #[test]
+    fn date_bucket_uses_suffix_then_mtime() {
+        use chrono::TimeZone;
+        // A Wednesday: Monday the 13th is still "this week".
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let bucket = |name: &str| DateBucket::of(&entry(name, SystemTime::UNIX_EPOCH), today);
+        assert_eq!(bucket("a-2024-05-15"), DateBucket::Today);
+        assert_eq!(bucket("a-2024-05-14"), DateBucket::Yesterday);
+        assert_eq!(bucket("a-2024-05-13"), DateBucket::ThisWeek);
+        assert_eq!(bucket("a-2024-05-12"), DateBucket::Older);
+        assert_eq!(bucket("plain"), DateBucket::Older);
+
+        let noon = Local.with_ymd_and_hms(2024, 5, 14, 12, 0, 0).unwrap();
+        let mut e = entry("plain", SystemTime::from(noon));
+        assert_eq!(DateBucket::of(&e, today), DateBucket::Yesterday);
+        e.pinned = true;
+        assert_eq!(DateBucket::of(&e, today), DateBucket::Pinned);
+    }
+
+    #[test]
+    fn date_bound_accepts_dates_and_relative_days() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        assert_eq!(parse_date_bound_at("2024-05-01", today), Ok(day(1)));
+        assert_eq!(parse_date_bound_at("7d", today), Ok(day(8)));
+        assert_eq!(parse_date_bound_at("0d", today), Ok(today));
+        assert_eq!(parse_date_bound_at("2w", today), Ok(day(1)));
+        for bad in [
+            "",
+            "d",
+            "7",
+            "7y",
+            "-1d",
+            "2024-13-01",
+            "é",
+            "7é",
+            "日d",
+            "3週",
+        ] {
+            assert!(parse_date_bound_at(bad, today).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn date_range_uses_suffix_then_mtime() {
+        use chrono::TimeZone;
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d);
+        let range = DateRange {
+            since: day(10),
+            until: day(12),
+        };
+        let at = |d| SystemTime::from(Local.with_ymd_and_hms(2024, 5, d, 12, 0, 0).unwrap());
+        assert!(range.contains(&entry("a-2024-05-10", at(1))));
+        assert!(!range.contains(&entry("a-2024-05-13", at(11))));
+        assert!(range.contains(&entry("plain", at(12))));
+        assert!(!range.contains(&entry("plain", at(9))));
+        // No date at all: only kept without a range.
+        let undated = entry("plain", SystemTime::UNIX_EPOCH);
+        assert!(!range.contains(&undated));
+        assert!(DateRange::default().contains(&undated));
+    }
+
+    #[test]
+    fn load_entries_drops_entries_outside_date_range() {
+        let dir = unique_tmp_dir("date-range");
+        for name in ["old-2020-01-01", "new-2024-05-14"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir);
+        sel.date_range.since = NaiveDate::from_ymd_opt(2024, 1, 1);
+        sel.load_entries().unwrap();
+        let names: Vec<_> = sel.entries.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["new-2024-05-14"]);
+    }
+
+    #[test]
+    fn date_headers_take_lines_but_not_cursor_positions() {
+        let (new, also) = (
+            format!("new-{}", today_suffix()),
+            format!("also-{}", today_suffix()),
+        );
+        let mut sel = snapshot_selector("date-headers", &[&new, "old", &also], "");
+        sel.date_headers = true;
+        sel.refresh_scores();
+        assert_eq!(
+            sel.list_rows(0, &sel.get_filtered_entries()),
+            [
+                ListRow::Header(DateBucket::Today),
+                ListRow::Item(0),
+                ListRow::Item(1),
+                ListRow::Header(DateBucket::Older),
+                ListRow::Item(2),
+            ]
+        );
+        assert_eq!(sel.visible_count(), 3);
+        assert_eq!(sel.entries[2].basename, "old");
+
+        assert!(render_plain(&mut sel).contains("── Today\n→ "));
+        sel.cursor_down();
+        sel.cursor_down();
+        assert_eq!(sel.selected_path(), Some(sel.entries[2].path.clone()));
+        let out = render_plain(&mut sel);
+        assert!(out.contains("── Older\n→ "), "{}", out);
+    }
+
+    #[test]
+    fn restored_query_is_replaced_cleared_or_kept() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let restored = || {
+            let mut sel = snapshot_selector("restore-query", &["a"], "proj");
+            sel.query_restored = true;
+            sel
+        };
+
+        let mut sel = restored();
+        assert!(sel.take_restored_query(&key(KeyCode::Char('x'))));
+        assert_eq!(sel.input_buffer, "");
+        assert!(!sel.take_restored_query(&key(KeyCode::Char('x'))));
+
+        let mut sel = restored();
+        sel.take_restored_query(&key(KeyCode::Backspace));
+        assert_eq!(sel.input_buffer, "");
+
+        let mut sel = restored();
+        sel.take_restored_query(&key(KeyCode::Down));
+        assert_eq!(sel.input_buffer, "proj");
+        assert!(!sel.query_restored);
+    }
+
+    #[test]
+    fn last_query_roundtrips_through_state_file() {
+        let dir = unique_tmp_dir("last-query");
+        let state_path = dir.join("state.toml");
+        TryState::remember_query(&state_path, Some("proj")).unwrap();
+        let state = TryState::load_from(&state_path).unwrap();
+        assert_eq!(state.last_query.as_deref(), Some("proj"));
+        TryState::remember_query(&state_path, None).unwrap();
+        assert_eq!(TryState::load_from(&state_path).unwrap().last_query, None);
+    }
+
+    #[test]
+    fn sort_mode_is_remembered_per_base_path() {
+        let dir = unique_tmp_dir("sort-state");
+        let state_path = dir.join("state.toml");
+        let mut sel = snapshot_selector("sort-state-base", &["a"], "");
+        sel.state_path = Some(state_path.clone());
+        sel.cycle_sort_mode();
+        sel.cycle_sort_mode();
+        assert_eq!(sel.sort_mode, SortMode::Name);
+
+        let SelectorMode::Scan(base) = &sel.mode else {
+            unreachable!()
+        };
+        let state = TryState::load_from(&state_path).unwrap();
+        assert_eq!(state.sort_for(base), Some(SortMode::Name));
+        assert_eq!(state.sort_for(&dir), None);
+    }
+
+    #[test]
+    fn pinned_entries_stay_on_top_and_persist() {
+        let dir = unique_tmp_dir("pins");
+        let state_path = dir.join("state.toml");
+        let mut sel = snapshot_selector("pins-base", &["fresh", "old"], "");
+        sel.state_path = Some(state_path.clone());
+        sel.entries[0].mtime = hours_ago(1);
+        sel.entries[1].mtime = hours_ago(5000);
+        sel.refresh_scores();
+        assert_eq!(sel.entries[0].basename, "fresh");
+
+        sel.cursor_pos = 1;
+        sel.toggle_pin();
+        sel.refresh_scores();
+        assert_eq!(sel.entries[0].basename, "old");
+        assert!(render_plain(&mut sel).contains("📌 old"));
+        sel.sort_mode = SortMode::Name;
+        sel.input_buffer = "f".to_string();
+        sel.refresh_scores();
+        // Filtered out by the query despite the pin.
+        assert_eq!(sel.get_filtered_entries()[0].basename, "fresh");
+
+        let pinned_path = sel.entries.iter().find(|e| e.pinned).unwrap().path.clone();
+        let state = TryState::load_from(&state_path).unwrap();
+        assert!(state.pins.contains(pinned_path.to_string_lossy().as_ref()));
+
+        sel.input_buffer.clear();
+        sel.refresh_scores();
+        sel.cursor_pos = 0;
+        sel.toggle_pin();
+        assert!(TryState::load_from(&state_path).unwrap().pins.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pin_follows_a_moved_directory() {
+        let dir = unique_tmp_dir("moved-pin");
+        let state_path = dir.join("state.toml");
+        let mut sel = snapshot_selector("moved-pin-base", &["before"], "");
+        sel.state_path = Some(state_path.clone());
+        sel.load_entries().unwrap();
+        sel.refresh_scores();
+        sel.cursor_pos = 0;
+        sel.toggle_pin();
+        let old = sel.entries[0].path.clone();
+        let new = old.with_file_name("after");
+        fs::rename(&old, &new).unwrap();
+
+        sel.load_entries().unwrap();
+        assert!(sel.entries.iter().any(|e| e.path == new && e.pinned));
+        let state = TryState::load_from(&state_path).unwrap();
+        assert!(state.pins.contains(new.to_string_lossy().as_ref()));
+        assert!(!state.pins.contains(old.to_string_lossy().as_ref()));
+        assert_eq!(state.ids.len(), 1);
+        let renamed = read_events(&dir.join(EVENTS_FILE));
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(renamed[0].kind, EventKind::Renamed);
+        assert_eq!(renamed[0].from.as_deref(), Some(old.as_path()));
+        assert_eq!(renamed[0].path, new);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn visits_and_history_follow_a_moved_directory() {
+        let dir = unique_tmp_dir("moved-visit");
+        let state_path = dir.join("state.toml");
+        let history_path = dir.join("workspaces");
+        let mut sel = snapshot_selector("moved-visit-base", &["before"], "");
+        let old = sel.entries[0].path.clone();
+        let new = old.with_file_name("after");
+        WorkspaceManager::add_workspace_to(&history_path, &old, None).unwrap();
+        sel.state_path = Some(state_path.clone());
+        sel.history_path = Some(history_path.clone());
+        sel.visits.insert(old.to_string_lossy().to_string(), 7);
+        sel.load_entries().unwrap();
+        fs::rename(&old, &new).unwrap();
+
+        sel.load_entries().unwrap();
+        let state = TryState::load_from(&state_path).unwrap();
+        assert_eq!(state.visits.get(new.to_string_lossy().as_ref()), Some(&7));
+        assert!(!state.visits.contains_key(old.to_string_lossy().as_ref()));
+        assert_eq!(
+            WorkspaceManager::get_workspaces_from(&history_path).unwrap(),
+            vec![new]
+        );
+    }
+
+    #[test]
+    fn identity_whose_old_path_still_exists_is_not_migrated() {
+        let mut sel = snapshot_selector("reused-id", &["old", "new"], "");
+        let old = sel.entries[0].path.to_string_lossy().to_string();
+        sel.pins.insert(old.clone());
+        sel.ids.insert("1:42".to_string(), old.clone());
+        let mut reused = sel.entries[1].clone();
+        reused.file_id = Some("1:42".to_string());
+
+        assert!(!sel.track_identity(&reused));
+        assert!(sel.pins.contains(&old));
+        assert_eq!(sel.ids["1:42"], old);
+    }
+
+    #[test]
+    fn permanent_create_skips_date_suffix_and_is_remembered() {
+        let dir = unique_tmp_dir("permanent");
+        let state_path = dir.join("state.toml");
+        let mut sel = snapshot_selector("permanent-base", &["alpha"], "notes");
+        sel.state_path = Some(state_path.clone());
+        sel.refresh_scores();
+        sel.cursor_pos = sel.get_filtered_entries().len();
+        let SelectorMode::Scan(base) = &sel.mode else {
+            unreachable!()
+        };
+        let base = base.clone();
+
+        let Some(ShellAction::MkdirCd(dated)) = sel.handle_selection(false) else {
+            panic!("expected MkdirCd");
+        };
+        assert_eq!(dated, base.join(format!("notes-{}", today_suffix())));
+        let Some(ShellAction::MkdirCd(path)) = sel.handle_selection(true) else {
+            panic!("expected MkdirCd");
+        };
+        assert_eq!(path, base.join("notes"));
+
+        sel.remember_permanent(&path);
+        fs::create_dir_all(&path).unwrap();
+        let state = TryState::load_from(&state_path).unwrap();
+        assert!(state.permanent.contains(path.to_string_lossy().as_ref()));
+
+        sel.input_buffer.clear();
+        sel.load_entries().unwrap();
+        sel.refresh_scores();
+        assert!(sel
+            .entries
+            .iter()
+            .any(|e| e.basename == "notes" && e.permanent));
+        assert!(render_plain(&mut sel).contains("♾  notes"));
+    }
+
+    #[test]
+    fn navigation_only_selector_hides_create_and_delete() {
+        let mut sel = snapshot_selector("nav-only", &["alpha"], "al");
+        sel.refresh_scores();
+        assert_eq!(sel.visible_count(), 2);
+
+        sel.allow_create = false;
+        sel.allow_delete = false;
+        assert_eq!(sel.visible_count(), 1);
+        sel.cursor_pos = 1;
+        assert!(sel.handle_selection(false).is_none());
+
+        sel.cursor_pos = 0;
+        sel.toggle_delete_mark();
+        assert!(!sel.delete_mode && sel.marked_for_deletion.is_empty());
+
+        let out = render_plain(&mut sel);
+        assert!(!out.contains("Create new"), "{}", out);
+        assert!(!out.contains("Del:"), "{}", out);
+    }
+
+    fn grouped_history(dir: &Path) -> TrySelector {
+        let records = [("a", Some("work")), ("b", Some("home")), ("c", None)]
+            .iter()
+            .map(|(n, g)| WorkspaceRecord {
+                path: dir.join(n),
+                group: g.map(String::from),
+                ..Default::default()
+            })
+            .collect();
+        let mut sel = TrySelector::new(
+            SelectorMode::History(records),
+            String::new(),
+            dir.to_path_buf(),
+        );
+        sel.load_entries().unwrap();
+        sel
+    }
+
+    #[test]
+    fn history_lists_groups_before_entries() {
+        let dir = unique_tmp_dir("groups-rows");
+        let mut sel = grouped_history(&dir);
+        assert_eq!(sel.group_rows(), vec!["home", "work"]);
+        assert_eq!(sel.visible_count(), 5);
+        assert_eq!(sel.selected_group().as_deref(), Some("home"));
+        assert!(sel.handle_selection(false).is_none());
+
+        // The first workspace row follows the two group rows.
+        sel.cursor_pos = 2;
+        let first = sel.entries[0].path.clone();
+        assert_eq!(sel.selected_path(), Some(first.clone()));
+        assert!(matches!(sel.handle_selection(false), Some(ShellAction::Set(p)) if p == first));
+    }
+
+    #[test]
+    fn at_prefix_filters_history_by_group() {
+        let dir = unique_tmp_dir("groups-query");
+        let mut sel = grouped_history(&dir);
+        sel.input_buffer = "@work ".to_string();
+        sel.refresh_scores();
+        assert!(sel.group_rows().is_empty());
+        assert_eq!(sel.active_group(), Some("work"));
+        assert_eq!(sel.search(), "");
+        let paths: Vec<_> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.path.clone())
+            .collect();
+        assert_eq!(paths, vec![dir.join("a")]);
+
+        sel.input_buffer = "@home zz".to_string();
+        sel.refresh_scores();
+        assert!(sel.get_filtered_entries().is_empty());
+    }
+
+    #[test]
+    fn session_group_hides_other_groups() {
+        let dir = unique_tmp_dir("groups-session");
+        let mut sel = grouped_history(&dir);
+        sel.group_filter = Some("home".to_string());
+        assert!(sel.group_rows().is_empty());
+        let paths: Vec<_> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.path.clone())
+            .collect();
+        assert_eq!(paths, vec![dir.join("b")]);
+        // An explicit `@group` query still reaches other groups.
+        sel.input_buffer = "@work".to_string();
+        assert_eq!(sel.get_filtered_entries().len(), 1);
+        assert_eq!(sel.get_filtered_entries()[0].path, dir.join("a"));
+    }
+
+    #[test]
+    fn groups_only_filter_history_mode() {
+        let mut sel = snapshot_selector("groups-scan", &["alpha", "beta"], "");
+        sel.group_filter = Some("work".to_string());
+        assert_eq!(sel.active_group(), None);
+        assert_eq!(sel.get_filtered_entries().len(), 2);
+        sel.input_buffer = "@work".to_string();
+        assert_eq!(sel.query_parts(), (None, "@work"));
+        assert_eq!(sel.active_group(), None);
+    }
+
+    #[test]
+    fn history_entries_record_whether_path_exists() {
+        let dir = unique_tmp_dir("verified");
+        let records = ["here", "gone"]
+            .iter()
+            .map(|n| WorkspaceRecord {
+                path: dir.join(n),
+                ..Default::default()
+            })
+            .collect();
+        fs::create_dir_all(dir.join("here")).unwrap();
+        let mut sel = TrySelector::new(SelectorMode::History(records), String::new(), dir);
+        sel.load_entries().unwrap();
+        let verified: Vec<_> = sel
+            .entries
+            .iter()
+            .map(|e| (e.path.ends_with("here"), e.verified))
+            .collect();
+        assert!(verified.iter().all(|(here, v)| here == v), "{:?}", verified);
+    }
+
+    #[test]
+    fn typing_after_scrolling_snaps_viewport_to_top() {
+        let names: Vec<String> = (0..20).map(|i| format!("proj-{:02}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut sel = snapshot_selector("scroll-type", &names, "");
+        for _ in 0..15 {
+            sel.cursor_down();
+        }
+        render_plain(&mut sel);
+        assert!(sel.scroll_offset > 0);
+
+        sel.input_buffer.push('p');
+        sel.reset_viewport();
+        sel.refresh_scores();
+        let out = render_plain(&mut sel);
+        assert_eq!((sel.cursor_pos, sel.scroll_offset), (0, 0));
+        let first_row = out.lines().find(|l| l.contains("proj-")).unwrap();
+        assert!(first_row.starts_with("→ "), "{}", out);
+    }
+
+    /// Feed `keys` to the selector as presses; returns the last step.
+    fn press(sel: &mut TrySelector, keys: &[KeyEvent]) -> Step {
+        let mut last = Step::Continue { redraw: false };
+        for key in keys {
+            last = sel.step(&Event::Key(*key)).unwrap();
+        }
+        last
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_keys(text: &str) -> Vec<KeyEvent> {
+        text.chars().map(|c| key(KeyCode::Char(c))).collect()
+    }
+
+    #[test]
+    fn step_typing_filters_and_enter_selects() {
+        let mut sel = snapshot_selector("step-select", &["alpha", "beta"], "");
+        sel.refresh_scores();
+        let step = press(&mut sel, &type_keys("bet"));
+        assert!(matches!(step, Step::Continue { redraw: true }));
+        assert_eq!(sel.visible_count(), 2); // beta + "Create new"
+        let step = press(&mut sel, &[key(KeyCode::Enter)]);
+        assert!(
+            matches!(&step, Step::Exit(Some(ShellAction::Cd(p))) if p.ends_with("beta")),
+            "{:?}",
+            step
+        );
+    }
+
+    #[test]
+    fn step_enter_on_create_row_makes_dated_dir() {
+        let mut sel = snapshot_selector("step-create", &["alpha"], "");
+        sel.refresh_scores();
+        let mut keys = type_keys("new idea");
+        keys.push(key(KeyCode::Enter));
+        let step = press(&mut sel, &keys);
+        let Step::Exit(Some(ShellAction::MkdirCd(path))) = step else {
+            panic!("{:?}", step);
+        };
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(name, format!("new-idea-{}", today_suffix()));
+    }
+
+    #[test]
+    fn create_row_counts_past_a_name_taken_today() {
+        let taken = format!("foo-{}", today_suffix());
+        let mut sel = snapshot_selector("step-create-taken", &[&taken], "");
+        sel.width = 60;
+        sel.refresh_scores();
+        let mut keys = type_keys("foo");
+        keys.push(key(KeyCode::Down));
+        press(&mut sel, &keys);
+        let second = format!("{}-2", taken);
+        // `render_plain` redacts today's date.
+        assert!(render_plain(&mut sel).contains("Create new: foo-YYYY-MM-DD-2"));
+        let step = press(&mut sel, &[key(KeyCode::Enter)]);
+        let Step::Exit(Some(ShellAction::MkdirCd(path))) = step else {
+            panic!("{:?}", step);
+        };
+        assert_eq!(path.file_name().unwrap().to_string_lossy(), second);
+
+        // The counter keeps the date readable.
+        let counted = entry(&second, SystemTime::UNIX_EPOCH);
+        assert_eq!(entry_date(&counted), Some(Local::now().date_naive()));
+        fs::create_dir_all(&path).unwrap();
+        assert_eq!(
+            dated_name(&path, "foo"),
+            format!("foo-{}-3", today_suffix())
+        );
+    }
+
+    #[test]
+    fn step_cursor_clamps_and_ignores_key_releases() {
+        let mut sel = snapshot_selector("step-cursor", &["a", "b", "c"], "");
+        sel.refresh_scores();
+        let step = press(&mut sel, &[key(KeyCode::Up)]);
+        assert!(matches!(step, Step::Continue { redraw: false }));
+        press(&mut sel, &[key(KeyCode::Down); 5]);
+        assert_eq!(sel.cursor_pos, 2);
+
+        let mut release = key(KeyCode::Enter);
+        release.kind = KeyEventKind::Release;
+        assert!(matches!(
+            press(&mut sel, &[release]),
+            Step::Continue { redraw: false }
+        ));
+    }
+
+    #[test]
+    fn create_new_accepts_paths_outside_the_base_after_confirming() {
+        let mut sel = snapshot_selector("create-elsewhere", &["alpha"], "~/elsewhere/my thing");
+        sel.refresh_scores();
+        sel.cursor_pos = sel.get_filtered_entries().len();
+        let home = dirs::home_dir().unwrap();
+        let Some(ShellAction::MkdirCd(path)) = sel.handle_selection(false) else {
+            panic!("expected MkdirCd");
+        };
+        assert_eq!(
+            path,
+            home.join("elsewhere")
+                .join(format!("my-thing-{}", today_suffix()))
+        );
+        let step = press(&mut sel, &[key(KeyCode::Enter)]);
+        assert!(matches!(step, Step::ConfirmCreate(ShellAction::MkdirCd(p)) if p == path));
+
+        // Relative paths still land under the base, without asking.
+        sel.input_buffer = "sub/thing".to_string();
+        sel.refresh_scores();
+        sel.cursor_pos = sel.get_filtered_entries().len();
+        let step = press(&mut sel, &[key(KeyCode::Enter)]);
+        assert!(matches!(step, Step::Exit(Some(ShellAction::MkdirCd(_)))));
+    }
+
+    #[test]
+    fn step_delete_mode_escape_and_confirm() {
+        let mut sel = snapshot_selector("step-delete", &["a", "b"], "");
+        sel.refresh_scores();
+        press(&mut sel, &[key(KeyCode::Delete)]);
+        assert!(sel.delete_mode);
+        assert!(matches!(
+            press(&mut sel, &[key(KeyCode::Enter)]),
+            Step::ConfirmDelete
+        ));
+
+        // Esc leaves delete mode first, then cancels the selector.
+        let step = press(&mut sel, &[key(KeyCode::Esc)]);
+        assert!(matches!(step, Step::Continue { redraw: true }));
+        assert!(!sel.delete_mode && sel.marked_for_deletion.is_empty());
+        assert!(matches!(
+            press(&mut sel, &[key(KeyCode::Esc)]),
+            Step::Exit(None)
+        ));
+    }
+
+    #[test]
+    fn delete_double_tap_flashes_before_marking() {
+        let mut sel = snapshot_selector("double-tap", &["a", "b"], "");
+        sel.delete_double_tap = true;
+        sel.refresh_scores();
+        let start = Instant::now();
+
+        assert!(!sel.delete_tap(start));
+        assert!(sel.flash_remaining(start).is_some());
+        assert!(sel.flash_remaining(start + DELETE_FLASH).is_none());
+        // Too slow: the second press only flashes again.
+        let late = start + DOUBLE_TAP_WINDOW + Duration::from_millis(1);
+        assert!(!sel.delete_tap(late));
+        assert!(sel.delete_tap(late + Duration::from_millis(100)));
+
+        // Through `step`, a quick double press enters delete mode.
+        sel.delete_armed = None;
+        press(&mut sel, &[key(KeyCode::Delete)]);
+        assert!(!sel.delete_mode);
+        press(&mut sel, &[key(KeyCode::Delete)]);
+        assert!(sel.delete_mode);
+        // Once in delete mode, single presses toggle marks.
+        press(&mut sel, &[key(KeyCode::Down), key(KeyCode::Delete)]);
+        assert_eq!(sel.marked_for_deletion.len(), 2);
+    }
+
+    #[test]
+    fn config_delete_confirm_required_defaults_on() {
+        assert!(TryConfig::parse("").unwrap().delete_double_tap());
+        let cfg = TryConfig::parse("delete_confirm_required = false").unwrap();
+        assert!(!cfg.delete_double_tap());
+    }
+
+    #[test]
+    fn headless_selector_reads_json_keys_and_writes_states() {
+        let dir = unique_tmp_dir("headless");
+        for name in ["alpha", "beta"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir.clone());
+        let input = concat!(
+            "{\"key\": \"b\"}\n",
+            "not json\n",
+            "\n",
+            "{\"key\": \"Down\"}\n",
+            "{\"key\": \"Up\"}\n",
+            "{\"key\": \"Enter\"}\n",
+        );
+        let mut out = Vec::new();
+        let action = sel.run_headless(input.as_bytes(), &mut out).unwrap();
+        assert!(matches!(&action, Some(ShellAction::Cd(p)) if p.ends_with("beta")));
+
+        let states: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(states.len(), 6);
+        assert_eq!(states[0]["rows"].as_array().unwrap().len(), 2);
+        assert_eq!(states[1]["query"], "b");
+        let rows = states[1]["rows"].as_array().unwrap();
+        assert_eq!(rows[0]["name"], "beta");
+        assert_eq!(rows.last().unwrap()["kind"], "create");
+        assert!(states[2]["status"]
+            .as_str()
+            .unwrap()
+            .starts_with("Bad event"));
+        assert_eq!(states[3]["cursor"], 1);
+        assert_eq!(states[5]["exit"]["verb"], "cd");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn headless_selector_survives_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = unique_tmp_dir("headless-bytes");
+        fs::create_dir_all(dir.join(std::ffi::OsStr::from_bytes(b"caf\xe9"))).unwrap();
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir.clone());
+        let mut out = Vec::new();
+        sel.run_headless("".as_bytes(), &mut out).unwrap();
+        let first = String::from_utf8(out).unwrap();
+        let state: serde_json::Value = serde_json::from_str(first.lines().next().unwrap()).unwrap();
+        assert!(state["rows"][0]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("caf\u{fffd}"));
+    }
+
+    #[test]
+    fn event_lines_parse_keys_modifiers_and_resizes() {
+        let Event::Key(k) = parse_event_line(r#"{"key": "s", "ctrl": true}"#).unwrap() else {
+            panic!("expected a key");
+        };
+        assert_eq!(
+            (k.code, k.modifiers),
+            (KeyCode::Char('s'), KeyModifiers::CONTROL)
+        );
+        assert!(matches!(
+            parse_event_line(r#"{"resize": [80, 24]}"#),
+            Ok(Event::Resize(80, 24))
+        ));
+        assert!(parse_event_line(r#"{"key": "PageUp"}"#).is_err());
+        assert!(parse_event_line(r#"{"keys": "a"}"#).is_err());
+    }
+
+    #[test]
+    fn ctrl_b_cycles_through_base_paths() {
+        let a = unique_tmp_dir("base-a");
+        let b = unique_tmp_dir("base-b");
+        fs::create_dir_all(a.join("in-a")).unwrap();
+        fs::create_dir_all(b.join("in-b")).unwrap();
+        let mut sel = TrySelector::new(SelectorMode::Scan(a.clone()), String::new(), a.clone());
+        sel.width = 60;
+        sel.height = 12;
+        let ctrl_b = KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL);
+
+        // A single base has nowhere to go.
+        sel.bases = vec![a.clone()];
+        press(&mut sel, &[ctrl_b]);
+        assert!(matches!(&sel.mode, SelectorMode::Scan(p) if *p == a));
+        assert!(sel.status.is_some());
+
+        sel.bases = vec![a.clone(), b.clone()];
+        sel.status = None;
+        press(&mut sel, &[ctrl_b]);
+        assert!(matches!(&sel.mode, SelectorMode::Scan(p) if *p == b));
+        assert_eq!(sel.workspace_path, b);
+        let names: Vec<&str> = sel.entries.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["in-b"]);
+
+        press(&mut sel, &[ctrl_b]);
+        assert_eq!(sel.workspace_path, a);
+        assert_eq!(sel.entries[0].basename, "in-a");
+    }
+
+    #[test]
+    fn concurrent_visits_are_all_kept() {
+        let dir = unique_tmp_dir("visits-race");
+        let state_path = dir.join("state.toml");
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let state_path = state_path.clone();
+                std::thread::spawn(move || {
+                    let path = PathBuf::from(format!("/w{}", i));
+                    TryState::remember_visit(&state_path, &path).unwrap();
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(TryState::load_from(&state_path).unwrap().visits.len(), 8);
+    }
+
+    #[test]
+    fn visits_order_entries_the_mtime_cannot_tell_apart() {
+        let dir = unique_tmp_dir("visits");
+        let state_path = dir.join("state.toml");
+        let mut sel = snapshot_selector("visits-sel", &["aaa-1", "bbb-1", "ccc-1"], "");
+        for e in &mut sel.entries {
+            e.mtime = hours_ago(1);
+        }
+        // Two visits in a row still get distinct, increasing stamps.
+        TryState::remember_visit(&state_path, &sel.entries[2].path).unwrap();
+        TryState::remember_visit(&state_path, &sel.entries[1].path).unwrap();
+        let visits = TryState::load_from(&state_path).unwrap().visits;
+        let stamp = |i: usize| visits[sel.entries[i].path.to_string_lossy().as_ref()];
+        assert!(stamp(1) > stamp(2));
+
+        sel.visits = visits.clone();
+        let mut entries = std::mem::take(&mut sel.entries);
+        for e in &mut entries {
+            sel.apply_state(e);
+        }
+        sel.entries = entries;
+        sel.refresh_scores();
+        let names: Vec<&str> = sel.entries.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["bbb-1", "ccc-1", "aaa-1"]);
+    }
+
+    #[test]
+    fn truncate_to_width_counts_display_columns() {
+        assert_eq!(truncate_to_width("short", 10), "short");
+        assert_eq!(truncate_to_width("exactly", 7), "exactly");
+        assert_eq!(truncate_to_width("workspaces", 5), "work…");
+        // Wide characters take two columns and are never split.
+        assert_eq!(truncate_to_width("项目笔记", 6), "项目…");
+        assert_eq!(truncate_to_width("项目笔记", 5), "项目…");
+        assert_eq!(truncate_to_width("项目笔记", 4), "项…");
+        assert_eq!(truncate_to_width("abc", 1), "…");
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+
+    #[test]
+    fn long_paths_and_names_never_wrap() {
+        let long = format!("{}-2025-01-01", "very-long-experiment-name".repeat(3));
+        let mut sel = snapshot_selector(
+            "no-wrap",
+            &[&long, "项目-笔记-实验-记录-草稿-备份-归档"],
+            "",
+        );
+        sel.workspace_path = PathBuf::from("/home/user/a/b/c/d/e/f/g/h/i/j/k/workspaces");
+        sel.sort_mode = SortMode::Name;
+        sel.entries[0].worktrees = Some(2);
+        sel.refresh_scores();
+        for width in [20, 40, 60] {
+            sel.width = width;
+            let text = render_plain(&mut sel);
+            for line in text.lines().filter(|l| !l.starts_with("↑↓")) {
+                assert!(line.width() < width as usize, "{:?} at {}", line, width);
+            }
+            if width >= 40 {
+                assert!(text.contains("📁 Try Selector @ /home/user"));
+                assert!(text.contains("very-long-experiment-name"));
+            }
+        }
+    }
+
+    #[test]
+    fn ctrl_g_shows_the_full_path_and_scrolls_it() {
+        let name = "a-rather-long-directory-name-2025-01-01";
+        let mut sel = snapshot_selector("full-path", &[name, "other"], "");
+        sel.refresh_scores();
+        let full = canonicalize_clean(&sel.entries[0].path)
+            .to_string_lossy()
+            .to_string();
+        let ctrl_g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL);
+
+        sel.width = (full.chars().count() + 10) as u16;
+        press(&mut sel, &[ctrl_g]);
+        assert_eq!(sel.path_detail().unwrap(), full);
+        assert!(render_plain(&mut sel).contains(&full));
+
+        sel.width = 30;
+        let head = sel.path_detail().unwrap();
+        assert_eq!(head.chars().count(), 29);
+        assert!(head.ends_with('…') && full.starts_with(head.trim_end_matches('…')));
+
+        press(&mut sel, &[key(KeyCode::Right); 100]);
+        let tail = sel.path_detail().unwrap();
+        assert_eq!(tail.chars().count(), 29);
+        assert!(tail.starts_with('…') && full.ends_with(tail.trim_start_matches('…')));
+        press(&mut sel, &[key(KeyCode::Left); 100]);
+        assert_eq!(sel.path_detail().unwrap(), head);
+
+        // Moving on starts the next path from its beginning.
+        press(&mut sel, &[key(KeyCode::Right), key(KeyCode::Down)]);
+        assert_eq!(sel.path_scroll, 0);
+        press(&mut sel, &[ctrl_g]);
+        let footer = render_plain(&mut sel).lines().last().unwrap().to_string();
+        assert!(!footer.contains('…'));
+    }
+
+    #[test]
+    fn ctrl_g_path_fits_wide_characters_by_column() {
+        let name = "项目笔记与实验代码仓库的一个相当长的名字-2025-01-01";
+        let mut sel = snapshot_selector("full-path-wide", &[name], "");
+        sel.refresh_scores();
+        let full = canonicalize_clean(&sel.entries[0].path)
+            .to_string_lossy()
+            .to_string();
+        let ctrl_g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL);
+        sel.width = 30;
+        press(&mut sel, &[ctrl_g]);
+
+        let head = sel.path_detail().unwrap();
+        assert!(head.width() <= 29 && head.width() >= 28);
+        assert!(head.ends_with('…') && full.starts_with(head.trim_end_matches('…')));
+
+        press(&mut sel, &[key(KeyCode::Right); 100]);
+        let tail = sel.path_detail().unwrap();
+        assert!(tail.width() <= 29 && tail.width() >= 28);
+        assert!(tail.starts_with('…') && full.ends_with(tail.trim_start_matches('…')));
+    }
+
+    #[test]
+    fn split_negations_keeps_plain_queries_as_typed() {
+        assert_eq!(
+            split_negations("rust  web"),
+            (vec!["rust  web".into()], vec![])
+        );
+        assert_eq!(split_negations(""), (vec![], vec![]));
+        assert_eq!(
+            split_negations("rust !2023 web !old"),
+            (
+                vec!["rust".into(), "web".into()],
+                vec!["2023".into(), "old".into()]
+            )
+        );
+        assert_eq!(split_negations("!"), (vec![], vec![]));
+    }
+
+    #[test]
+    fn bang_tokens_exclude_matching_entries() {
+        let names = ["rust-2023-01-01", "rust-2024-02-02", "go-2024-03-03"];
+        let mut sel = snapshot_selector("negation", &names, "");
+        press(&mut sel, &type_keys("rust !2023"));
+        let shown: Vec<_> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.basename.clone())
+            .collect();
+        assert_eq!(shown, ["rust-2024-02-02"]);
+        assert!(!sel.show_create_new());
+
+        sel.input_buffer = "!2023".into();
+        sel.refresh_scores();
+        assert_eq!(sel.get_filtered_entries().len(), 2);
+    }
+
+    #[test]
+    fn bang_queries_from_the_command_line_keep_their_tokens() {
+        let names = [
+            "rust-web-2023-01-01",
+            "rust-web-2024-02-02",
+            "rust-cli-2024-03-03",
+        ];
+        let mut sel = snapshot_selector("negation-cli", &names, "rust web !2023");
+        assert_eq!(sel.input_buffer, "rust web !2023");
+        sel.refresh_scores();
+        let shown: Vec<_> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.basename.clone())
+            .collect();
+        assert_eq!(shown, ["rust-web-2024-02-02"]);
+
+        // Tokens match in any order.
+        let mut sel = snapshot_selector("negation-cli-order", &names, "web rust !2024");
+        sel.refresh_scores();
+        assert_eq!(
+            sel.get_filtered_entries()[0].basename,
+            "rust-web-2023-01-01"
+        );
+        assert_eq!(sel.get_filtered_entries().len(), 1);
+    }
+
+    #[test]
+    fn ctrl_f_hides_entries_that_no_longer_exist() {
+        let mut sel = snapshot_selector("existing-only", &["kept", "gone"], "");
+        fs::remove_dir(&sel.entries[1].path).unwrap();
+        let ctrl_f = KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL);
+
+        press(&mut sel, &[ctrl_f]);
+        let names: Vec<&str> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.basename.as_str())
+            .collect();
+        assert_eq!(names, ["kept"]);
+        assert!(render_plain(&mut sel).contains("[existing only]"));
+
+        press(&mut sel, &[ctrl_f]);
+        assert_eq!(sel.get_filtered_entries().len(), 2);
+        assert_eq!(sel.entries.len(), 2);
+        assert!(!render_plain(&mut sel).contains("[existing only]"));
+    }
+
+    #[test]
+    fn ctrl_r_matches_paths_and_finds_nested_workspaces() {
+        let dir = unique_tmp_dir("match-paths");
+        for d in [
+            "client/idea-2025-01-01",
+            "client/src",
+            "client/notes",
+            "other-2025-02-01",
+        ] {
+            fs::create_dir_all(dir.join(d)).unwrap();
+        }
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir.clone());
+        sel.width = 60;
+        sel.height = 12;
+        sel.permanent
+            .insert(dir.join("client/notes").to_string_lossy().to_string());
+        sel.load_entries().unwrap();
+        press(&mut sel, &type_keys("cli/idea"));
+        assert!(sel.get_filtered_entries().is_empty());
+
+        let ctrl_r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        press(&mut sel, &[ctrl_r]);
+        let mut names: Vec<_> = sel.entries.iter().map(|e| e.basename.as_str()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "client",
+                "client/idea-2025-01-01",
+                "client/notes",
+                "other-2025-02-01"
+            ]
+        );
+        let shown = sel.get_filtered_entries();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].path, dir.join("client/idea-2025-01-01"));
+        let text = render_plain(&mut sel);
+        assert!(text.contains("client/idea-2025-01-01"));
+        assert!(text.contains("Matching paths"));
+
+        press(&mut sel, &[ctrl_r]);
+        assert_eq!(sel.entries.len(), 2);
+        assert!(!render_plain(&mut sel).contains("Matching paths"));
+    }
+
+    #[test]
+    fn step_scrolls_to_keep_cursor_visible() {
+        let names: Vec<String> = (0..20).map(|i| format!("proj-{:02}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut sel = snapshot_selector("step-scroll", &names, "");
+        sel.refresh_scores();
+        press(&mut sel, &[key(KeyCode::Down); 12]);
+        render_plain(&mut sel);
+        let max = sel.max_visible();
+        assert!(sel.scroll_offset <= sel.cursor_pos);
+        assert!(sel.cursor_pos < sel.scroll_offset + max);
+        press(&mut sel, &[key(KeyCode::Up); 12]);
+        render_plain(&mut sel);
+        assert_eq!((sel.cursor_pos, sel.scroll_offset), (0, 0));
+    }
+
+    #[test]
+    fn initial_query_dashes_spaces_only_in_scan_mode() {
+        let dir = unique_tmp_dir("initial-query");
+        let sel = TrySelector::new(
+            SelectorMode::Scan(dir.clone()),
+            "new idea".to_string(),
+            dir.clone(),
+        );
+        assert_eq!(sel.input_buffer, "new-idea");
+        let sel = TrySelector::new(
+            SelectorMode::History(Vec::new()),
+            "@work /my projects".to_string(),
+            dir,
+        );
+        assert_eq!(sel.input_buffer, "@work /my projects");
+        assert_eq!(sel.active_group(), Some("work"));
+    }
+
+    #[test]
+    fn selected_path_skips_create_new_row() {
+        let mut sel = snapshot_selector("selected-path", &["alpha"], "al");
+        sel.refresh_scores();
+        assert!(sel.selected_path().unwrap().ends_with("alpha"));
+        sel.cursor_pos = 1;
+        assert_eq!(sel.selected_path(), None);
+        sel.copy_selected_path();
+        assert_eq!(sel.status.as_deref(), Some("Nothing to copy."));
+    }
+
+    #[test]
+    fn copy_falls_back_to_a_temp_file() {
+        let mut sel = snapshot_selector("copy-file", &["alpha"], "");
+        let dir = unique_tmp_dir("copy-file-state");
+        sel.state_path = Some(dir.join("state.toml"));
+        sel.copy_method = CopyMethod::File;
+        sel.copy_selected_path();
+        let file = dir.join("copied-path");
+        assert_eq!(
+            sel.status.as_deref().unwrap(),
+            format!("Saved to {} (copy_method is \"file\")", file.display())
+        );
+        assert_eq!(
+            PathBuf::from(fs::read_to_string(file).unwrap()),
+            canonicalize_clean(&sel.entries[0].path)
+        );
+    }
+
+    #[test]
+    fn clipboard_command_matches_the_session() {
+        let have = |tools: &'static [&'static str]| move |p: &str| tools.contains(&p);
+        assert_eq!(
+            clipboard_command(true, true, &have(&["wl-copy", "xclip"])),
+            Some(vec!["wl-copy"])
+        );
+        assert_eq!(
+            clipboard_command(false, true, &have(&["wl-copy", "xsel"])),
+            Some(vec!["xsel", "--clipboard", "--input"])
+        );
+        assert_eq!(clipboard_command(false, false, &have(&["xclip"])), None);
+    }
+
+    #[test]
+    fn osc52_encodes_base64_and_skips_dumb_terminals() {
+        assert_eq!(osc52_sequence("foo"), "\x1b]52;c;Zm9v\x07");
+        assert_eq!(osc52_sequence("fooba"), "\x1b]52;c;Zm9vYmE=\x07");
+        assert_eq!(osc52_sequence("/a"), "\x1b]52;c;L2E=\x07");
+        assert!(osc52_likely(Some("xterm-256color"), None));
+        assert!(!osc52_likely(Some("linux"), None));
+        assert!(!osc52_likely(None, None));
+        assert!(!osc52_likely(
+            Some("xterm-256color"),
+            Some("Apple_Terminal")
+        ));
+    }
+
+    #[test]
+    fn config_parses_copy_method() {
+        assert_eq!(TryConfig::parse("").unwrap().copy_method, CopyMethod::Auto);
+        let cfg = TryConfig::parse("copy_method = \"osc52\"").unwrap();
+        assert_eq!(cfg.copy_method, CopyMethod::Osc52);
+        assert!(TryConfig::parse("copy_method = \"telepathy\"").is_err());
+    }
+
+    #[test]
+    fn auto_accept_single_exits_on_the_sole_match() {
+        let mut sel = snapshot_selector("auto-accept", &["rust-cli", "ruby-gem"], "");
+        sel.auto_accept_single = true;
+        sel.refresh_scores();
+        let step = press(&mut sel, &type_keys("ru"));
+        assert!(matches!(step, Step::Continue { .. }));
+        let step = press(&mut sel, &type_keys("s"));
+        assert!(
+            matches!(&step, Step::Exit(Some(ShellAction::Cd(p))) if p.ends_with("rust-cli")),
+            "{:?}",
+            step
+        );
+
+        // Off by default: the same keys just filter.
+        let mut sel = snapshot_selector("auto-accept-off", &["rust-cli", "ruby-gem"], "");
+        sel.refresh_scores();
+        let step = press(&mut sel, &type_keys("rus"));
+        assert!(matches!(step, Step::Continue { .. }));
+    }
+
+    #[test]
+    fn config_sort_is_the_starting_order() {
+        assert_eq!(TryConfig::parse("").unwrap().sort, SortMode::Score);
+        let cfg = TryConfig::parse("sort = \"mtime\"").unwrap();
+        assert_eq!(cfg.sort, SortMode::Mtime);
+        assert!(TryConfig::parse("sort = \"size\"").is_err());
+    }
+
+    #[test]
+    fn selecting_externally_deleted_entry_is_detected() {
+        let mut sel = snapshot_selector("stale", &["keep", "doomed"], "");
+        sel.cursor_pos = 1;
+        assert_eq!(sel.selected_missing_path(), None);
+        let doomed = sel.entries[1].path.clone();
+        fs::remove_dir_all(&doomed).unwrap();
+        assert_eq!(sel.selected_missing_path(), Some(doomed));
+        sel.cursor_pos = 0;
+        assert_eq!(sel.selected_missing_path(), None);
+    }
+
+    #[test]
+    fn background_scan_streams_all_entries() {
+        let dir = unique_tmp_dir("scan-stream");
+        for i in 0..25 {
+            fs::create_dir_all(dir.join(format!("proj-{:02}", i))).unwrap();
+        }
+        fs::write(dir.join("proj-03/Cargo.toml"), "").unwrap();
+        let mut sel = TrySelector::new(SelectorMode::Scan(dir.clone()), String::new(), dir);
+        sel.width = 40;
+        sel.height = 12;
+        sel.start_loading().unwrap();
+        assert!(render_plain(&mut sel).contains("⠋ Scanning…"));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while sel.scan.is_some() {
+            assert!(Instant::now() < deadline, "scan never finished");
+            sel.poll_scan(Instant::now());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(sel.entries.len(), 25);
+        let rust = sel
+            .entries
+            .iter()
+            .find(|e| e.basename == "proj-03")
+            .unwrap();
+        assert_eq!(rust.icon.as_deref(), Some("🦀"));
+        // The worker's icon cache came back with it.
+        assert_eq!(sel.icons.cache.len(), 25);
+        assert!(!render_plain(&mut sel).contains("Scanning"));
+    }
+
+    #[test]
+    fn project_icons_detect_markers_with_overrides() {
+        let dir = unique_tmp_dir("icons");
+        fs::create_dir_all(dir.join("rust")).unwrap();
+        fs::write(dir.join("rust/Cargo.toml"), "").unwrap();
+        fs::create_dir_all(dir.join("deno")).unwrap();
+        fs::write(dir.join("deno/deno.json"), "").unwrap();
+        fs::write(dir.join("deno/package.json"), "").unwrap();
+        fs::create_dir_all(dir.join("plain")).unwrap();
+
+        let overrides = HashMap::from([
+            ("deno.json".to_string(), "🦕".to_string()),
+            ("Cargo.toml".to_string(), "R".to_string()),
+        ]);
+        let mut icons = ProjectIcons::new(Some(&overrides));
+        let t = SystemTime::UNIX_EPOCH;
+        assert_eq!(icons.icon_for(&dir.join("rust"), t).as_deref(), Some("R"));
+        assert_eq!(icons.icon_for(&dir.join("deno"), t).as_deref(), Some("🦕"));
+        assert_eq!(icons.icon_for(&dir.join("plain"), t), None);
+
+        // Cached until the directory's mtime changes.
+        fs::write(dir.join("plain/go.mod"), "").unwrap();
+        assert_eq!(icons.icon_for(&dir.join("plain"), t), None);
+        let later = t + Duration::from_secs(1);
+        assert_eq!(
+            icons.icon_for(&dir.join("plain"), later).as_deref(),
+            Some("🐹")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_base_symlink_is_unavailable() {
+        let dir = unique_tmp_dir("base-dangling");
+        let base = dir.join("tries");
+        std::os::unix::fs::symlink(dir.join("unmounted"), &base).unwrap();
+        let err = check_base_available(&base).unwrap_err();
+        assert!(matches!(err, TryError::WorkspaceUnavailable { ref path, .. } if *path == base));
+        assert!(err.to_string().contains("unmounted"));
+
+        std::os::unix::fs::symlink(&dir, dir.join("ok")).unwrap();
+        check_base_available(&dir.join("ok")).unwrap();
+        check_base_available(&dir.join("not-yet-created")).unwrap();
+    }
+
+    #[test]
+    fn shell_parse_known_names() {
+        assert_eq!(Shell::parse("bash"), Some(Shell::Bash));
+        assert_eq!(Shell::parse("ZSH"), Some(Shell::Bash));
+        assert_eq!(Shell::parse("fish"), Some(Shell::Fish));
+        assert_eq!(Shell::parse("powershell"), Some(Shell::PowerShell));
+        assert_eq!(Shell::parse("pwsh"), Some(Shell::PowerShell));
+        assert_eq!(Shell::parse("cmd"), None);
+    }
+
+    #[test]
+    fn detect_prefers_posix_shell_env() {
+        let s = Shell::detect_from(|k| match k {
+            "SHELL" => Some("/bin/bash".to_string()),
+            "PSModulePath" => Some("C:/foo".to_string()),
+            _ => None,
+        });
+        assert_eq!(s, Shell::Bash);
+    }
+
+    #[test]
+    fn init_detects_the_shell_running_it_first() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |k: &str| {
+                pairs
+                    .iter()
+                    .find(|(key, _)| *key == k)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        let login_bash = env(&[("SHELL", "/bin/bash")]);
+        let (shell, origin, warning) = Shell::detect_for_init(Some("fish"), login_bash);
+        assert_eq!(
+            (shell, origin.as_str()),
+            (Shell::Fish, "the parent process is fish")
+        );
+        assert!(warning.is_none());
+
+        // A login shell's `-zsh`, or a parent that isn't a shell at all.
+        let (shell, _, _) = Shell::detect_for_init(Some("-zsh"), env(&[]));
+        assert_eq!(shell, Shell::Bash);
+        let (shell, origin, _) =
+            Shell::detect_for_init(Some("tmux: server"), env(&[("SHELL", "/usr/bin/fish")]));
+        assert_eq!((shell, origin.as_str()), (Shell::Fish, "$SHELL is fish"));
+        let (shell, _, _) = Shell::detect_for_init(Some("pwsh.exe"), env(&[]));
+        assert_eq!(shell, Shell::PowerShell);
+        let (shell, origin, _) = Shell::detect_for_init(
+            Some("bash"),
+            env(&[("TRY_SHELL", "fish"), ("SHELL", "/bin/bash")]),
+        );
+        assert_eq!((shell, origin.as_str()), (Shell::Fish, "TRY_SHELL"));
+
+        let (shell, _, warning) = Shell::detect_for_init(Some("nu"), env(&[]));
+        assert_eq!(shell, Shell::Bash);
+        assert!(warning.unwrap().contains("nushell"));
+        let (_, origin, warning) = Shell::detect_for_init(None, env(&[]));
+        assert_eq!(origin, "no shell detected");
+        assert!(warning.unwrap().contains("--shell"));
+    }
+
+    #[test]
+    fn init_install_uses_the_detected_shells_rc_file() {
+        let home = Path::new("/home/me");
+        let login_bash = |k: &str| (k == "SHELL").then(|| "/bin/bash".to_string());
+        let rc_for = |parent: Option<&str>| {
+            let (shell, _, _) = Shell::detect_for_init(parent, login_bash);
+            let program = shell.program_for_init(parent, login_bash);
+            shell_rc_file(shell, program.as_deref(), home, None)
+        };
+        // fish started from a bash login must not touch .bashrc.
+        assert_eq!(rc_for(Some("fish")), home.join(".config/fish/config.fish"));
+        assert_eq!(rc_for(Some("-zsh")), home.join(".zshrc"));
+        assert_eq!(rc_for(Some("tmux: server")), home.join(".bashrc"));
+    }
+
+    #[test]
+    fn detect_fish_from_shell_env() {
+        let s = Shell::detect_from(|k| match k {
+            "SHELL" => Some("/usr/local/bin/fish".to_string()),
+            _ => None,
+        });
+        assert_eq!(s, Shell::Fish);
+    }
 
-                stderr.queue(SetAttribute(Attribute::Reset))?;
+    #[test]
+    fn detect_powershell_when_only_psmodulepath() {
+        let s = Shell::detect_from(|k| match k {
+            "PSModulePath" => Some("C:/foo".to_string()),
+            _ => None,
+        });
+        assert_eq!(s, Shell::PowerShell);
+    }
 
-                // Meta (Time) - Right aligned simplified
-                // let time_str = format_relative_time(entry.mtime);
-                // Basic alignment logic could go here, omitting for brevity/complexity balance
-                // stderr.queue(cursor::MoveToColumn(self.width - 15))?;
-                // stderr.queue(Print(time_str))?;
-            } else {
-                // Create New / Add Path Option
-                if is_selected {
-                    stderr.queue(SetAttribute(Attribute::Bold))?;
-                }
-                match &self.mode {
-                    SelectorMode::Scan(_) => {
-                        let date_suffix = today_suffix();
-                        stderr.queue(Print(format!(
-                            "✨ Create new: {}-{}",
-                            self.input_buffer, date_suffix
-                        )))?;
-                    }
-                    SelectorMode::History(_) => {
-                        stderr.queue(Print(format!("📌 Add path: {}", self.input_buffer)))?;
-                    }
-                }
-                stderr.queue(SetAttribute(Attribute::Reset))?;
-            }
+    #[test]
+    fn fish_scripts_avoid_posix_syntax() {
+        let g = FishGenerator;
+        let path = Path::new("/tmp/it's a\\dir");
+        assert_eq!(
+            build_action_script(&g, &ShellAction::MkdirCd(path.to_path_buf()), true),
+            "mkdir -p '/tmp/it\\'s a\\\\dir'; and cd '/tmp/it\\'s a\\\\dir'; and \
+             begin; touch '/tmp/it\\'s a\\\\dir' 2>/dev/null; or true; end"
+        );
+        assert_eq!(g.set_env("TRY_PATH", "/w"), "set -gx TRY_PATH '/w'");
 
-            stderr.queue(Clear(ClearType::UntilNewLine))?;
-            stderr.queue(Print("\r\n"))?;
-        }
+        let s = g.init_script("try", "/usr/local/bin/try", "~/experiments");
+        assert!(s.contains("function try\n"), "{}", s);
+        assert!(s.contains("(command '/usr/local/bin/try' $argv"), "{}", s);
+        assert!(s.contains("set -gx TRY_SHELL fish"), "{}", s);
+        assert!(!s.contains("&&") && !s.contains("export"), "{}", s);
+    }
 
-        // Fill remaining empty lines in the list area with blanks/clear
-        let lines_to_clear = max_visible.saturating_sub(visible_end - self.scroll_offset);
-        for _ in 0..lines_to_clear {
-            stderr.queue(Clear(ClearType::CurrentLine))?;
-            stderr.queue(Print("\r\n"))?;
-        }
+    #[test]
+    fn bash_cd_and_mkdir() {
+        let g = BashGenerator;
+        assert_eq!(g.cd(Path::new("/tmp/x")), "cd '/tmp/x'");
+        assert_eq!(g.mkdir(Path::new("/tmp/x")), "mkdir -p '/tmp/x'");
+        assert_eq!(g.touch(Path::new("/tmp/x")), "touch '/tmp/x'");
+    }
 
-        // Footer
-        stderr.queue(cursor::MoveTo(0, self.height - 2))?;
-        stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-        stderr.queue(Print(&separator))?;
-        stderr.queue(SetAttribute(Attribute::Reset))?;
-        stderr.queue(Clear(ClearType::UntilNewLine))?;
-        stderr.queue(Print("\r\n"))?;
+    #[test]
+    fn bash_escape_single_quote() {
+        let g = BashGenerator;
+        assert_eq!(g.escape(Path::new("/tmp/it's")), "/tmp/it'\\''s");
+    }
 
-        if let Some(status) = &self.delete_status {
-            stderr.queue(SetAttribute(Attribute::Bold))?;
-            stderr.queue(Print(status))?;
-            stderr.queue(SetAttribute(Attribute::Reset))?;
-        } else if self.delete_mode {
-            stderr.queue(SetAttribute(Attribute::Bold))?;
-            stderr.queue(SetForegroundColor(Color::Red))?;
-            stderr.queue(Print(format!(
-                "DELETE MODE ({} marked) | Enter: Confirm | Esc: Cancel",
-                self.marked_for_deletion.len()
-            )))?;
-            stderr.queue(SetAttribute(Attribute::Reset))?;
+    #[test]
+    fn bash_escape_normalizes_backslashes() {
+        let g = BashGenerator;
+        if cfg!(windows) {
+            // Git Bash: mixed separators normalized to '/'.
+            assert_eq!(g.escape(Path::new(r"C:\a\b")), "C:/a/b");
         } else {
-            stderr.queue(SetForegroundColor(Color::DarkGrey))?;
-            stderr.queue(Print(
-                "↑↓: Navigate  Enter: Select  Del: Delete  Esc: Cancel",
-            ))?;
-            stderr.queue(SetAttribute(Attribute::Reset))?;
+            // Unix: backslash is a legal filename char, left untouched.
+            assert_eq!(g.escape(Path::new(r"C:\a\b")), r"C:\a\b");
         }
-        stderr.queue(Clear(ClearType::UntilNewLine))?;
-
-        stderr.flush()?;
-        Ok(())
     }
 
-    fn print_highlighted(
-        &self,
-        stderr: &mut Stderr,
-        text: &str,
-        query: &str,
-        is_selected: bool,
-    ) -> Result<()> {
-        if query.is_empty() {
-            stderr.queue(Print(text))?;
-            return Ok(());
+    /// True when every newline in `quoted` sits inside single quotes, no NUL
+    /// survives, and the quotes are balanced.
+    fn only_quoted_newlines(quoted: &str) -> bool {
+        let mut in_quote = false;
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\0' => return false,
+                '\'' => in_quote = !in_quote,
+                '\\' if !in_quote => {
+                    chars.next();
+                }
+                '\n' if !in_quote => return false,
+                _ => {}
+            }
         }
+        !in_quote
+    }
 
-        let text_chars: Vec<char> = text.chars().collect();
-        let query_chars: Vec<char> = query.to_lowercase().chars().collect();
-        let text_lower: Vec<char> = text.to_lowercase().chars().collect();
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(1_000))]
 
-        let mut query_idx = 0;
+        #[test]
+        fn shell_quote_never_leaves_bare_newlines_or_nul(s in proptest::prelude::any::<String>()) {
+            proptest::prop_assert!(only_quoted_newlines(&shell_quote(&s)));
+        }
+    }
 
-        for (i, c) in text_chars.iter().enumerate() {
-            if query_idx < query_chars.len() && text_lower[i] == query_chars[query_idx] {
-                stderr.queue(SetForegroundColor(Color::Yellow))?;
-                stderr.queue(SetAttribute(Attribute::Bold))?;
-                stderr.queue(Print(c))?;
+    proptest::proptest! {
+        // Each case quotes up to 100 strings in one `sh` call, so 200 cases
+        // round-trip ~10,000 strings without 10,000 process spawns.
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(200))]
+
+        #[cfg(unix)]
+        #[test]
+        fn shell_quote_round_trips_through_sh(
+            words in proptest::collection::vec("[^\\x00]*", 1..100)
+        ) {
+            let quoted: Vec<String> = words.iter().map(|w| shell_quote(w)).collect();
+            let script = format!("printf '%s\\0' {}", quoted.join(" "));
+            let out = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(&script)
+                .output()
+                .unwrap();
+            proptest::prop_assert!(out.status.success());
+            let mut got: Vec<String> = out
+                .stdout
+                .split(|b| *b == 0)
+                .map(|w| String::from_utf8(w.to_vec()).unwrap())
+                .collect();
+            got.pop(); // trailing terminator
+            proptest::prop_assert_eq!(got, words);
+        }
+    }
 
-                // Reset attributes but restore selection state if needed
-                stderr.queue(SetAttribute(Attribute::Reset))?;
-                if is_selected {
-                    stderr.queue(SetAttribute(Attribute::Bold))?;
-                }
+    #[test]
+    fn bash_join_uses_and_chain() {
+        let g = BashGenerator;
+        let joined = g.join(&["a".to_string(), "b".to_string()]);
+        assert!(joined.contains("&&"));
+        assert!(joined.contains('a') && joined.contains('b'));
+    }
 
-                query_idx += 1;
-            } else {
-                stderr.queue(Print(c))?;
-            }
-        }
-        Ok(())
+    #[test]
+    fn clone_commands_with_and_without_proxy() {
+        let plain = clone_commands("https://x/y'; rm -rf ~", Path::new("/d"), None, &[]);
+        // Arguments are passed as-is, never through a shell.
+        assert_eq!(plain, [["git", "clone", "https://x/y'; rm -rf ~", "/d"]]);
+        let proxied = clone_commands(
+            "https://x/y.git",
+            Path::new("/d"),
+            Some("proxychains4 -q"),
+            &[],
+        );
+        assert_eq!(proxied[0][..4], ["proxychains4", "-q", "git", "clone"]);
     }
 
-    fn confirm_batch_delete(&mut self, stderr: &mut Stderr) -> Result<()> {
-        // Simple confirmation via raw input (not full UI dialog for brevity)
-        stderr.execute(Clear(ClearType::All))?;
-        stderr.execute(cursor::MoveTo(0, 0))?;
-        stderr.execute(Print(format!(
-            "Delete {} directories? Type YES to confirm: ",
-            self.marked_for_deletion.len()
-        )))?;
+    #[test]
+    fn sparse_clone_commands_order_steps() {
+        let steps = clone_commands(
+            "https://x/mono.git",
+            Path::new("/d"),
+            None,
+            &["web".to_string(), "infra/k8s".to_string()],
+        );
+        let steps: Vec<String> = steps.iter().map(|argv| argv.join(" ")).collect();
+        assert_eq!(
+            steps,
+            [
+                "git clone --no-checkout https://x/mono.git /d",
+                "git -C /d sparse-checkout init --cone",
+                "git -C /d sparse-checkout set web infra/k8s",
+                "git -C /d checkout HEAD",
+            ]
+        );
+    }
 
-        // We need to temporarily disable raw mode or handle string input manually.
-        // Let's handle manually character by character
-        let mut input = String::new();
-        loop {
-            if event::poll(Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Release {
-                        continue; // ignore key-up events (Windows)
-                    }
-                    match key.code {
-                        KeyCode::Enter => break,
-                        KeyCode::Char(c) => {
-                            input.push(c);
-                            stderr.execute(Print(c))?;
-                        }
-                        KeyCode::Backspace => {
-                            input.pop();
-                            stderr.execute(cursor::MoveLeft(1))?;
-                            stderr.execute(Print(" "))?;
-                            stderr.execute(cursor::MoveLeft(1))?;
-                        }
-                        KeyCode::Esc => {
-                            input.clear();
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
-            }
+    #[cfg(unix)]
+    #[test]
+    fn pull_workspaces_reports_the_clones_that_fail() {
+        let git = |dir: &Path, args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        let dir = unique_tmp_dir("pull");
+        let origin = dir.join("origin");
+        fs::create_dir_all(&origin).unwrap();
+        let ready = git(&origin, &["init", "-q"])
+            && git(&origin, &["commit", "-q", "--allow-empty", "-m", "one"])
+            && git(&dir, &["clone", "-q", "origin", "good"])
+            && git(&dir, &["clone", "-q", "origin", "orphan"])
+            && git(&origin, &["commit", "-q", "--allow-empty", "-m", "two"])
+            && git(
+                &dir.join("orphan"),
+                &["remote", "set-url", "origin", "/no/such/repo"],
+            );
+        assert!(ready, "this test needs a working git");
+
+        let good = dir.join("good");
+        let orphan = dir.join("orphan");
+        assert!(matches!(
+            WorkspaceKind::detect(&good),
+            WorkspaceKind::Clone { .. }
+        ));
+        for parallel in [false, true] {
+            let failed = pull_workspaces(&[good.clone(), orphan.clone()], parallel);
+            assert_eq!(failed.len(), 1, "{:?}", failed);
+            assert!(failed[0].starts_with("orphan: "), "{:?}", failed);
         }
-
-        if input == "YES" {
-            let count = self.marked_for_deletion.len();
-            match &self.mode {
-                SelectorMode::History(_) => {
-                    // In History mode, remove from config file
-                    if let Err(e) = WorkspaceManager::remove_workspaces(&self.marked_for_deletion) {
-                        self.delete_status = Some(format!("Error removing workspaces: {}", e));
-                    } else {
-                        self.delete_status = Some(format!("Removed {} workspaces.", count));
-                    }
-                }
-                SelectorMode::Scan(_) => {
-                    // In Scan mode, delete directories from filesystem
-                    for path in &self.marked_for_deletion {
-                        if path.exists() {
-                            fs::remove_dir_all(path)?;
-                        }
-                    }
-                    self.delete_status = Some(format!("Deleted {} items.", count));
-                }
-            }
-        } else {
-            self.delete_status = Some("Delete cancelled.".to_string());
+        // More pulls than workers: all run, failures stay in order.
+        let gone: Vec<PathBuf> = (0..PULL_JOBS + 2)
+            .map(|i| dir.join(format!("gone-{}", i)))
+            .collect();
+        let failed = pull_workspaces(&gone, true);
+        assert_eq!(failed.len(), gone.len(), "{:?}", failed);
+        for (i, line) in failed.iter().enumerate() {
+            assert!(line.starts_with(&format!("gone-{}: ", i)), "{:?}", failed);
         }
+        let log = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&good)
+            .args(["log", "--format=%s"])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&log.stdout), "two\none\n");
+    }
 
-        self.marked_for_deletion.clear();
-        self.delete_mode = false;
-        // Reload entries
-        self.load_entries()?;
-        Ok(())
+    #[cfg(unix)]
+    #[test]
+    fn git_log_since_lists_recent_commits_or_gives_up() {
+        let dir = unique_tmp_dir("git-log-since").join("proj-2025-01-01");
+        fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success())
+        };
+        let ready = git(&["init", "-q"])
+            && (1..=12).all(|i| git(&["commit", "-q", "--allow-empty", "-m", &format!("c{}", i)]));
+        assert!(ready, "this test needs a working git");
+
+        let created = workspace_created(&dir).unwrap();
+        assert_eq!(created, NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        let lines = git_log_since(&dir, created, Duration::from_secs(10)).unwrap();
+        assert_eq!(lines.len(), GIT_LOG_LIMIT);
+        assert!(lines[0].ends_with(" c12"), "{:?}", lines);
+
+        let tomorrow = Local::now().date_naive() + chrono::Days::new(1);
+        assert_eq!(
+            git_log_since(&dir, tomorrow, Duration::from_secs(10)),
+            Some(vec![])
+        );
+        assert_eq!(git_log_since(&dir, created, Duration::ZERO), None);
+        assert_eq!(
+            git_log_since(&dir.join("missing"), created, Duration::from_secs(10)),
+            None
+        );
     }
-}
 
-// Scoring Algorithm Port
-fn calculate_score(entry: &TryEntry, query: &str, query_chars: &[char], now: SystemTime) -> f64 {
-    let mut score = 0.0;
+    #[test]
+    fn archives_round_trip_through_tar() {
+        assert!(
+            std::process::Command::new("tar")
+                .arg("--version")
+                .output()
+                .is_ok(),
+            "this test needs tar on PATH"
+        );
+        let dir = unique_tmp_dir("archive");
+        let (base, archives) = (dir.join("tries"), dir.join("archive"));
+        let work = base.join("idea-2024-05-01");
+        fs::create_dir_all(work.join("src")).unwrap();
+        fs::write(work.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let archive = archive_workspace(&work, &archives).unwrap();
+        assert_eq!(archive, archives.join("idea-2024-05-01.tar.gz"));
+        assert!(!work.exists());
+        fs::create_dir_all(&work).unwrap();
+        let second = archive_workspace(&work, &archives).unwrap();
+        assert_eq!(second, archives.join("idea-2024-05-01-2.tar.gz"));
+        let mut names: Vec<_> = archive_entries(&archives)
+            .into_iter()
+            .map(|e| e.basename)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["idea-2024-05-01", "idea-2024-05-01-2"]);
+
+        assert_eq!(restore_archive(&archive, &base).unwrap(), work);
+        assert_eq!(
+            fs::read_to_string(work.join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert!(!archive.exists());
+        let err = restore_archive(&second, &base).unwrap_err();
+        assert!(matches!(err, TryError::RestoreTargetExists(p) if p == work));
+        assert!(second.exists());
+        assert!(archive_entries(&dir.join("missing")).is_empty());
+    }
 
-    // Default date suffix bonus (ends with digit)
-    if entry
-        .basename
-        .chars()
-        .last()
-        .map_or(false, |c| c.is_numeric())
-    {
-        score += 2.0;
+    #[test]
+    fn ctrl_a_never_archives_in_history_mode() {
+        let dir = unique_tmp_dir("ctrl-a-history");
+        let root = dir.join("src");
+        fs::create_dir_all(&root).unwrap();
+        let record = WorkspaceRecord {
+            path: root.clone(),
+            ..WorkspaceRecord::default()
+        };
+        let mut sel = TrySelector::new(
+            SelectorMode::History(vec![record]),
+            String::new(),
+            dir.clone(),
+        );
+        sel.load_entries().unwrap();
+        sel.refresh_scores();
+        sel.archive_dir = Some(dir.join("archive"));
+        sel.allow_delete = true;
+        let ctrl_a = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert!(matches!(press(&mut sel, &[ctrl_a]), Step::Continue { .. }));
+        assert!(root.exists());
     }
 
-    if !query.is_empty() {
-        let text_lower: Vec<char> = entry.basename_down.chars().collect();
-        let query_len = query_chars.len();
-        let text_len = text_lower.len();
+    #[test]
+    fn ctrl_a_archives_the_selected_entry() {
+        assert!(
+            std::process::Command::new("tar")
+                .arg("--version")
+                .output()
+                .is_ok(),
+            "this test needs tar on PATH"
+        );
+        let mut sel = snapshot_selector("ctrl-a", &["alpha", "beta"], "");
+        let base = match &sel.mode {
+            SelectorMode::Scan(base) => base.clone(),
+            _ => unreachable!(),
+        };
+        let ctrl_a = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        let step = press(&mut sel, &[ctrl_a]);
+        assert!(
+            matches!(step, Step::Continue { .. }),
+            "no archive_dir, no archiving"
+        );
 
-        let mut last_pos: isize = -1;
-        let mut query_idx = 0;
-        let mut i = 0;
+        let archives = base.with_extension("archive");
+        sel.archive_dir = Some(archives.clone());
+        sel.allow_delete = false;
+        let step = press(&mut sel, &[ctrl_a]);
+        assert!(
+            matches!(step, Step::Continue { .. }),
+            "--no-delete keeps it"
+        );
 
-        while i < text_len && query_idx < query_len {
-            let char = text_lower[i];
+        sel.allow_delete = true;
+        let Step::ConfirmArchive(path) = press(&mut sel, &[ctrl_a]) else {
+            panic!("Ctrl-A should ask first");
+        };
+        assert_eq!(path, base.join("alpha"));
+        sel.finish_archive(&path, false).unwrap();
+        assert!(base.join("alpha").exists(), "declining keeps it");
+        assert_eq!(sel.status.as_deref(), Some("Archive cancelled."));
+
+        sel.finish_archive(&path, true).unwrap();
+        assert!(!base.join("alpha").exists());
+        assert!(archives.join("alpha.tar.gz").exists());
+        let names: Vec<_> = sel.entries.iter().map(|e| e.basename.as_str()).collect();
+        assert_eq!(names, ["beta"]);
+        assert!(sel.status.as_deref().unwrap().starts_with("Archived to "));
+
+        let mut picker = TrySelector::new(
+            SelectorMode::Archive(archives.clone()),
+            "alp".into(),
+            base.clone(),
+        );
+        picker.allow_create = false;
+        picker.load_entries().unwrap();
+        picker.refresh_scores();
+        assert!(matches!(
+            picker.handle_selection(false),
+            Some(ShellAction::Cd(p)) if p == archives.join("alpha.tar.gz")
+        ));
+    }
 
-            if char == query_chars[query_idx] {
-                score += 1.0;
+    #[test]
+    fn run_command_reports_failure() {
+        let argv = |a: &[&str]| -> Vec<String> { a.iter().map(|s| s.to_string()).collect() };
+        let err = run_command(&argv(&["git", "clone", "/definitely/not/here", "/tmp/x"]));
+        if let Err(TryError::CommandFailed { command, .. }) = &err {
+            assert!(command.starts_with("git clone"));
+        }
+        assert!(err.is_err());
+        let err = run_command(&argv(&["try-rs-no-such-tool"])).unwrap_err();
+        assert!(matches!(err, TryError::CommandFailed { .. }));
+    }
 
-                // Boundary bonus
-                let is_boundary = i == 0 || !text_lower[i - 1].is_alphanumeric();
-                if is_boundary {
-                    score += 1.0;
-                }
+    #[cfg(unix)]
+    #[test]
+    fn sparse_clone_checks_out_only_matching_paths() {
+        let git = |dir: &Path, args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        let dir = unique_tmp_dir("sparse-clone");
+        let src = dir.join("src");
+        for f in ["web/app.js", "infra/main.tf", "docs/readme.md"] {
+            fs::create_dir_all(src.join(f).parent().unwrap()).unwrap();
+            fs::write(src.join(f), "x").unwrap();
+        }
+        let committed = git(&src, &["init", "-q"])
+            && git(&src, &["add", "-A"])
+            && git(
+                &src,
+                &[
+                    "-c",
+                    "user.name=t",
+                    "-c",
+                    "user.email=t@t",
+                    "commit",
+                    "-qm",
+                    "init",
+                ],
+            );
+        assert!(committed, "this test needs a working git");
+
+        let dest = dir.join("dest");
+        let url = format!("file://{}", src.display());
+        let out = dir.join("action");
+        let sparse = ["web".to_string()];
+        let opts = CloneOptions {
+            name: Some("dest".to_string()),
+            sparse: &sparse,
+            ..Default::default()
+        };
+        clone_and_cd(&dir, &url, opts, Some(&out)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap().trim(),
+            BashGenerator.cd(&dest)
+        );
+        assert!(dest.join("web/app.js").exists());
+        assert!(!dest.join("infra").exists());
+        assert!(!dest.join("docs").exists());
+    }
 
-                // Proximity bonus
-                if last_pos >= 0 {
-                    let gap = (i as isize) - last_pos - 1;
-                    score += 2.0 / ((gap + 1) as f64).sqrt();
-                }
+    #[cfg(unix)]
+    #[test]
+    fn nested_clone_goes_under_the_dated_dir_and_reuses_a_checkout() {
+        let dir = unique_tmp_dir("nest-clone");
+        let src = dir.join("proj");
+        fs::create_dir_all(&src).unwrap();
+        let ok = std::process::Command::new("git")
+            .args(["init", "-q"])
+            .arg(&src)
+            .status()
+            .is_ok_and(|s| s.success());
+        assert!(ok, "this test needs a working git");
+
+        let base = dir.join("tries");
+        let url = format!("file://{}", src.display());
+        let out = dir.join("action");
+        let dest = base.join("notes").join("proj");
+        let setup = BashGenerator.join(&[BashGenerator.cd(&dest), "make setup".to_string()]);
+        // The post-clone command only follows a fresh clone.
+        for expected in [setup, BashGenerator.cd(&dest)] {
+            let opts = CloneOptions {
+                name: Some("notes".into()),
+                nest: true,
+                post_clone: Some("make setup"),
+                ..Default::default()
+            };
+            clone_and_cd(&base, &url, opts, Some(&out)).unwrap();
+            assert_eq!(fs::read_to_string(&out).unwrap().trim(), expected);
+        }
+        assert!(dest.join(".git").exists());
 
-                last_pos = i as isize;
-                query_idx += 1;
-            }
-            i += 1;
+        let opts = CloneOptions {
+            name: Some("mono".into()),
+            into: Some("web/app"),
+            ..Default::default()
+        };
+        clone_and_cd(&base, &url, opts, Some(&out)).unwrap();
+        let deep = base.join("mono").join("web").join("app");
+        assert_eq!(
+            fs::read_to_string(&out).unwrap().trim(),
+            BashGenerator.cd(&deep)
+        );
+        assert!(deep.join(".git").exists());
+    }
+
+    #[test]
+    fn clone_into_rejects_paths_that_escape() {
+        assert_eq!(checked_subdir("web/app").unwrap(), PathBuf::from("web/app"));
+        for bad in ["", "../up", "web/../../x", "/abs"] {
+            assert!(
+                matches!(checked_subdir(bad), Err(TryError::InvalidSubdir(_))),
+                "{}",
+                bad
+            );
         }
+        let dir = unique_tmp_dir("clone-into-bad");
+        let out = dir.join("action");
+        let opts = CloneOptions {
+            into: Some(".."),
+            ..Default::default()
+        };
+        let err = clone_and_cd(&dir, "https://github.com/u/r.git", opts, Some(&out)).unwrap_err();
+        assert!(matches!(err, TryError::InvalidSubdir(_)));
+        assert!(!out.exists());
+    }
 
-        if query_idx < query_len {
-            return 0.0;
+    #[test]
+    fn clone_names_must_stay_inside_the_base() {
+        assert!(checked_clone_name("repo-2024-01-01").is_ok());
+        for bad in ["", ".", "..", "/etc", "a/b", "../{repo}", "a\\b"] {
+            assert!(
+                matches!(checked_clone_name(bad), Err(TryError::InvalidCloneName(_))),
+                "{}",
+                bad
+            );
+        }
+        let dir = unique_tmp_dir("clone-name-bad");
+        let out = dir.join("action");
+        for template in ["/tmp/{repo}", "../{repo}-{date}", "{host}/{repo}"] {
+            let opts = CloneOptions {
+                name_template: Some(template),
+                ..Default::default()
+            };
+            let err =
+                clone_and_cd(&dir, "https://github.com/u/r.git", opts, Some(&out)).unwrap_err();
+            assert!(matches!(err, TryError::InvalidCloneName(_)), "{}", template);
         }
+        assert!(!out.exists());
+    }
 
-        // Density bonus
-        if last_pos >= 0 {
-            score *= query_len as f64 / (last_pos as f64 + 1.0);
-        }
+    #[test]
+    fn watch_hooks_run_try_set_silently_on_directory_change() {
+        let bash = BashGenerator.watch_hook("/opt/it's/try");
+        assert!(bash.contains(r#"command '/opt/it'\''s/try' set --silent "$PWD""#));
+        assert!(bash.contains("add-zsh-hook precmd __try_watch"));
+        assert!(bash.contains("PROMPT_COMMAND=\"__try_watch"));
+        let ps = PowerShellGenerator.watch_hook("C:/it's/try.exe");
+        assert!(ps.contains("-Name 'C:/it''s/try.exe'"));
+        assert!(ps.contains("set --silent $PWD.Path"));
+    }
 
-        // Length penalty
-        score *= 10.0 / (entry.basename.len() as f64 + 10.0);
+    #[test]
+    fn set_silent_requires_nothing_else_and_path_requires_silent() {
+        let cli = Cli::try_parse_from(["try", "set", "--silent", "/tmp/x"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Set { silent: true, path: Some(p) }) if p == "/tmp/x"
+        ));
+        assert!(Cli::try_parse_from(["try", "set", "/tmp/x"]).is_err());
     }
 
-    // Recency bonus
-    if let Ok(duration) = now.duration_since(entry.mtime) {
-        let hours = duration.as_secs_f64() / 3600.0;
-        score += 3.0 / (hours + 1.0).sqrt();
+    #[test]
+    fn clone_base_overrides_the_base_path_for_one_clone() {
+        let cli =
+            Cli::try_parse_from(["try", "clone", "https://h/u/r.git", "--base", "~/oss"]).unwrap();
+        let Some(Commands::Clone { base, .. }) = cli.command else {
+            panic!("not a clone");
+        };
+        let home = dirs::home_dir().unwrap();
+        let tries = PathBuf::from("/tries");
+        assert_eq!(
+            clone_base(base.as_deref(), tries.clone()).unwrap(),
+            home.join("oss")
+        );
+        assert_eq!(clone_base(None, tries.clone()).unwrap(), tries);
+        assert_eq!(
+            clone_base(Some("scratch"), tries).unwrap(),
+            env::current_dir().unwrap().join("scratch")
+        );
     }
 
-    score
-}
+    #[test]
+    fn out_file_only_applies_to_commands_that_emit_a_script() {
+        let emits = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            cli.command.as_ref().is_none_or(Commands::emits_script)
+        };
+        assert!(emits(&["try", "--out-file", "/tmp/a"]));
+        assert!(emits(&[
+            "try",
+            "clone",
+            "https://h/u/r.git",
+            "--out-file",
+            "/tmp/a"
+        ]));
+        assert!(emits(&["try", "set", "--out-file", "/tmp/a"]));
+        assert!(emits(&["try", "open", "proj", "--out-file", "/tmp/a"]));
+        assert!(!emits(&["try", "set", "--silent", "--out-file", "/tmp/a"]));
+        assert!(!emits(&[
+            "try",
+            "open",
+            "proj",
+            "--print",
+            "--out-file",
+            "/tmp/a"
+        ]));
+        assert!(!emits(&["try", "recent", "--out-file", "/tmp/a"]));
+        assert!(!emits(&["try", "list", "--out-file", "/tmp/a"]));
+        assert!(!emits(&["try", "init", "--out-file", "/tmp/a"]));
+    }
 
-#[derive(Debug)]
-enum ShellAction {
-    Cd(PathBuf),
-    MkdirCd(PathBuf),
-    Set(PathBuf),
-}
+    #[test]
+    fn height_is_global_and_leaves_room_for_the_compact_layout() {
+        let cli = Cli::try_parse_from(["try", "set", "--height", "10"]).unwrap();
+        assert_eq!(cli.height, Some(10));
+        assert!(Cli::try_parse_from(["try", "--height", "2"]).is_err());
+    }
 
-fn expand_path(path: &str) -> PathBuf {
-    if path.starts_with("~/") {
-        let home = dirs::home_dir().expect("Could not find home directory");
-        home.join(&path[2..])
-    } else {
-        PathBuf::from(path)
+    #[cfg(unix)]
+    #[test]
+    fn read_only_base_is_detected_and_never_created() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = unique_tmp_dir("read-only");
+        assert!(!is_read_only_dir(&dir));
+        assert!(!is_read_only_dir(&dir.join("missing")));
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        assert!(is_read_only_dir(&dir));
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0); // nothing was probed
+
+        let missing = dir.join("missing");
+        let mut sel = TrySelector::new(SelectorMode::Scan(missing.clone()), String::new(), dir);
+        sel.read_only = true;
+        assert!(sel.prepare_base().is_err());
+        assert!(!missing.exists());
     }
-}
 
-/// Characters accepted into the search/path input buffer. Includes `:` and `\`
-/// so Windows absolute paths (e.g. `D:\tests`) can be typed in History mode.
-fn is_allowed_input_char(c: char) -> bool {
-    c.is_alphanumeric()
-        || matches!(c, '-' | '_' | '.' | ' ' | '/' | '~' | ':' | '\\')
-}
+    #[cfg(unix)]
+    #[test]
+    fn bash_eval_line_survives_awkward_words() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = unique_tmp_dir("eval-line");
+        let exe = dir.join("it's try");
+        fs::write(&exe, "#!/bin/sh\nprintf 'echo %s:%s\\n' \"$#\" \"$3\"\n").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+        let args: Vec<String> = ["init", "~/x", "--group", "a \"b\" $c"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let line = BashGenerator.eval_line(&exe.to_string_lossy(), &args);
+        let out = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&line)
+            .output()
+            .expect("this test needs sh");
+        assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "4:--group");
+        assert_eq!(
+            PowerShellGenerator.eval_line("C:/it's/try.exe", &args[..2]),
+            "Invoke-Expression ((& 'C:/it''s/try.exe' 'init' '~/x') -join \"`n\")"
+        );
+    }
 
-/// Strip Windows extended-length (verbatim) path prefixes. `std::fs::canonicalize`
-/// returns paths like `\\?\D:\foo`, which PowerShell can't map back to a drive
-/// and which leak into prompts. Returns the path unchanged when no prefix is
-/// present (always the case on non-Windows).
-fn strip_verbatim_prefix(path: &Path) -> PathBuf {
-    let s = path.to_string_lossy();
-    if let Some(rest) = s.strip_prefix(r"\\?\UNC\") {
-        PathBuf::from(format!(r"\\{}", rest))
-    } else if let Some(rest) = s.strip_prefix(r"\\?\") {
-        PathBuf::from(rest)
-    } else {
-        path.to_path_buf()
+    #[test]
+    fn install_finds_the_rc_file_and_spots_existing_setups() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            shell_rc_file(Shell::Bash, Some("/usr/bin/zsh"), home, None),
+            home.join(".zshrc")
+        );
+        assert_eq!(
+            shell_rc_file(Shell::Bash, Some("/bin/zsh"), home, Some(Path::new("/z"))),
+            Path::new("/z/.zshrc")
+        );
+        assert_eq!(
+            shell_rc_file(Shell::Bash, None, home, None),
+            home.join(".bashrc")
+        );
+        assert_eq!(
+            shell_rc_file(Shell::Fish, Some("/usr/bin/fish"), home, None),
+            home.join(".config/fish/config.fish")
+        );
+        // The shell decides; a login shell of another kind doesn't.
+        assert_eq!(
+            shell_rc_file(Shell::Fish, Some("/bin/bash"), home, None),
+            home.join(".config/fish/config.fish")
+        );
+        assert_eq!(
+            shell_rc_file(Shell::Bash, Some("/usr/bin/fish"), home, None),
+            home.join(".bashrc")
+        );
+        let fish = FishGenerator.eval_line("/opt/try", &["init".to_string()]);
+        assert_eq!(fish, "command '/opt/try' 'init' | source");
+        assert!(has_init_line("try init ~/tries | source\n", &fish));
+
+        let line = BashGenerator.eval_line("/opt/try", &["init".to_string(), "~/x".to_string()]);
+        assert!(has_init_line(
+            &format!("alias ll='ls -l'\n{}\n", line),
+            &line
+        ));
+        assert!(has_init_line("eval \"$(~/bin/try init ~/tries)\"\n", &line));
+        assert!(!has_init_line("# eval \"$(try init)\"\n", &line));
+        assert!(!has_init_line("export TRY_PATH=~/x\n", &line));
     }
-}
 
-/// Canonicalize a path for storage, without the verbatim prefix. Falls back to
-/// the input path if canonicalization fails (e.g. the path doesn't exist yet).
-fn canonicalize_clean(path: &Path) -> PathBuf {
-    std::fs::canonicalize(path)
-        .map(|p| strip_verbatim_prefix(&p))
-        .unwrap_or_else(|_| path.to_path_buf())
-}
+    #[test]
+    fn bash_init_script_shape() {
+        let g = BashGenerator;
+        let s = g.init_script("try", "/usr/local/bin/try", "~/experiments");
+        assert!(s.contains("try()"));
+        assert!(s.contains("eval \"$out\""));
+        assert!(s.contains(r#"export TRY_PATH="~/experiments""#));
+        assert!(s.contains(r#"export TRY_SHELL="bash""#));
+    }
 
-/// Parse a repository name out of a git URL (the last path segment, sans `.git`).
-fn parse_repo_name(url: &str) -> Option<String> {
-    git_url_regex()
-        .captures(url)
-        .and_then(|caps| caps.get(1))
-        .map(|m| m.as_str().to_string())
-}
+    #[test]
+    fn init_scripts_never_call_themselves() {
+        // Even with a bare `try`, the wrapper must not recurse into itself.
+        let s = BashGenerator.init_script("try", "try", "~/x");
+        assert!(s.contains("out=$(command 'try' \"$@\""), "{}", s);
+        let s = PowerShellGenerator.init_script("try", "try", "~/x");
+        assert!(
+            s.contains("& (Get-Command -CommandType Application -Name 'try'"),
+            "{}",
+            s
+        );
+    }
 
-// ============================================================================
-// Shell integration layer
-//
-// The TUI (crossterm) is cross-platform, but the *scripts* `try` emits for the
-// shell wrapper to `eval` are shell-specific. `Shell` selects the right
-// `ScriptGenerator` so the exact same core logic drives Bash/Zsh on Unix and
-// PowerShell on Windows.
-// ============================================================================
+    #[test]
+    fn wrapper_exe_falls_back_to_path_lookup() {
+        let dir = unique_tmp_dir("wrapper-exe");
+        let exe = dir.join("try");
+        fs::write(&exe, "").unwrap();
+        assert_eq!(wrapper_exe(Ok(exe.clone())), exe.to_string_lossy());
+        assert_eq!(wrapper_exe(Ok(dir.join("gone"))), "try");
+        assert_eq!(wrapper_exe(Err(io::Error::other("no /proc"))), "try");
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Shell {
-    Bash,
-    PowerShell,
-}
+    #[test]
+    fn bash_init_script_custom_name() {
+        let g = BashGenerator;
+        let s = g.init_script("t", "/usr/local/bin/try", "~/x");
+        assert!(s.contains("t()"));
+    }
 
-impl Shell {
-    /// Parse a shell name (case-insensitive). Recognizes bash/zsh/sh and
-    /// powershell/pwsh/ps.
-    fn parse(name: &str) -> Option<Shell> {
-        match name.trim().to_lowercase().as_str() {
-            "bash" | "zsh" | "sh" => Some(Shell::Bash),
-            "powershell" | "pwsh" | "ps" | "ps1" => Some(Shell::PowerShell),
-            _ => None,
-        }
+    #[test]
+    fn powershell_cd_and_mkdir() {
+        let g = PowerShellGenerator;
+        assert_eq!(
+            g.cd(Path::new("C:/tmp/x")),
+            "Set-Location -LiteralPath 'C:/tmp/x'"
+        );
+        assert!(g
+            .mkdir(Path::new("C:/tmp/x"))
+            .starts_with("New-Item -ItemType Directory"));
+        assert!(g.touch(Path::new("C:/tmp/x")).contains("LastWriteTime"));
     }
 
-    /// Detect the active shell from the environment.
-    ///
-    /// Priority: explicit `TRY_SHELL` (set by our own init wrapper) > presence
-    /// of POSIX `SHELL` (bash/zsh) > PowerShell markers > compile-time OS.
-    fn detect() -> Shell {
-        if let Ok(s) = env::var("TRY_SHELL") {
-            if let Some(shell) = Shell::parse(&s) {
-                return shell;
-            }
-        }
-        Self::detect_from(|k| env::var(k).ok())
+    #[test]
+    fn powershell_escape_doubles_single_quote() {
+        let g = PowerShellGenerator;
+        assert_eq!(g.escape(Path::new("C:/it's")), "C:/it''s");
     }
 
-    /// Pure detection helper, parameterized over an env lookup for testability.
-    fn detect_from(get: impl Fn(&str) -> Option<String>) -> Shell {
-        // A POSIX-style $SHELL strongly implies bash/zsh, even on Windows
-        // (e.g. Git Bash / WSL).
-        if get("SHELL").is_some() {
-            return Shell::Bash;
-        }
-        // PowerShell sets PSModulePath; cmd does not export $SHELL either.
-        if get("PSModulePath").is_some() {
-            return Shell::PowerShell;
-        }
-        if cfg!(windows) {
-            Shell::PowerShell
-        } else {
-            Shell::Bash
-        }
+    #[test]
+    fn powershell_escape_normalizes_backslashes() {
+        let g = PowerShellGenerator;
+        assert_eq!(g.escape(Path::new(r"C:\a\b")), "C:/a/b");
     }
 
-    fn generator(self) -> Box<dyn ScriptGenerator> {
-        match self {
-            Shell::Bash => Box::new(BashGenerator),
-            Shell::PowerShell => Box::new(PowerShellGenerator),
-        }
+    #[test]
+    fn powershell_join_uses_semicolons() {
+        let g = PowerShellGenerator;
+        assert_eq!(g.join(&["a".to_string(), "b".to_string()]), "a; b");
     }
-}
 
-/// Default wrapper command name per shell. `try` is a reserved keyword in
-/// PowerShell, so PowerShell uses `tr` instead.
-fn default_fn_name(shell: Shell) -> &'static str {
-    match shell {
-        Shell::Bash => "try",
-        Shell::PowerShell => "tr",
+    #[test]
+    fn powershell_set_env() {
+        let g = PowerShellGenerator;
+        assert_eq!(g.set_env("TRY_PATH", "C:/x"), "$env:TRY_PATH = 'C:/x'");
     }
-}
 
-/// Abstraction over emitting shell commands. Each method returns a single
-/// command string; `join` combines a sequence into one script.
-trait ScriptGenerator {
-    /// Quote/escape a path for safe interpolation into a command.
-    fn escape(&self, path: &Path) -> String;
-    fn cd(&self, path: &Path) -> String;
-    fn mkdir(&self, path: &Path) -> String;
-    /// Update a directory's mtime so recency scoring bumps it to the top.
-    fn touch(&self, path: &Path) -> String;
-    fn set_env(&self, key: &str, value: &str) -> String;
-    fn echo(&self, msg: &str) -> String;
-    /// Combine commands into a single line the shell can `eval`.
-    fn join(&self, cmds: &[String]) -> String;
-    /// The shell function + env setup printed by `try init`.
-    /// `fn_name` is the wrapper command the user will type.
-    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String;
+    #[test]
+    fn powershell_init_script_shape() {
+        let g = PowerShellGenerator;
+        let s = g.init_script("tr", r"C:\bin\try.exe", r"C:\experiments");
+        assert!(s.contains("function tr"));
+        // must NOT define a function literally named `try` (reserved keyword)
+        assert!(!s.contains("function try"));
+        assert!(s.contains("Invoke-Expression"));
+        assert!(s.contains("$LASTEXITCODE"));
+        assert!(s.contains("$env:TRY_SHELL = 'powershell'"));
+    }
 
-    fn git_clone(&self, url: &str, dest: &Path, proxy: Option<&str>) -> String {
-        let escaped = self.escape(dest);
-        if let Some(proxy_tool) = proxy {
-            format!("{} git clone '{}' '{}'", proxy_tool, url, escaped)
-        } else {
-            format!("git clone '{}' '{}'", url, escaped)
-        }
+    #[test]
+    fn default_fn_name_per_shell() {
+        assert_eq!(default_fn_name(Shell::Bash), "try");
+        assert_eq!(default_fn_name(Shell::PowerShell), "tr");
     }
-}
 
-struct BashGenerator;
+    #[test]
+    fn build_action_script_cd_bash() {
+        let g = BashGenerator;
+        let s = build_action_script(&g, &ShellAction::Cd(PathBuf::from("/tmp/x")), true);
+        assert!(s.contains("touch '/tmp/x'"));
+        assert!(s.contains("cd '/tmp/x'"));
+    }
 
-impl ScriptGenerator for BashGenerator {
-    fn escape(&self, path: &Path) -> String {
-        let s = path.to_string_lossy();
-        // On Windows (Git Bash), normalize mixed separators to '/' so paths
-        // joined by PathBuf don't break `cd`. On real Unix, a backslash is a
-        // legal filename character and must be left untouched.
-        let s = if cfg!(windows) {
-            s.replace('\\', "/")
-        } else {
-            s.into_owned()
-        };
-        // single-quote escape: ' -> '\''
-        s.replace('\'', "'\\''")
+    #[test]
+    fn build_action_script_can_skip_the_touch() {
+        let g = BashGenerator;
+        let cd = ShellAction::Cd(PathBuf::from("/tmp/x"));
+        assert_eq!(build_action_script(&g, &cd, false), "cd '/tmp/x'");
+        let mkdir = ShellAction::MkdirCd(PathBuf::from("/tmp/x"));
+        let s = build_action_script(&g, &mkdir, false);
+        assert!(s.contains("mkdir -p") && !s.contains("touch"), "{}", s);
     }
 
-    fn cd(&self, path: &Path) -> String {
-        format!("cd '{}'", self.escape(path))
+    #[test]
+    fn build_action_script_cd_is_not_gated_on_touch() {
+        let g = BashGenerator;
+        let s = build_action_script(&g, &ShellAction::Cd(PathBuf::from("/tmp/x")), true);
+        assert_eq!(
+            s,
+            "cd '/tmp/x' && \\\n  { touch '/tmp/x' 2>/dev/null || true; }"
+        );
+
+        let s = build_action_script(&g, &ShellAction::MkdirCd(PathBuf::from("/tmp/x")), true);
+        let mkdir = s.find("mkdir -p").unwrap();
+        let cd = s.find("cd '/tmp/x'").unwrap();
+        let touch = s.find("touch").unwrap();
+        assert!(mkdir < cd && cd < touch, "{}", s);
+
+        let g = PowerShellGenerator;
+        let s = build_action_script(&g, &ShellAction::Cd(PathBuf::from("C:/x")), true);
+        assert!(
+            s.starts_with("Set-Location -LiteralPath 'C:/x'; try {"),
+            "{}",
+            s
+        );
     }
 
-    fn mkdir(&self, path: &Path) -> String {
-        format!("mkdir -p '{}'", self.escape(path))
+    #[test]
+    fn build_action_script_mkdircd_powershell() {
+        let g = PowerShellGenerator;
+        let s = build_action_script(&g, &ShellAction::MkdirCd(PathBuf::from("C:/tmp/x")), true);
+        assert!(s.contains("New-Item"));
+        assert!(s.contains("Set-Location"));
+        assert!(s.contains(';'));
     }
 
-    fn touch(&self, path: &Path) -> String {
-        format!("touch '{}'", self.escape(path))
+    #[test]
+    fn build_action_script_set_updates_env_and_cds() {
+        let g = BashGenerator;
+        let s = build_action_script(&g, &ShellAction::Set(PathBuf::from("/tmp/ws")), true);
+        assert!(s.contains("export TRY_PATH='/tmp/ws'"));
+        assert!(s.contains("cd '/tmp/ws'"));
     }
 
-    fn set_env(&self, key: &str, value: &str) -> String {
-        format!("export {}='{}'", key, value.replace('\'', "'\\''"))
+    #[test]
+    fn build_action_script_set_powershell_updates_env() {
+        let g = PowerShellGenerator;
+        let s = build_action_script(&g, &ShellAction::Set(PathBuf::from("C:/ws")), true);
+        assert!(s.contains("$env:TRY_PATH = 'C:/ws'"));
+        assert!(s.contains("Set-Location -LiteralPath 'C:/ws'"));
     }
 
-    fn echo(&self, msg: &str) -> String {
-        format!("echo '{}'", msg.replace('\'', "'\\''"))
+    #[cfg(unix)]
+    #[test]
+    fn ctrl_o_opens_the_file_manager_and_stays_open() {
+        let mut sel = snapshot_selector("ctrl-o", &["alpha", "beta"], "");
+        assert_eq!(sel.opener, file_opener());
+        if cfg!(target_os = "linux") {
+            assert_eq!(file_opener(), "xdg-open");
+        }
+        let ctrl_o = KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL);
+
+        sel.opener = "true";
+        let step = press(&mut sel, &[ctrl_o]);
+        assert!(matches!(step, Step::Continue { redraw: true }));
+        assert_eq!(sel.status.as_deref(), Some("Opened in file manager"));
+
+        sel.opener = "try-test-no-such-opener";
+        let step = press(&mut sel, &[ctrl_o]);
+        assert!(matches!(step, Step::Continue { .. }));
+        let status = sel.status.clone().unwrap();
+        assert!(
+            status.starts_with("Could not run try-test-no-such-opener"),
+            "{}",
+            status
+        );
     }
 
-    fn join(&self, cmds: &[String]) -> String {
-        cmds.join(" && \\\n  ")
+    #[test]
+    fn open_script_replaces_cd_with_editor() {
+        let g = BashGenerator;
+        let p = PathBuf::from("/tmp/it's");
+        assert_eq!(
+            build_open_script(&g, &ShellAction::Cd(p.clone()), "code -n"),
+            "code -n '/tmp/it'\\''s'"
+        );
+        let s = build_open_script(&g, &ShellAction::MkdirCd(p), "nvim");
+        assert!(
+            s.starts_with("mkdir -p") && s.ends_with("nvim '/tmp/it'\\''s'"),
+            "{}",
+            s
+        );
+        let g = PowerShellGenerator;
+        assert_eq!(
+            build_open_script(&g, &ShellAction::Cd(PathBuf::from("C:/x")), "code"),
+            "code 'C:/x'"
+        );
     }
 
-    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String {
-        format!(
-            r#"
-{name}() {{
-    local out
-    # Use absolute path to the binary to ensure consistency
-    out=$('{exe}' "$@" 2>/dev/tty)
-    if [ $? -eq 0 ]; then
-        eval "$out"
-    else
-        # Echo error to stderr if needed, or do nothing on cancel
-        :
-    fi
-}}
-export TRY_PATH="{path}"
-export TRY_SHELL="bash"
-"#,
-            name = fn_name,
-            exe = exe,
-            path = default_path
-        )
+    #[test]
+    fn resolve_editor_precedence() {
+        let env = || Some("vim".to_string());
+        assert_eq!(
+            resolve_editor(Some("code".into()), Some("nvim"), env()).as_deref(),
+            Some("code")
+        );
+        assert_eq!(
+            resolve_editor(None, Some("nvim"), env()).as_deref(),
+            Some("nvim")
+        );
+        assert_eq!(resolve_editor(None, None, env()).as_deref(), Some("vim"));
+        assert_eq!(resolve_editor(None, None, Some(" ".into())), None);
     }
-}
 
-struct PowerShellGenerator;
-
-impl PowerShellGenerator {
-    /// PowerShell single-quote escaping: ' -> ''
-    fn ps_quote(s: &str) -> String {
-        s.replace('\'', "''")
+    #[test]
+    fn workspace_size_counts_excluded_dirs_apart() {
+        let dir = unique_tmp_dir("workspace-size");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join(".git/objects")).unwrap();
+        fs::create_dir_all(dir.join("web/node_modules/left-pad")).unwrap();
+        fs::write(dir.join("README.md"), [0u8; 10]).unwrap();
+        fs::write(dir.join("src/main.rs"), [0u8; 100]).unwrap();
+        fs::write(dir.join(".git/objects/pack"), [0u8; 1000]).unwrap();
+        fs::write(dir.join("web/node_modules/left-pad/index.js"), [0u8; 5000]).unwrap();
+
+        let exclude = TryConfig::default().size_exclude();
+        assert_eq!(
+            compute_workspace_size(&dir, &exclude).unwrap(),
+            WorkspaceSize {
+                included: 110,
+                excluded: 6000
+            }
+        );
+        assert_eq!(
+            compute_workspace_size(&dir, &[]).unwrap(),
+            WorkspaceSize {
+                included: 6110,
+                excluded: 0
+            }
+        );
+        assert!(compute_workspace_size(&dir.join("missing"), &exclude).is_err());
     }
-}
 
-impl ScriptGenerator for PowerShellGenerator {
-    fn escape(&self, path: &Path) -> String {
-        // Normalize separators to '/' (PowerShell accepts them) then
-        // PowerShell single-quote escape: ' -> ''
-        Self::ps_quote(&path.to_string_lossy().replace('\\', "/"))
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
     }
 
-    fn cd(&self, path: &Path) -> String {
-        format!("Set-Location -LiteralPath '{}'", self.escape(path))
-    }
+    #[test]
+    fn resolve_path_reports_ties_and_misses() {
+        let dir = unique_tmp_dir("resolve-path");
+        for name in ["api-2025-01-01", "api-2025-02-02", "webapp-2025-02-02"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        let resolve = |name: &str| {
+            resolve_path(
+                SelectorMode::Scan(dir.clone()),
+                name,
+                DateRange::default(),
+                Recency::default(),
+            )
+        };
+        assert_eq!(resolve("webap").unwrap(), dir.join("webapp-2025-02-02"));
+        assert_eq!(
+            resolve("api-2025-01-01").unwrap(),
+            dir.join("api-2025-01-01")
+        );
 
-    fn mkdir(&self, path: &Path) -> String {
-        format!(
-            "New-Item -ItemType Directory -Force -Path '{}' | Out-Null",
-            self.escape(path)
-        )
-    }
+        let err = resolve("api").unwrap_err();
+        match err.downcast_ref::<TryError>() {
+            Some(TryError::AmbiguousEntry { candidates, .. }) => {
+                let mut candidates = candidates.clone();
+                candidates.sort();
+                assert_eq!(candidates, ["api-2025-01-01", "api-2025-02-02"]);
+            }
+            other => panic!("expected an ambiguous match, got {:?}", other),
+        }
+        let err = resolve("zzz").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TryError>(),
+            Some(TryError::NoMatchingEntry { .. })
+        ));
 
-    fn touch(&self, path: &Path) -> String {
-        // Bump LastWriteTime so recency scoring promotes the directory.
-        format!(
-            "(Get-Item -LiteralPath '{}').LastWriteTime = Get-Date",
-            self.escape(path)
+        // An exact basename beats the fuzzy ranking, in archives too.
+        fs::create_dir_all(dir.join("api")).unwrap();
+        assert_eq!(resolve("api").unwrap(), dir.join("api"));
+        let archives = unique_tmp_dir("resolve-archive");
+        for name in ["api", "api-2025-01-01"] {
+            fs::write(archives.join(format!("{}{}", name, ARCHIVE_SUFFIX)), "").unwrap();
+        }
+        let archive = resolve_path(
+            SelectorMode::Archive(archives.clone()),
+            "api",
+            DateRange::default(),
+            Recency::default(),
         )
+        .unwrap();
+        assert_eq!(archive, archives.join(format!("api{}", ARCHIVE_SUFFIX)));
     }
 
-    fn set_env(&self, key: &str, value: &str) -> String {
-        format!("$env:{} = '{}'", key, Self::ps_quote(value))
+    #[test]
+    fn action_line_is_verb_tab_path() {
+        assert_eq!(
+            action_line(&ShellAction::Cd(PathBuf::from("/tmp/x"))),
+            "cd\t/tmp/x"
+        );
+        assert_eq!(
+            action_line(&ShellAction::MkdirCd(PathBuf::from("/tmp/new"))),
+            "mkdir\t/tmp/new"
+        );
+        assert_eq!(
+            action_line(&ShellAction::Set(PathBuf::from("/tmp/ws"))),
+            "set\t/tmp/ws"
+        );
     }
 
-    fn echo(&self, msg: &str) -> String {
-        format!("Write-Host '{}'", Self::ps_quote(msg))
+    #[test]
+    fn check_fifo_rejects_missing_and_regular_files() {
+        let dir = unique_tmp_dir("fifo-check");
+        let missing = dir.join("nope");
+        assert!(matches!(check_fifo(&missing), Err(TryError::FifoMissing(p)) if p == missing));
+        if cfg!(unix) {
+            let file = dir.join("regular");
+            fs::write(&file, "").unwrap();
+            assert!(matches!(check_fifo(&file), Err(TryError::NotAFifo(p)) if p == file));
+        }
     }
 
-    fn join(&self, cmds: &[String]) -> String {
-        cmds.join("; ")
+    #[cfg(unix)]
+    #[test]
+    fn write_fifo_line_reaches_reader() {
+        let dir = unique_tmp_dir("fifo-write");
+        let fifo = dir.join("pipe");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        check_fifo(&fifo).unwrap();
+
+        let reader = {
+            let fifo = fifo.clone();
+            std::thread::spawn(move || fs::read_to_string(fifo).unwrap())
+        };
+        write_fifo_line(&fifo, "cd\t/tmp/x").unwrap();
+        assert_eq!(reader.join().unwrap(), "cd\t/tmp/x\n");
     }
 
-    fn git_clone(&self, url: &str, dest: &Path, proxy: Option<&str>) -> String {
-        let escaped = self.escape(dest);
-        if let Some(proxy_tool) = proxy {
-            format!("{} git clone '{}' '{}'", proxy_tool, url, escaped)
-        } else {
-            format!("git clone '{}' '{}'", url, escaped)
-        }
+    #[test]
+    fn emit_script_writes_out_file() {
+        let dir = unique_tmp_dir("out-file");
+        let out = dir.join("action");
+        emit_script("cd '/tmp/x'", Some(&out)).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "cd '/tmp/x'\n");
     }
 
-    fn init_script(&self, fn_name: &str, exe: &str, default_path: &str) -> String {
-        // NOTE: `try` is a reserved keyword in PowerShell, so the wrapper must
-        // use a different name (default `tr`).
-        format!(
+    #[test]
+    fn config_parses_global_and_per_base_hooks() {
+        let cfg = TryConfig::parse(
             r#"
-function {name} {{
-    $out = & '{exe}' @args
-    if ($LASTEXITCODE -eq 0 -and $out) {{
-        Invoke-Expression ($out -join "`n")
-    }}
-}}
-$env:TRY_PATH = '{path}'
-$env:TRY_SHELL = 'powershell'
+after_create = "git init"
+
+[bases."/tmp/js-tries"]
+after_create = "npm init -y"
 "#,
-            name = fn_name,
-            exe = PowerShellGenerator::ps_quote(exe),
-            path = PowerShellGenerator::ps_quote(default_path)
         )
+        .unwrap();
+        assert_eq!(
+            cfg.after_create_for(Path::new("/tmp/js-tries")),
+            Some("npm init -y")
+        );
+        assert_eq!(
+            cfg.after_create_for(Path::new("/tmp/other")),
+            Some("git init")
+        );
     }
-}
 
-/// Build the command sequence for a resolved user action.
-fn build_action_script(gen: &dyn ScriptGenerator, action: &ShellAction) -> String {
-    match action {
-        ShellAction::Cd(path) => gen.join(&[gen.touch(path), gen.cd(path)]),
-        ShellAction::MkdirCd(path) => {
-            gen.join(&[gen.mkdir(path), gen.touch(path), gen.cd(path)])
-        }
-        ShellAction::Set(path) => {
-            // Update the live shell's TRY_PATH to the chosen workspace, then cd.
-            let env_cmd = gen.set_env("TRY_PATH", &path.to_string_lossy());
-            gen.join(&[env_cmd, gen.cd(path)])
+    #[test]
+    fn config_delete_confirmation_defaults_to_type_yes() {
+        let cfg = TryConfig::parse("").unwrap();
+        assert_eq!(cfg.delete_confirmation, DeleteConfirmation::TypeYes);
+        for (text, want) in [
+            ("none", DeleteConfirmation::None),
+            ("keypress", DeleteConfirmation::Keypress),
+            ("type-yes", DeleteConfirmation::TypeYes),
+        ] {
+            let cfg = TryConfig::parse(&format!("delete_confirmation = \"{}\"", text)).unwrap();
+            assert_eq!(cfg.delete_confirmation, want);
         }
+        assert!(TryConfig::parse("delete_confirmation = \"yolo\"").is_err());
     }
-}
 
-fn main() -> Result<()> {
-    // Manually check for subcommands to redirect execution flow similar to Ruby script
-    // Or use Clap properly.
-    // The Ruby script uses a clever `try exec` pattern. We will emulate that.
+    #[test]
+    fn config_missing_file_is_default() {
+        let dir = unique_tmp_dir("cfg-missing");
+        let cfg = TryConfig::load_from(&dir.join("config.toml")).unwrap();
+        assert!(cfg.after_create.is_none());
+        assert!(cfg.bases.is_empty());
+    }
 
-    let cli = Cli::parse();
+    #[test]
+    fn config_invalid_toml_is_corrupted() {
+        let dir = unique_tmp_dir("cfg-bad");
+        let path = dir.join("config.toml");
+        fs::write(&path, "after_create = [").unwrap();
+        let err = TryConfig::load_from(&path).unwrap_err();
+        assert!(matches!(err, TryError::ConfigCorrupted(p, _) if p == path));
+        fs::write(&path, "unknown_key = 1").unwrap();
+        assert!(TryConfig::load_from(&path).is_err());
+    }
 
-    // Resolve base path: workspaces config takes priority over TRY_PATH env var
-    let base_path = {
-        let workspaces = WorkspaceManager::get_workspaces().unwrap_or_default();
-        if let Some(first) = workspaces.first() {
-            // Use the first workspace from config (set by `try set`).
-            // Strip any stale verbatim prefix from older configs.
-            strip_verbatim_prefix(first)
-        } else if let Ok(p) = env::var("TRY_PATH") {
-            // Fall back to TRY_PATH env var if no workspaces configured
-            expand_path(&p)
-        } else {
-            // Ultimate fallback
-            expand_path("~/project/test")
+    #[test]
+    fn post_clone_cmd_must_be_a_single_command() {
+        assert!(check_post_clone_cmd("npm install > /dev/null").is_ok());
+        assert!(check_post_clone_cmd("code {path}").is_ok());
+        for (bad, pattern) in [
+            ("make; rm -rf ~", ";"),
+            ("echo $(id)", "("),
+            ("echo `id`", "`"),
+            ("a && b", "&"),
+            ("a || b", "|"),
+            ("a\nb", "\n"),
+            ("make & rm -rf ~", "&"),
+            ("cat setup.sh | sh", "|"),
+            ("echo (id)", "("),
+            ("diff <(a) b", "("),
+        ] {
+            assert!(
+                matches!(
+                    check_post_clone_cmd(bad),
+                    Err(TryError::UnsafePostClone { pattern: p }) if p == pattern
+                ),
+                "{}",
+                bad
+            );
         }
-    };
+        // Refused before anything is cloned, unless allowed.
+        let dir = unique_tmp_dir("post-clone-unsafe");
+        let opts = CloneOptions {
+            name: Some("x".into()),
+            post_clone: Some("a; b"),
+            ..Default::default()
+        };
+        let err = clone_and_cd(&dir, "file:///nowhere", opts, None).unwrap_err();
+        assert!(matches!(err, TryError::UnsafePostClone { pattern: ";" }));
+        assert!(!dir.join("x").exists());
+    }
 
-    // If command is None, it defaults to interactive (or query)
-    match cli.command {
-        Some(Commands::Init { path, shell, name }) => {
-            let path_buf = expand_path(&path);
-            // Only add workspace if the list is empty (first time init)
-            let workspaces = WorkspaceManager::get_workspaces().unwrap_or_default();
-            if workspaces.is_empty() {
-                if let Err(e) = WorkspaceManager::add_workspace(&path_buf) {
-                    eprintln!("Warning: Failed to save workspace: {}", e);
-                }
-            }
-            let shell = shell
-                .as_deref()
-                .and_then(Shell::parse)
-                .unwrap_or_else(Shell::detect);
-            let fn_name = name.unwrap_or_else(|| default_fn_name(shell).to_string());
-            print_init_script(shell, &fn_name, &path);
-        }
-        Some(Commands::Clone { url, name, proxy }) => {
-            generate_clone_script(&base_path, &url, name, proxy)?;
-        }
-        Some(Commands::Set) => {
-            let workspaces = WorkspaceManager::get_workspaces().unwrap_or_default();
+    #[test]
+    fn after_create_command_substitutes_quoted_path() {
+        let g = BashGenerator;
+        assert_eq!(
+            after_create_command(&g, "git init && echo {path}", Path::new("/tmp/it's")),
+            "git init && echo '/tmp/it'\\''s'"
+        );
+        let g = PowerShellGenerator;
+        assert_eq!(
+            after_create_command(&g, "code {path}", Path::new("C:/x")),
+            "code 'C:/x'"
+        );
+    }
 
-            run_interactive(SelectorMode::History(workspaces), String::new(), base_path)?;
-        }
-        None => {
-            // Default: try [query] -> mapped to try exec cd [query] by the shell wrapper
-            // But if called directly without wrapper:
-            let query_str = cli.query.unwrap_or_default();
+    #[cfg(unix)]
+    #[test]
+    fn readable_workspaces_file_is_flagged_and_fixed() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = unique_tmp_dir("ws-perms");
+        let cfg = dir.join("workspaces");
+        fs::write(&cfg, "").unwrap();
+        fs::set_permissions(&cfg, fs::Permissions::from_mode(0o644)).unwrap();
+        let warning = WorkspaceManager::permissions_warning(&cfg).unwrap();
+        assert!(warning.contains("mode 644"), "{}", warning);
 
-            // Check if query looks like a git url
-            if query_str.starts_with("http") || query_str.starts_with("git@") {
-                generate_clone_script(&base_path, &query_str, None, None)?;
-            } else {
-                // The wrapper usually calls `try exec ...`.
-                // If we are here, we should output the script for the wrapper to eval.
-                run_interactive(SelectorMode::Scan(base_path.clone()), query_str, base_path)?;
-            }
-        }
-    }
+        WorkspaceManager::warn_once(&cfg, &warning);
+        let sentinel = dir.join(PERMISSIONS_WARNED_FILE);
+        assert_eq!(fs::read_to_string(&sentinel).unwrap(), warning);
 
-    Ok(())
-}
+        WorkspaceManager::restrict_permissions(&cfg).unwrap();
+        assert_eq!(
+            fs::metadata(&cfg).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(WorkspaceManager::permissions_warning(&cfg), None);
+    }
 
-fn run_interactive(mode: SelectorMode, query: String, workspace_path: PathBuf) -> Result<()> {
-    let mut selector = TrySelector::new(mode, query, workspace_path);
-    if let Some(action) = selector.run()? {
-        let gen = Shell::detect().generator();
-        // For `Set`, update workspace history before emitting the cd script.
-        if let ShellAction::Set(path) = &action {
-            let _ = WorkspaceManager::add_workspace(path);
+    #[test]
+    fn gc_drops_missing_workspaces_and_stale_state() {
+        let dir = unique_tmp_dir("gc");
+        let cfg = dir.join("workspaces");
+        let [first, old, gone, new] = ["first", "old", "gone", "new"].map(|n| dir.join(n));
+        for (i, path) in [&first, &old, &gone, &new].into_iter().enumerate() {
+            fs::create_dir_all(path).unwrap();
+            let record = WorkspaceRecord {
+                path: path.clone(),
+                last_accessed: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(i as u64)),
+                group: None,
+                sort_weight: 0.0,
+                source: None,
+            };
+            let mut records = WorkspaceManager::get_records_from(&cfg).unwrap_or_default();
+            records.push(record);
+            WorkspaceManager::save_records_to(&cfg, &records).unwrap();
         }
-        let script = build_action_script(gen.as_ref(), &action);
-        println!("{}", script);
-    } else {
-        // Cancelled
-        std::process::exit(1);
+        fs::remove_dir(&gone).unwrap();
+
+        assert_eq!(WorkspaceManager::gc_to(&cfg, false).unwrap(), 1);
+        let order = |cfg: &Path| WorkspaceManager::get_workspaces_from(cfg).unwrap();
+        assert_eq!(order(&cfg), [first.clone(), old.clone(), new.clone()]);
+        assert_eq!(WorkspaceManager::gc_to(&cfg, true).unwrap(), 0);
+        assert_eq!(order(&cfg), [first.clone(), new.clone(), old.clone()]);
+
+        let state_path = dir.join("state.toml");
+        TryState::remember_pin(&state_path, &old, true).unwrap();
+        TryState::remember_pin(&state_path, &gone, true).unwrap();
+        TryState::remember_permanent(&state_path, &gone).unwrap();
+        TryState::remember_visit(&state_path, &gone).unwrap();
+        assert_eq!(TryState::gc_at(&state_path).unwrap(), 3);
+        let state = TryState::load_from(&state_path).unwrap();
+        assert_eq!(state.pins.len(), 1);
+        assert!(state.permanent.is_empty());
     }
-    Ok(())
-}
-
-fn print_init_script(shell: Shell, fn_name: &str, default_path: &str) {
-    let exe = env::current_exe().unwrap_or(PathBuf::from("try"));
-    let exe_str = exe.to_string_lossy().to_string();
-    let gen = shell.generator();
-    print!("{}", gen.init_script(fn_name, &exe_str, default_path));
-}
 
-fn generate_clone_script(
-    base_path: &Path,
-    url: &str,
-    name: Option<String>,
-    proxy: Option<String>,
-) -> Result<()> {
-    let dir_name = if let Some(n) = name {
-        n
-    } else {
-        // Parse git url for name; Ruby version produces repo-date style.
-        let repo_name = parse_repo_name(url).context("Invalid git url")?;
-        let date_suffix = today_suffix();
-        format!("{}-{}", repo_name, date_suffix)
-    };
+    #[test]
+    fn workspace_add_get_roundtrip_and_dedup_to_top() {
+        let dir = unique_tmp_dir("ws-roundtrip");
+        let cfg = dir.join("workspaces");
 
-    let full_path = base_path.join(&dir_name);
+        let a = dir.join("a");
+        let b = dir.join("b");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
 
-    // Determine proxy command: CLI option > environment variable
-    let proxy_cmd = proxy.or_else(|| env::var("TRY_PROXY").ok());
+        WorkspaceManager::add_workspace_to(&cfg, &a, None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &b, None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &a, None).unwrap();
 
-    let gen = Shell::detect().generator();
-    let script = gen.join(&[
-        gen.mkdir(&full_path),
-        gen.echo(&format!("Cloning {}...", url)),
-        gen.git_clone(url, &full_path, proxy_cmd.as_deref()),
-        gen.cd(&full_path),
-    ]);
-    println!("{}", script);
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert_eq!(ws.len(), 2);
+        assert_eq!(ws[0], canonicalize_clean(&a));
+    }
 
-    Ok(())
-}
+    #[test]
+    fn workspace_remove() {
+        let dir = unique_tmp_dir("ws-remove");
+        let cfg = dir.join("workspaces");
+        let a = dir.join("a");
+        let b = dir.join("b");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::atomic::{AtomicUsize, Ordering};
+        WorkspaceManager::add_workspace_to(&cfg, &a, None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &b, None).unwrap();
 
-    static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let canon_a = canonicalize_clean(&a);
+        WorkspaceManager::remove_workspaces_from(&cfg, std::slice::from_ref(&canon_a)).unwrap();
 
-    fn unique_tmp_dir(tag: &str) -> PathBuf {
-        let n = TMP_COUNTER.fetch_add(1, Ordering::SeqCst);
-        let dir = std::env::temp_dir().join(format!(
-            "try-rs-test-{}-{}-{}",
-            tag,
-            std::process::id(),
-            n
-        ));
-        fs::create_dir_all(&dir).unwrap();
-        dir
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert_eq!(ws.len(), 1);
+        assert!(!ws.contains(&canon_a));
     }
 
-    fn entry(basename: &str, mtime: SystemTime) -> TryEntry {
-        TryEntry {
-            basename: basename.to_string(),
-            basename_down: basename.to_lowercase(),
-            path: PathBuf::from(basename),
-            mtime,
-            score: 0.0,
-        }
+    #[test]
+    fn workspace_remove_unknown_path_is_typed_error() {
+        let dir = unique_tmp_dir("ws-remove-unknown");
+        let cfg = dir.join("workspaces");
+        WorkspaceManager::add_workspace_to(&cfg, &dir, None).unwrap();
+
+        let ghost = dir.join("ghost");
+        let err = WorkspaceManager::remove_workspaces_from(&cfg, std::slice::from_ref(&ghost))
+            .unwrap_err();
+        assert!(matches!(err, TryError::WorkspaceNotFound(p) if p == ghost));
+        // Nothing was removed.
+        assert_eq!(
+            WorkspaceManager::get_workspaces_from(&cfg).unwrap().len(),
+            1
+        );
     }
 
-    fn score_for(basename: &str, query: &str) -> f64 {
-        let q = query.to_lowercase();
-        let qc: Vec<char> = q.chars().collect();
-        calculate_score(
-            &entry(basename, SystemTime::UNIX_EPOCH),
-            &q,
-            &qc,
-            SystemTime::now(),
-        )
+    #[test]
+    fn workspace_get_invalid_utf8_is_corrupted() {
+        let dir = unique_tmp_dir("ws-corrupt");
+        let cfg = dir.join("workspaces");
+        fs::write(&cfg, b"/ok\n\xff\xfe\n").unwrap();
+        let err = WorkspaceManager::get_workspaces_from(&cfg).unwrap_err();
+        assert!(matches!(err, TryError::ConfigCorrupted(..)));
     }
 
     #[test]
-    fn parse_repo_name_https_with_git_suffix() {
-        assert_eq!(
-            parse_repo_name("https://github.com/user/repo.git").as_deref(),
-            Some("repo")
-        );
+    fn clone_script_rejects_unparseable_url() {
+        let dir = unique_tmp_dir("clone-bad-url");
+        let out = dir.join("action");
+        let err = clone_and_cd(
+            &dir,
+            "https://github.com/user/",
+            CloneOptions::default(),
+            Some(&out),
+        )
+        .unwrap_err();
+        assert!(matches!(err, TryError::InvalidGitUrl(_)));
+        assert!(!out.exists());
     }
 
     #[test]
-    fn parse_repo_name_https_without_git_suffix() {
-        assert_eq!(
-            parse_repo_name("https://github.com/user/cool-thing").as_deref(),
-            Some("cool-thing")
-        );
+    fn emit_script_reports_unwritable_target() {
+        let dir = unique_tmp_dir("out-file-bad");
+        let out = dir.join("missing-dir").join("action");
+        let err = emit_script("cd '/tmp/x'", Some(&out)).unwrap_err();
+        assert!(matches!(err, TryError::ShellEmitFailed { path, .. } if path == out));
     }
 
     #[test]
-    fn parse_repo_name_ssh_scp_syntax() {
+    fn workspace_add_several_remove_one() {
+        let dir = unique_tmp_dir("ws-lifecycle");
+        let cfg = dir.join("workspaces");
+        let paths: Vec<PathBuf> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|n| {
+                let p = dir.join(n);
+                fs::create_dir_all(&p).unwrap();
+                canonicalize_clean(&p)
+            })
+            .collect();
+        for p in &paths {
+            WorkspaceManager::add_workspace_to(&cfg, p, None).unwrap();
+        }
+        // Most recently added first.
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert_eq!(ws, paths.iter().rev().cloned().collect::<Vec<_>>());
+
+        WorkspaceManager::remove_workspaces_from(&cfg, std::slice::from_ref(&paths[1])).unwrap();
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
         assert_eq!(
-            parse_repo_name("git@github.com:user/repo.git").as_deref(),
-            Some("repo")
+            ws,
+            vec![paths[3].clone(), paths[2].clone(), paths[0].clone()]
         );
     }
 
     #[test]
-    fn expand_path_tilde() {
-        let home = dirs::home_dir().unwrap();
-        assert_eq!(expand_path("~/foo/bar"), home.join("foo/bar"));
+    fn workspace_add_same_twice_is_single_entry() {
+        let dir = unique_tmp_dir("ws-twice");
+        let cfg = dir.join("workspaces");
+        WorkspaceManager::add_workspace_to(&cfg, &dir, None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &dir, None).unwrap();
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert_eq!(ws, vec![canonicalize_clean(&dir)]);
     }
 
     #[test]
-    fn expand_path_absolute_untouched() {
-        assert_eq!(expand_path("/abs/path"), PathBuf::from("/abs/path"));
+    fn workspace_history_is_capped() {
+        let dir = unique_tmp_dir("ws-cap");
+        let cfg = dir.join("workspaces");
+        for i in 0..(MAX_HISTORY + 100) {
+            // Nonexistent paths are stored as given, no mkdir needed.
+            WorkspaceManager::add_workspace_to(&cfg, &dir.join(format!("ws{}", i)), None).unwrap();
+        }
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert_eq!(ws.len(), MAX_HISTORY);
+        assert_eq!(ws[0], dir.join(format!("ws{}", MAX_HISTORY + 99)));
+        // The oldest 100 fell off the end.
+        assert!(!ws.contains(&dir.join("ws99")));
+        assert!(ws.contains(&dir.join("ws100")));
     }
 
     #[test]
-    fn score_subsequence_match_is_positive() {
-        assert!(score_for("myproject-2025-01-01", "myp") > 0.0);
+    fn workspace_file_is_written_with_version_header() {
+        let dir = unique_tmp_dir("ws-header");
+        let cfg = dir.join("workspaces");
+        WorkspaceManager::add_workspace_to(&cfg, &dir, None).unwrap();
+        let text = fs::read_to_string(&cfg).unwrap();
+        assert!(text.starts_with(&format!("{}{}\n", WORKSPACES_HEADER, WORKSPACES_VERSION)));
     }
 
     #[test]
-    fn score_non_subsequence_is_zero() {
-        assert_eq!(score_for("myproject", "zzz"), 0.0);
+    fn workspace_legacy_file_is_migrated_with_backup() {
+        let dir = unique_tmp_dir("ws-migrate");
+        let cfg = dir.join("workspaces");
+        fs::write(&cfg, "/a\n\n/b\n").unwrap();
+
+        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert_eq!(ws, vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        assert_eq!(
+            fs::read_to_string(dir.join("workspaces.v0.bak")).unwrap(),
+            "/a\n\n/b\n"
+        );
+        let text = fs::read_to_string(&cfg).unwrap();
+        assert!(text.starts_with(WORKSPACES_HEADER));
+        // Already current: reading again doesn't rewrite or re-backup.
+        fs::remove_file(dir.join("workspaces.v0.bak")).unwrap();
+        WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+        assert!(!dir.join("workspaces.v0.bak").exists());
     }
 
     #[test]
-    fn score_empty_query_still_ranks_by_recency_and_suffix() {
-        let e = entry("proj-2025-01-01", SystemTime::now());
-        let s = calculate_score(&e, "", &[], SystemTime::now());
-        assert!(s > 0.0);
+    fn concurrent_readers_migrate_a_legacy_file_once() {
+        // Rounds on fresh legacy files, as the race is one migration wide.
+        for round in 0..200 {
+            let dir = unique_tmp_dir(&format!("ws-migrate-concurrent-{}", round));
+            let cfg = dir.join("workspaces");
+            fs::write(&cfg, "/a\n/b\n").unwrap();
+            let start = std::sync::Arc::new(std::sync::Barrier::new(8));
+            let threads: Vec<_> = (0..8)
+                .map(|t| {
+                    let (dir, cfg, start) = (dir.clone(), cfg.clone(), start.clone());
+                    std::thread::spawn(move || {
+                        start.wait();
+                        if t % 2 == 0 {
+                            WorkspaceManager::get_records_from(&cfg).unwrap();
+                        } else {
+                            let path = dir.join(format!("added-{}", t));
+                            WorkspaceManager::add_workspace_to(&cfg, &path, None).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(
+                fs::read_to_string(dir.join("workspaces.v0.bak")).unwrap(),
+                "/a\n/b\n"
+            );
+            let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
+            assert_eq!(ws.len(), 6, "round {}: {:?}", round, ws);
+        }
     }
 
     #[test]
-    fn score_prefers_contiguous_match() {
-        let contiguous = score_for("test-2025-01-01", "test");
-        let spread = score_for("t-e-s-t-2025-01-01", "test");
-        assert!(contiguous > spread, "{} !> {}", contiguous, spread);
+    fn concurrent_adds_keep_every_workspace() {
+        let dir = unique_tmp_dir("ws-concurrent");
+        let cfg = dir.join("workspaces");
+        // Line the writers up so they contend for the lock.
+        let start = std::sync::Arc::new(std::sync::Barrier::new(8));
+        let writers: Vec<_> = (0..8)
+            .map(|w| {
+                let (dir, cfg, start) = (dir.clone(), cfg.clone(), start.clone());
+                std::thread::spawn(move || {
+                    start.wait();
+                    for i in 0..10 {
+                        let path = dir.join(format!("w{}-{}", w, i));
+                        WorkspaceManager::add_workspace_to(&cfg, &path, None).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records.len(), 80);
+        let leftovers: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|n| n.contains(".tmp-"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
     }
 
     #[test]
-    fn shell_parse_known_names() {
-        assert_eq!(Shell::parse("bash"), Some(Shell::Bash));
-        assert_eq!(Shell::parse("ZSH"), Some(Shell::Bash));
-        assert_eq!(Shell::parse("powershell"), Some(Shell::PowerShell));
-        assert_eq!(Shell::parse("pwsh"), Some(Shell::PowerShell));
-        assert_eq!(Shell::parse("cmd"), None);
+    fn workspace_add_stamps_last_accessed() {
+        let dir = unique_tmp_dir("ws-accessed");
+        let cfg = dir.join("workspaces");
+        let before = SystemTime::now() - Duration::from_secs(1);
+        WorkspaceManager::add_workspace_to(&cfg, &dir.join("a"), None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &dir.join("b"), None).unwrap();
+
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].path.ends_with("b"));
+        assert!(records.iter().all(|r| r.last_accessed.unwrap() >= before));
     }
 
-    #[test]
-    fn detect_prefers_posix_shell_env() {
-        let s = Shell::detect_from(|k| match k {
-            "SHELL" => Some("/bin/bash".to_string()),
-            "PSModulePath" => Some("C:/foo".to_string()),
-            _ => None,
-        });
-        assert_eq!(s, Shell::Bash);
+    fn read_events(path: &Path) -> Vec<WorkspaceEvent> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
     }
 
     #[test]
-    fn detect_powershell_when_only_psmodulepath() {
-        let s = Shell::detect_from(|k| match k {
-            "PSModulePath" => Some("C:/foo".to_string()),
-            _ => None,
-        });
-        assert_eq!(s, Shell::PowerShell);
+    fn list_sorted_orders_by_recency_name_frequency_and_size() {
+        let dir = unique_tmp_dir("ws-list-sorted");
+        let cfg = dir.join("workspaces");
+        let [alpha, bravo, charlie] = ["alpha", "bravo", "charlie"].map(|n| dir.join(n));
+        for (path, bytes) in [(&alpha, 100), (&bravo, 0), (&charlie, 10)] {
+            fs::create_dir_all(path).unwrap();
+            fs::write(path.join("f"), vec![0u8; bytes]).unwrap();
+            WorkspaceManager::add_workspace_to(&cfg, path, None).unwrap();
+            if *path == bravo {
+                WorkspaceManager::touch_workspace_to(&cfg, path).unwrap();
+                WorkspaceManager::touch_workspace_to(&cfg, path).unwrap();
+            }
+        }
+
+        let names = |order| -> Vec<String> {
+            WorkspaceManager::list_sorted_from(&cfg, order, &[])
+                .unwrap()
+                .iter()
+                .map(|r| r.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        // Frequency ties (alpha, charlie) fall back to recency.
+        assert_eq!(names(SortOrder::Recency), ["charlie", "bravo", "alpha"]);
+        assert_eq!(names(SortOrder::Name), ["alpha", "bravo", "charlie"]);
+        assert_eq!(names(SortOrder::Frequency), ["bravo", "charlie", "alpha"]);
+        assert_eq!(names(SortOrder::Size), ["alpha", "charlie", "bravo"]);
     }
 
     #[test]
-    fn bash_cd_and_mkdir() {
-        let g = BashGenerator;
-        assert_eq!(g.cd(Path::new("/tmp/x")), "cd '/tmp/x'");
-        assert_eq!(g.mkdir(Path::new("/tmp/x")), "mkdir -p '/tmp/x'");
-        assert_eq!(g.touch(Path::new("/tmp/x")), "touch '/tmp/x'");
+    fn access_counts_follow_renames() {
+        let dir = unique_tmp_dir("ws-access-counts");
+        let log = dir.join(EVENTS_FILE);
+        let (old, new) = (Path::new("/old"), Path::new("/new"));
+        let mut renamed = WorkspaceEvent::now(EventKind::Renamed, new);
+        renamed.from = Some(old.to_path_buf());
+        let events = [
+            WorkspaceEvent::now(EventKind::Added, old),
+            WorkspaceEvent::now(EventKind::Accessed, old),
+            renamed,
+            WorkspaceEvent::now(EventKind::Accessed, new),
+            WorkspaceEvent::now(EventKind::Removed, new),
+        ];
+        WorkspaceManager::append_events(&log, &events, EVENTS_TRIM_BYTES).unwrap();
+        fs::write(&log, fs::read_to_string(&log).unwrap() + "not json\n").unwrap();
+
+        let counts = WorkspaceManager::access_counts(&log);
+        assert_eq!(counts.get(new), Some(&3));
+        assert!(!counts.contains_key(old));
     }
 
     #[test]
-    fn bash_escape_single_quote() {
-        let g = BashGenerator;
-        assert_eq!(g.escape(Path::new("/tmp/it's")), "/tmp/it'\\''s");
+    fn workspace_changes_are_logged_as_events() {
+        let dir = unique_tmp_dir("ws-events");
+        let cfg = dir.join("workspaces");
+        let ws = dir.join("a");
+        fs::create_dir_all(&ws).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &ws, None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &ws, None).unwrap();
+        WorkspaceManager::touch_workspace_to(&cfg, &ws).unwrap();
+        fs::remove_dir(&ws).unwrap();
+        WorkspaceManager::gc_to(&cfg, false).unwrap();
+
+        let events = read_events(&dir.join(EVENTS_FILE));
+        let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Added,
+                EventKind::Accessed,
+                EventKind::Accessed,
+                EventKind::Removed
+            ]
+        );
+        assert!(events.iter().all(|e| e.path == ws && e.from.is_none()));
     }
 
     #[test]
-    fn bash_escape_normalizes_backslashes() {
-        let g = BashGenerator;
-        if cfg!(windows) {
-            // Git Bash: mixed separators normalized to '/'.
-            assert_eq!(g.escape(Path::new(r"C:\a\b")), "C:/a/b");
-        } else {
-            // Unix: backslash is a legal filename char, left untouched.
-            assert_eq!(g.escape(Path::new(r"C:\a\b")), r"C:\a\b");
-        }
+    fn event_log_keeps_only_the_newest_lines() {
+        let dir = unique_tmp_dir("ws-events-cap");
+        let log = dir.join(EVENTS_FILE);
+        let events: Vec<WorkspaceEvent> = (0..MAX_EVENTS + 5)
+            .map(|i| WorkspaceEvent::now(EventKind::Accessed, Path::new(&format!("/w{}", i))))
+            .collect();
+        // Under the trim size, nothing is dropped.
+        WorkspaceManager::append_events(&log, &events[..3], 1 << 20).unwrap();
+        WorkspaceManager::append_events(&log, &events[3..], 1 << 20).unwrap();
+        assert_eq!(read_events(&log).len(), MAX_EVENTS + 5);
+        WorkspaceManager::append_events(&log, &[], 0).unwrap();
+        assert_eq!(read_events(&log).len(), MAX_EVENTS + 5);
+
+        let extra = WorkspaceEvent::now(EventKind::Accessed, Path::new("/extra"));
+        WorkspaceManager::append_events(&log, &[extra], 1 << 10).unwrap();
+        let logged = read_events(&log);
+        assert_eq!(logged.len(), MAX_EVENTS);
+        assert_eq!(logged[0].path, PathBuf::from("/w6"));
+        assert_eq!(logged.last().unwrap().path, PathBuf::from("/extra"));
     }
 
     #[test]
-    fn bash_join_uses_and_chain() {
-        let g = BashGenerator;
-        let joined = g.join(&["a".to_string(), "b".to_string()]);
-        assert!(joined.contains("&&"));
-        assert!(joined.contains('a') && joined.contains('b'));
+    fn workspace_v1_file_migrates_without_access_times() {
+        let dir = unique_tmp_dir("ws-v1");
+        let cfg = dir.join("workspaces");
+        fs::write(&cfg, format!("{}1\n/a\n/b\n", WORKSPACES_HEADER)).unwrap();
+
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                WorkspaceRecord {
+                    path: PathBuf::from("/a"),
+                    ..Default::default()
+                },
+                WorkspaceRecord {
+                    path: PathBuf::from("/b"),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert!(dir.join("workspaces.v1.bak").exists());
     }
 
     #[test]
-    fn bash_git_clone_with_and_without_proxy() {
-        let g = BashGenerator;
-        let plain = g.git_clone("https://x/y.git", Path::new("/d"), None);
-        assert_eq!(plain, "git clone 'https://x/y.git' '/d'");
-        let proxied = g.git_clone("https://x/y.git", Path::new("/d"), Some("proxychains"));
-        assert!(proxied.starts_with("proxychains git clone"));
+    fn workspace_record_line_roundtrip() {
+        let record = WorkspaceRecord {
+            path: PathBuf::from("/tmp/with\ttab"),
+            last_accessed: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            group: Some("work".to_string()),
+            sort_weight: -2.5,
+            source: None,
+        };
+        assert_eq!(record.to_line(), "1700000000\twork\t-2.5\t/tmp/with\ttab");
+        assert_eq!(WorkspaceRecord::parse_line(&record.to_line()), Some(record));
+        let plain = WorkspaceRecord::parse_line("-\t\t\t/a").unwrap();
+        assert_eq!((plain.group, plain.sort_weight), (None, 0.0));
+        assert_eq!(WorkspaceRecord::parse_line("-\t\tNaN\t/a"), None);
+        assert_eq!(WorkspaceRecord::parse_line("/no/time"), None);
     }
 
     #[test]
-    fn bash_init_script_shape() {
-        let g = BashGenerator;
-        let s = g.init_script("try", "/usr/local/bin/try", "~/experiments");
-        assert!(s.contains("try()"));
-        assert!(s.contains("eval \"$out\""));
-        assert!(s.contains(r#"export TRY_PATH="~/experiments""#));
-        assert!(s.contains(r#"export TRY_SHELL="bash""#));
+    fn shared_workspaces_merge_after_the_history_and_stay_read_only() {
+        let dir = unique_tmp_dir("ws-shared");
+        let cfg = dir.join("workspaces");
+        let shared = dir.join("workspaces.d");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(
+            shared.join("team.txt"),
+            "# common roots\n/srv/ci\n\n/home/me/mine\n",
+        )
+        .unwrap();
+        fs::write(
+            shared.join("ci.toml"),
+            "[[workspace]]\npath = \"/srv/builds\"\ngroup = \"ci\"\n",
+        )
+        .unwrap();
+        fs::write(shared.join("README.md"), "/not/a/workspace\n").unwrap();
+        WorkspaceManager::save_records_to(
+            &cfg,
+            &[WorkspaceRecord {
+                path: PathBuf::from("/home/me/mine"),
+                ..Default::default()
+            }],
+        )
+        .unwrap();
+
+        let records = WorkspaceManager::get_merged_records_from(&cfg).unwrap();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|r| {
+                (
+                    r.path.to_str().unwrap(),
+                    r.source.as_deref(),
+                    r.group.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("/home/me/mine", None, None),
+                ("/srv/builds", Some("ci.toml"), Some("ci")),
+                ("/srv/ci", Some("team.txt"), None),
+            ]
+        );
+        assert_eq!(WorkspaceManager::get_records_from(&cfg).unwrap().len(), 1);
+
+        // A broken drop-in is reported and left out; the others still load.
+        fs::write(shared.join("bad.toml"), "[[workspace]]\nname = \"x\"\n").unwrap();
+        let mut skipped = Vec::new();
+        let records = WorkspaceManager::get_shared_records_from(&shared, &mut skipped).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(
+            matches!(skipped.as_slice(), [TryError::ConfigCorrupted(p, _)] if p.ends_with("bad.toml"))
+        );
+        assert_eq!(
+            WorkspaceManager::get_merged_records_from(&cfg)
+                .unwrap()
+                .len(),
+            3
+        );
     }
 
     #[test]
-    fn bash_init_script_custom_name() {
-        let g = BashGenerator;
-        let s = g.init_script("t", "/usr/local/bin/try", "~/x");
-        assert!(s.contains("t()"));
+    fn shared_workspaces_cannot_be_marked_for_deletion() {
+        let records = vec![WorkspaceRecord {
+            path: PathBuf::from("/srv/ci"),
+            source: Some("team.txt".to_string()),
+            ..Default::default()
+        }];
+        let mut sel = TrySelector::new(SelectorMode::History(records), String::new(), "/".into());
+        sel.load_entries().unwrap();
+        sel.refresh_scores();
+        sel.toggle_delete_mark();
+        assert!(sel.marked_for_deletion.is_empty());
+        assert!(sel
+            .status
+            .as_deref()
+            .unwrap()
+            .contains("workspaces.d/team.txt"));
     }
 
     #[test]
-    fn powershell_cd_and_mkdir() {
-        let g = PowerShellGenerator;
+    fn touch_workspace_stamps_the_innermost_match_in_place() {
+        let dir = unique_tmp_dir("ws-touch");
+        let cfg = dir.join("workspaces");
+        let (outer, inner) = (dir.join("outer"), dir.join("outer").join("inner"));
+        fs::create_dir_all(inner.join("src")).unwrap();
+        let record = |path: &Path| WorkspaceRecord {
+            path: canonicalize_clean(path),
+            ..Default::default()
+        };
+        WorkspaceManager::save_records_to(&cfg, &[record(&outer), record(&inner)]).unwrap();
+
+        let touched = WorkspaceManager::touch_workspace_to(&cfg, &inner.join("src")).unwrap();
+        assert_eq!(touched, Some(canonicalize_clean(&inner)));
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records[0].path, canonicalize_clean(&outer));
+        assert_eq!(records[0].last_accessed, None);
+        assert!(records[1].last_accessed.is_some());
+
         assert_eq!(
-            g.cd(Path::new("C:/tmp/x")),
-            "Set-Location -LiteralPath 'C:/tmp/x'"
+            WorkspaceManager::touch_workspace_to(&cfg, &dir).unwrap(),
+            None
         );
-        assert!(g
-            .mkdir(Path::new("C:/tmp/x"))
-            .starts_with("New-Item -ItemType Directory"));
-        assert!(g.touch(Path::new("C:/tmp/x")).contains("LastWriteTime"));
+        assert_eq!(WorkspaceManager::get_records_from(&cfg).unwrap().len(), 2);
     }
 
     #[test]
-    fn powershell_escape_doubles_single_quote() {
-        let g = PowerShellGenerator;
-        assert_eq!(g.escape(Path::new("C:/it's")), "C:/it''s");
+    fn workspace_v3_file_migrates_without_weights() {
+        let dir = unique_tmp_dir("ws-v3");
+        let cfg = dir.join("workspaces");
+        fs::write(&cfg, format!("{}3\n100\twork\t/a\n", WORKSPACES_HEADER)).unwrap();
+
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records[0].group.as_deref(), Some("work"));
+        assert_eq!(records[0].sort_weight, 0.0);
+        assert_eq!(records[0].path, PathBuf::from("/a"));
+        assert!(dir.join("workspaces.v3.bak").exists());
     }
 
     #[test]
-    fn powershell_escape_normalizes_backslashes() {
-        let g = PowerShellGenerator;
-        assert_eq!(g.escape(Path::new(r"C:\a\b")), "C:/a/b");
+    fn workspace_weight_is_set_by_name_and_survives_set() {
+        let dir = unique_tmp_dir("ws-weight");
+        let cfg = dir.join("workspaces");
+        let (a, b) = (dir.join("alpha"), dir.join("beta"));
+        WorkspaceManager::add_workspace_to(&cfg, &a, None).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &b, None).unwrap();
+
+        let path = WorkspaceManager::set_weight_to(&cfg, "alpha", 5.0).unwrap();
+        assert_eq!(path, canonicalize_clean(&a));
+        WorkspaceManager::set_weight_to(&cfg, &b.to_string_lossy(), -1.0).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &a, None).unwrap();
+        let weights: Vec<f64> = WorkspaceManager::get_records_from(&cfg)
+            .unwrap()
+            .iter()
+            .map(|r| r.sort_weight)
+            .collect();
+        assert_eq!(weights, [5.0, -1.0]);
+
+        let err = WorkspaceManager::set_weight_to(&cfg, "gamma", 1.0).unwrap_err();
+        assert!(matches!(err, TryError::WorkspaceNotFound(_)));
     }
 
     #[test]
-    fn powershell_join_uses_semicolons() {
-        let g = PowerShellGenerator;
-        assert_eq!(g.join(&["a".to_string(), "b".to_string()]), "a; b");
+    fn history_matches_in_the_last_component_rank_first() {
+        let dir = unique_tmp_dir("leaf-score");
+        let records = [("/work/projects/api", 1), ("/work/old/proj-x", 2000)]
+            .iter()
+            .map(|(p, h)| WorkspaceRecord {
+                path: PathBuf::from(p),
+                last_accessed: Some(hours_ago(*h)),
+                ..Default::default()
+            })
+            .collect();
+        let mut sel = TrySelector::new(SelectorMode::History(records), "proj".into(), dir);
+        sel.load_entries().unwrap();
+        sel.refresh_scores();
+        let order: Vec<_> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.path.clone())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                PathBuf::from("/work/old/proj-x"),
+                PathBuf::from("/work/projects/api")
+            ]
+        );
     }
 
     #[test]
-    fn powershell_set_env() {
-        let g = PowerShellGenerator;
-        assert_eq!(g.set_env("TRY_PATH", "C:/x"), "$env:TRY_PATH = 'C:/x'");
+    fn sort_weight_reorders_without_hiding_matches() {
+        let dir = unique_tmp_dir("weight-order");
+        let records = [("first", 0.0), ("sunk", -50.0), ("lifted", 50.0)]
+            .iter()
+            .map(|(n, w)| WorkspaceRecord {
+                path: Path::new("/w").join(n),
+                sort_weight: *w,
+                ..Default::default()
+            })
+            .collect();
+        let mut sel = TrySelector::new(SelectorMode::History(records), String::new(), dir);
+        sel.load_entries().unwrap();
+        sel.refresh_scores();
+        let order: Vec<_> = sel
+            .entries
+            .iter()
+            .map(|e| e.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(order, ["lifted", "first", "sunk"]);
+
+        sel.input_buffer = "s".to_string();
+        sel.refresh_scores();
+        let shown: Vec<_> = sel
+            .get_filtered_entries()
+            .iter()
+            .map(|e| e.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(shown, ["first", "sunk"]);
     }
 
-    #[test]
-    fn powershell_init_script_shape() {
-        let g = PowerShellGenerator;
-        let s = g.init_script("tr", r"C:\bin\try.exe", r"C:\experiments");
-        assert!(s.contains("function tr"));
-        // must NOT define a function literally named `try` (reserved keyword)
-        assert!(!s.contains("function try"));
-        assert!(s.contains("Invoke-Expression"));
-        assert!(s.contains("$LASTEXITCODE"));
-        assert!(s.contains("$env:TRY_SHELL = 'powershell'"));
+    /// `n` made-up entries that don't exist on disk, spread over a year.
+    fn synthetic_selector(n: usize) -> TrySelector {
+        let words = [
+            "api", "proj", "demo", "rust", "web", "spike", "data", "infra",
+        ];
+        let mut sel = TrySelector::new(
+            SelectorMode::Scan(PathBuf::from("/tries")),
+            String::new(),
+            PathBuf::from("/tries"),
+        );
+        sel.width = 100;
+        sel.height = 40;
+        sel.entries = (0..n)
+            .map(|i| {
+                let name = format!(
+                    "{}-{}-{}-2024-{:02}-{:02}",
+                    words[i % words.len()],
+                    words[(i / words.len()) % words.len()],
+                    i,
+                    i % 12 + 1,
+                    i % 28 + 1
+                );
+                entry(&name, hours_ago((i * 7 % 8760) as u64))
+            })
+            .collect();
+        sel
     }
 
     #[test]
-    fn default_fn_name_per_shell() {
-        assert_eq!(default_fn_name(Shell::Bash), "try");
-        assert_eq!(default_fn_name(Shell::PowerShell), "tr");
+    fn large_lists_keep_matches_in_score_order() {
+        let mut sel = synthetic_selector(500);
+        sel.refresh_scores();
+        for query in ["p", "pr", "prd", ""] {
+            sel.input_buffer = query.to_string();
+            sel.refresh_scores();
+            let shown = sel.get_filtered_entries();
+            assert!(shown.windows(2).all(|w| w[0].score >= w[1].score));
+            let matching = sel
+                .entries
+                .iter()
+                .filter(|e| query.is_empty() || e.score > 0.0)
+                .count();
+            assert_eq!(shown.len(), matching);
+        }
     }
 
+    /// Typing into a 5,000-entry list: rescoring and redrawing should stay
+    /// well under 50ms a keystroke, past which typing lags. Wall-clock time
+    /// is too noisy on shared CI to assert; run with
+    /// `cargo test --release -- --ignored --nocapture bench_`.
     #[test]
-    fn build_action_script_cd_bash() {
-        let g = BashGenerator;
-        let s = build_action_script(&g, &ShellAction::Cd(PathBuf::from("/tmp/x")));
-        assert!(s.contains("touch '/tmp/x'"));
-        assert!(s.contains("cd '/tmp/x'"));
+    #[ignore]
+    fn bench_typing_into_a_large_list() {
+        let mut sel = synthetic_selector(5_000);
+        sel.refresh_scores();
+        let mut out = Vec::new();
+        let keys = ["p", "pr", "pro", "proj", "proj-", "proj-1", ""];
+        let rounds = 5;
+        let (mut scoring, mut drawing) = (Duration::ZERO, Duration::ZERO);
+        for _ in 0..rounds {
+            for query in keys {
+                sel.input_buffer = query.to_string();
+                let start = Instant::now();
+                sel.refresh_scores();
+                scoring += start.elapsed();
+                out.clear();
+                let start = Instant::now();
+                sel.render(&mut out).unwrap();
+                drawing += start.elapsed();
+            }
+        }
+        let n = rounds * keys.len() as u32;
+        eprintln!(
+            "per keystroke: refresh_scores {:?}, render {:?}",
+            scoring / n,
+            drawing / n
+        );
     }
 
-    #[test]
-    fn build_action_script_mkdircd_powershell() {
-        let g = PowerShellGenerator;
-        let s = build_action_script(&g, &ShellAction::MkdirCd(PathBuf::from("C:/tmp/x")));
-        assert!(s.contains("New-Item"));
-        assert!(s.contains("Set-Location"));
-        assert!(s.contains(';'));
+    /// Counts the writes and flushes a frame arrives in.
+    #[derive(Default)]
+    struct WriteCounter {
+        bytes: usize,
+        writes: usize,
+        flushes: usize,
+    }
+
+    impl Write for WriteCounter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.bytes += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
     }
 
     #[test]
-    fn build_action_script_set_updates_env_and_cds() {
-        let g = BashGenerator;
-        let s = build_action_script(&g, &ShellAction::Set(PathBuf::from("/tmp/ws")));
-        assert!(s.contains("export TRY_PATH='/tmp/ws'"));
-        assert!(s.contains("cd '/tmp/ws'"));
+    fn each_frame_is_one_write_and_one_flush() {
+        let mut sel = synthetic_selector(50);
+        sel.refresh_scores();
+        let mut out = WriteCounter::default();
+        sel.render(&mut out).unwrap();
+        assert_eq!((out.writes, out.flushes), (1, 1));
+        sel.height = 2;
+        sel.render(&mut out).unwrap();
+        assert_eq!((out.writes, out.flushes), (2, 2));
     }
 
+    /// One second of redraws at 60 fps over 50 entries; run with
+    /// `cargo test --release -- --ignored --nocapture bench_`.
     #[test]
-    fn build_action_script_set_powershell_updates_env() {
-        let g = PowerShellGenerator;
-        let s = build_action_script(&g, &ShellAction::Set(PathBuf::from("C:/ws")));
-        assert!(s.contains("$env:TRY_PATH = 'C:/ws'"));
-        assert!(s.contains("Set-Location -LiteralPath 'C:/ws'"));
+    #[ignore]
+    fn bench_rendering_at_60_fps() {
+        let mut sel = synthetic_selector(50);
+        sel.refresh_scores();
+        let mut out = WriteCounter::default();
+        let start = Instant::now();
+        for i in 0..60 {
+            sel.cursor_pos = i % 50;
+            sel.render(&mut out).unwrap();
+        }
+        let elapsed = start.elapsed();
+        eprintln!(
+            "60 frames: {:?} ({:?} per frame, {} bytes and {} writes per frame)",
+            elapsed,
+            elapsed / 60,
+            out.bytes / 60,
+            out.writes / 60
+        );
+        assert!(elapsed < Duration::from_secs(1));
     }
 
     #[test]
-    fn workspace_add_get_roundtrip_and_dedup_to_top() {
-        let dir = unique_tmp_dir("ws-roundtrip");
+    fn workspace_v2_file_migrates_without_groups() {
+        let dir = unique_tmp_dir("ws-v2");
         let cfg = dir.join("workspaces");
+        fs::write(&cfg, format!("{}2\n100\t/a\n-\t/b\n", WORKSPACES_HEADER)).unwrap();
 
-        let a = dir.join("a");
-        let b = dir.join("b");
-        fs::create_dir_all(&a).unwrap();
-        fs::create_dir_all(&b).unwrap();
-
-        WorkspaceManager::add_workspace_to(&cfg, &a).unwrap();
-        WorkspaceManager::add_workspace_to(&cfg, &b).unwrap();
-        WorkspaceManager::add_workspace_to(&cfg, &a).unwrap();
-
-        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
-        assert_eq!(ws.len(), 2);
-        assert_eq!(ws[0], canonicalize_clean(&a));
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.group.is_none()));
+        assert_eq!(records[1].path, PathBuf::from("/b"));
+        assert!(dir.join("workspaces.v2.bak").exists());
     }
 
     #[test]
-    fn workspace_remove() {
-        let dir = unique_tmp_dir("ws-remove");
+    fn workspace_add_keeps_group_unless_reassigned() {
+        let dir = unique_tmp_dir("ws-group");
         let cfg = dir.join("workspaces");
         let a = dir.join("a");
-        let b = dir.join("b");
-        fs::create_dir_all(&a).unwrap();
-        fs::create_dir_all(&b).unwrap();
-
-        WorkspaceManager::add_workspace_to(&cfg, &a).unwrap();
-        WorkspaceManager::add_workspace_to(&cfg, &b).unwrap();
-
-        let canon_a = canonicalize_clean(&a);
-        WorkspaceManager::remove_workspaces_from(&cfg, &[canon_a.clone()]).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &a, Some("work")).unwrap();
+        WorkspaceManager::add_workspace_to(&cfg, &a, None).unwrap();
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records[0].group.as_deref(), Some("work"));
+
+        WorkspaceManager::add_workspace_to(&cfg, &a, Some("home")).unwrap();
+        let records = WorkspaceManager::get_records_from(&cfg).unwrap();
+        assert_eq!(records[0].group.as_deref(), Some("home"));
+    }
 
-        let ws = WorkspaceManager::get_workspaces_from(&cfg).unwrap();
-        assert_eq!(ws.len(), 1);
-        assert!(!ws.contains(&canon_a));
+    #[test]
+    fn workspace_newer_version_is_rejected() {
+        let dir = unique_tmp_dir("ws-too-new");
+        let cfg = dir.join("workspaces");
+        let future = WORKSPACES_VERSION + 1;
+        fs::write(&cfg, format!("{}{}\n/a\n", WORKSPACES_HEADER, future)).unwrap();
+        let err = WorkspaceManager::get_workspaces_from(&cfg).unwrap_err();
+        assert!(matches!(err, TryError::ConfigTooNew { found, .. } if found == future));
+        // Untouched.
+        assert!(fs::read_to_string(&cfg).unwrap().contains("/a"));
     }
 
     #[test]
@@ -1650,12 +11835,22 @@ mod tests {
 
     #[test]
     fn config_path_honors_try_config_env() {
-        // Note: env mutation is process-global; keep this test self-contained.
+        // Through the lookup rather than set_var, which would leak into tests
+        // running in parallel.
         let dir = unique_tmp_dir("cfg-env");
         let custom = dir.join("custom-workspaces");
-        std::env::set_var("TRY_CONFIG", &custom);
-        assert_eq!(WorkspaceManager::get_config_path(), custom);
-        std::env::remove_var("TRY_CONFIG");
+        assert_eq!(
+            WorkspaceManager::config_path_with(Some(custom.to_string_lossy().into())),
+            custom
+        );
+        assert_eq!(
+            WorkspaceManager::config_path_with(Some("  ".into())),
+            WorkspaceManager::default_config_path()
+        );
+        assert_eq!(
+            WorkspaceManager::config_path_with(None),
+            WorkspaceManager::default_config_path()
+        );
     }
 
     #[test]